@@ -0,0 +1,237 @@
+//! Units of angle and the angle mode, which sets the unit that trigonometric
+//! functions take and their inverses return.
+//!
+//! Angle literals such as `30°`, `12°30'15"`, `30deg`, `1.2rad` or `50grad`
+//! evaluate to radians on their own. `in_angle_mode` rewrites an expression
+//! for another mode, so that the same arithmetic serves every mode.
+
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::{apply_function, AstHead, AstNode};
+
+/// The trigonometric functions, which take angles.
+const TRIGONOMETRIC: [&str; 6] = ["cos", "cot", "csc", "sec", "sin", "tan"];
+
+/// The inverse trigonometric functions, which return angles.
+const INVERSE_TRIGONOMETRIC: [&str; 6] = ["acos", "acot", "acsc", "asec", "asin", "atan"];
+
+/// The sines of the multiples of a twelfth of a turn.
+const SINES_OF_TWELFTHS: [f64; 12] = [
+  0.0,
+  0.5,
+  0.866_025_403_784_438_6,
+  1.0,
+  0.866_025_403_784_438_6,
+  0.5,
+  0.0,
+  -0.5,
+  -0.866_025_403_784_438_6,
+  -1.0,
+  -0.866_025_403_784_438_6,
+  -0.5,
+];
+
+/// A unit of angle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AngleUnit {
+  Radians,
+  /// 360 to a turn
+  Degrees,
+  /// 400 to a turn, as used in surveying
+  Gradians,
+}
+
+impl fmt::Display for AngleUnit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AngleUnit::Radians => write!(f, "rad"),
+      AngleUnit::Degrees => write!(f, "deg"),
+      AngleUnit::Gradians => write!(f, "grad"),
+    }
+  }
+}
+
+impl FromStr for AngleUnit {
+  type Err = String;
+
+  fn from_str(unit: &str) -> Result<AngleUnit, String> {
+    match unit {
+      "rad" | "radians" => Ok(AngleUnit::Radians),
+      "deg" | "degrees" | "°" => Ok(AngleUnit::Degrees),
+      "grad" | "gradians" | "gon" => Ok(AngleUnit::Gradians),
+      _ => Err(format!("Unknown angle unit '{}'; expected rad, deg or grad.", unit)),
+    }
+  }
+}
+
+impl AngleUnit {
+  /// The size of the unit in radians.
+  pub fn radians(&self) -> f64 {
+    match self {
+      AngleUnit::Radians => 1.0,
+      AngleUnit::Degrees => PI / 180.0,
+      AngleUnit::Gradians => PI / 200.0,
+    }
+  }
+
+  /// The size of a turn in the unit.
+  fn turn(&self) -> f64 {
+    match self {
+      AngleUnit::Radians => 2.0 * PI,
+      AngleUnit::Degrees => 360.0,
+      AngleUnit::Gradians => 400.0,
+    }
+  }
+}
+
+/// The sine and cosine of `x` in `unit`, which are exact at the multiples of
+/// an eighth and a twelfth of a turn, so that `cos(90°)` is 0.
+fn sin_cos(x: f64, unit: AngleUnit) -> (f64, f64) {
+  let reduced = x.rem_euclid(unit.turn());
+  let twelfths = reduced * 12.0 / unit.turn();
+  let eighths = reduced * 8.0 / unit.turn();
+  if twelfths.fract() == 0.0 {
+    let k = twelfths as usize;
+    (SINES_OF_TWELFTHS[k % 12], SINES_OF_TWELFTHS[(k + 3) % 12])
+  } else if eighths.fract() == 0.0 {
+    // The odd eighths, 45° in each quadrant
+    let (sin, cos) = [(1.0, 1.0), (1.0, -1.0), (-1.0, -1.0), (-1.0, 1.0)][eighths as usize / 2];
+    (sin * FRAC_1_SQRT_2, cos * FRAC_1_SQRT_2)
+  } else {
+    let radians = reduced * unit.radians();
+    (radians.sin(), radians.cos())
+  }
+}
+
+/// The trigonometric function `name` of `x` in `unit`, or the inverse
+/// function `name` of `x` with its result in `unit`, in floating point.
+/// Degrees and gradians are exact at the angles where the function takes a
+/// simple value, and the results of inverses keep 15 significant digits,
+/// hiding the error of converting them from radians.
+pub fn trigonometric(name: &str, x: f64, unit: AngleUnit) -> Option<f64> {
+  if unit == AngleUnit::Radians {
+    return apply_function(name, x);
+  }
+  if INVERSE_TRIGONOMETRIC.contains(&name) {
+    let angle = apply_function(name, x)? / unit.radians();
+    return Some(format!("{:.14e}", angle).parse().unwrap_or(angle));
+  }
+  let (sin, cos) = sin_cos(x, unit);
+  match name {
+    "sin" => Some(sin),
+    "cos" => Some(cos),
+    "tan" => Some(sin / cos),
+    "cot" => Some(cos / sin),
+    "sec" => Some(cos.recip()),
+    "csc" => Some(sin.recip()),
+    _ => None,
+  }
+}
+
+/// The trigonometric function `name` of `x` in `unit`, or its inverse with
+/// the result in `unit`, in any arithmetic by converting to and from
+/// radians.
+pub fn convert_trigonometric<A: Arithmetic + ?Sized>(
+  arithmetic: &A,
+  name: &str,
+  x: &A::Value,
+  unit: AngleUnit,
+) -> Result<A::Value, String> {
+  let radians = arithmetic.number(unit.radians())?;
+  if INVERSE_TRIGONOMETRIC.contains(&name) {
+    arithmetic.divide(&arithmetic.function(name, x)?, &radians)
+  } else {
+    arithmetic.function(name, &arithmetic.multiply(x, &radians)?)
+  }
+}
+
+/// Rewrites `ast` to measure angles in `mode`: angle literals become numbers
+/// in `mode`, and trigonometric functions and their inverses measure angles
+/// in `mode`, except that a function of a literal measures it in the unit of
+/// the literal, so that `sin 30°` is exactly 0.5 in any mode.
+pub fn in_angle_mode(ast: &AstNode, mode: AngleUnit) -> AstNode {
+  if let (AstHead::Function(name), [literal]) = (ast.head(), ast.tail()) {
+    if let AstHead::Angle(unit) = literal.head() {
+      if TRIGONOMETRIC.contains(&name.as_str()) {
+        let trigonometric = AstHead::Trigonometric(name.clone(), *unit);
+        return AstNode::new(trigonometric, literal.tail().to_vec());
+      }
+    }
+  }
+  let tail: Vec<AstNode> = ast.tail().iter().map(|node| in_angle_mode(node, mode)).collect();
+  match (ast.head(), tail.as_slice()) {
+    (AstHead::Angle(unit), [x]) if *unit == mode => x.clone(),
+    (AstHead::Angle(unit), [x]) => {
+      AstNode::times(vec![x.clone(), AstNode::number(unit.radians() / mode.radians())])
+    }
+    (AstHead::Function(name), [_]) if mode != AngleUnit::Radians && is_trigonometric(name) => {
+      AstNode::new(AstHead::Trigonometric(name.clone(), mode), tail)
+    }
+    _ => AstNode::new(ast.head().clone(), tail),
+  }
+}
+
+/// Tests whether `name` is a trigonometric function or an inverse one.
+fn is_trigonometric(name: &str) -> bool {
+  TRIGONOMETRIC.contains(&name) || INVERSE_TRIGONOMETRIC.contains(&name)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::angle::{in_angle_mode, AngleUnit};
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use crate::lib::value::{FloatArithmetic, Value};
+  use std::collections::HashMap;
+  use std::f64::consts::{FRAC_PI_6, PI};
+
+  fn evaluate(input: &str, mode: AngleUnit) -> f64 {
+    let tokens = Lexer::lex(input).unwrap();
+    let ast = in_angle_mode(&Parser::parse(&tokens).unwrap(), mode);
+    match FloatArithmetic::default().evaluate(&ast, &mut HashMap::new()) {
+      Ok(Value::Number(x)) => x,
+      result => panic!("Expected a number, not {:?}", result),
+    }
+  }
+
+  fn assert_close(x: f64, y: f64) {
+    assert!((x - y).abs() < 1e-12, "{} is not close to {}", x, y);
+  }
+
+  #[test]
+  fn literals() {
+    assert_close(evaluate("30°", AngleUnit::Radians), FRAC_PI_6);
+    assert_close(evaluate("30deg", AngleUnit::Radians), FRAC_PI_6);
+    assert_eq!(evaluate("1.2rad", AngleUnit::Radians), 1.2);
+    assert_eq!(evaluate("30°", AngleUnit::Degrees), 30.0);
+    assert_eq!(evaluate("12°30'15\"", AngleUnit::Degrees), 12.504_166_666_666_666);
+    assert_eq!(evaluate("-12°30'", AngleUnit::Degrees), -12.5);
+    assert_close(evaluate("1rad", AngleUnit::Degrees), 180.0 / PI);
+    assert_close(evaluate("100grad", AngleUnit::Degrees), 90.0);
+    assert_close(evaluate("90°", AngleUnit::Gradians), 100.0);
+  }
+
+  #[test]
+  fn trigonometric_functions() {
+    assert_eq!(evaluate("sin(30)", AngleUnit::Degrees), 0.5);
+    assert_eq!(evaluate("cos(90)", AngleUnit::Degrees), 0.0);
+    assert_eq!(evaluate("tan(-315)", AngleUnit::Degrees), 1.0);
+    assert_eq!(evaluate("cos(200)", AngleUnit::Gradians), -1.0);
+    assert_eq!(evaluate("asin(0.5)", AngleUnit::Degrees), 30.0);
+    assert_eq!(evaluate("acos(0.5)", AngleUnit::Degrees), 60.0);
+    assert_close(evaluate("sin(30)", AngleUnit::Degrees), 0.5);
+    assert_eq!(evaluate("sin 30°", AngleUnit::Radians), 0.5);
+    assert_eq!(evaluate("cos(100grad)", AngleUnit::Degrees), 0.0);
+    assert_close(evaluate("cos(100)", AngleUnit::Gradians), 0.0);
+    assert_close(evaluate("tan(1.2rad)", AngleUnit::Degrees), 1.2f64.tan());
+    assert_close(evaluate("asin(0.5)", AngleUnit::Degrees), 30.0);
+    assert_close(evaluate("atan(1)", AngleUnit::Gradians), 50.0);
+    assert_close(evaluate("acot(1)", AngleUnit::Degrees), 45.0);
+    assert_close(evaluate("sinh(1)", AngleUnit::Degrees), 1f64.sinh());
+    assert_close(evaluate("sin(asin(0.3))", AngleUnit::Degrees), 0.3);
+  }
+}
@@ -0,0 +1,537 @@
+//! Evaluation of ASTs in each arithmetic, such as floating point, decimal or
+//! interval arithmetic.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::lib::angle::{convert_trigonometric, AngleUnit};
+use crate::lib::ast::{AstHead, AstNode, Bitwise, Comparison};
+
+/// An arithmetic in which an AST can be evaluated. Implementors provide the
+/// operations on their values; the traversal of the tree is shared.
+pub trait Arithmetic {
+  /// The values of this arithmetic
+  type Value: Clone;
+
+  /// Converts a number literal.
+  fn number(&self, value: f64) -> Result<Self::Value, String>;
+
+  /// Converts an integer literal, which may not be exact as a number.
+  fn integer(&self, value: u128) -> Result<Self::Value, String> {
+    self.number(value as f64)
+  }
+
+  /// Converts a number literal with more significant digits than `f64`
+  /// keeps, given as written.
+  fn decimal(&self, text: &str) -> Result<Self::Value, String> {
+    match text.parse() {
+      Ok(value) => self.number(value),
+      Err(_) => Err(format!("Failed to parse '{}' as a number.", text)),
+    }
+  }
+
+  /// Looks up a named constant such as `pi`.
+  fn constant(&self, name: &str) -> Result<Self::Value, String>;
+
+  /// Adds two values.
+  fn add(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String>;
+
+  /// Multiplies two values.
+  fn multiply(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String>;
+
+  /// Divides two values.
+  fn divide(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String>;
+
+  /// Negates a value.
+  fn negate(&self, x: &Self::Value) -> Result<Self::Value, String> {
+    self.multiply(&self.number(-1.0)?, x)
+  }
+
+  /// Subtracts `b` from `a`.
+  fn subtract(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String> {
+    self.add(a, &self.negate(b)?)
+  }
+
+  /// Raises `base` to `exponent`.
+  fn power(&self, base: &Self::Value, exponent: &Self::Value) -> Result<Self::Value, String>;
+
+  /// The quotient `a // b` rounded down.
+  fn floor_divide(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String> {
+    self.function("floor", &self.divide(a, b)?)
+  }
+
+  /// The remainder `a mod b = a - b (a // b)`, which has the sign of `b`.
+  fn modulo(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, String> {
+    self.subtract(a, &self.multiply(b, &self.floor_divide(a, b)?)?)
+  }
+
+  /// The percentage `x%`.
+  fn percent(&self, x: &Self::Value) -> Result<Self::Value, String> {
+    self.divide(x, &self.number(100.0)?)
+  }
+
+  /// Applies the bitwise operator `operator` to two integers.
+  fn bitwise(
+    &self,
+    _operator: Bitwise,
+    _a: &Self::Value,
+    _b: &Self::Value,
+  ) -> Result<Self::Value, String> {
+    Err("Bitwise operators are only supported in programmer mode.".to_string())
+  }
+
+  /// The bitwise complement `~x`.
+  fn complement(&self, _x: &Self::Value) -> Result<Self::Value, String> {
+    Err("Bitwise operators are only supported in programmer mode.".to_string())
+  }
+
+  /// Applies the built-in function `name`.
+  fn function(&self, name: &str, argument: &Self::Value) -> Result<Self::Value, String>;
+
+  /// Applies the trigonometric function `name` to an angle in `unit`, or its
+  /// inverse with the result in `unit`. Converts to and from radians by
+  /// default.
+  fn trigonometric(
+    &self,
+    name: &str,
+    x: &Self::Value,
+    unit: AngleUnit,
+  ) -> Result<Self::Value, String> {
+    convert_trigonometric(self, name, x, unit)
+  }
+
+  /// Applies the built-in function `name` to its arguments. Functions of one
+  /// argument go to `function`.
+  fn call(&self, name: &str, arguments: &[Self::Value]) -> Result<Self::Value, String> {
+    match arguments {
+      [argument] => self.function(name, argument),
+      _ => Err(format!(
+        "{} takes one argument, not {}.",
+        name,
+        arguments.len()
+      )),
+    }
+  }
+
+  /// Looks up a name that is not a variable, such as a unit.
+  fn identifier(&self, name: &str) -> Result<Self::Value, String> {
+    Err(format!("Unknown variable {}", name))
+  }
+
+  /// Builds a matrix from its entries in row-major order. Only a single row
+  /// of two entries, which is an interval `[lo, hi]`, is supported unless the
+  /// arithmetic has matrices.
+  fn matrix(
+    &self,
+    rows: usize,
+    columns: usize,
+    entries: &[Self::Value],
+  ) -> Result<Self::Value, String> {
+    match ((rows, columns), entries) {
+      ((1, 2), [lo, hi]) => self.interval(lo, hi),
+      _ => Err("Matrices are only supported in floating point mode.".to_string()),
+    }
+  }
+
+  /// Builds a list.
+  fn list(&self, _items: Vec<Self::Value>) -> Result<Self::Value, String> {
+    Err("Lists are only supported in floating point mode.".to_string())
+  }
+
+  /// The items of a list, e.g. for `map`.
+  fn items(&self, _value: &Self::Value) -> Result<Vec<Self::Value>, String> {
+    Err("Lists are only supported in floating point mode.".to_string())
+  }
+
+  /// Builds the list of values from `start` to `end` inclusive, spaced by
+  /// `step`.
+  fn range(
+    &self,
+    _start: &Self::Value,
+    _end: &Self::Value,
+    _step: Option<&Self::Value>,
+  ) -> Result<Self::Value, String> {
+    Err("Ranges are only supported in floating point mode.".to_string())
+  }
+
+  /// Looks up the `index`th item of `value`.
+  fn index(&self, _value: &Self::Value, _index: &Self::Value) -> Result<Self::Value, String> {
+    Err("Indexing is only supported in floating point mode.".to_string())
+  }
+
+  /// Converts a truth value, which is 1 or 0 unless the arithmetic has
+  /// booleans.
+  fn boolean(&self, value: bool) -> Result<Self::Value, String> {
+    self.number(if value { 1.0 } else { 0.0 })
+  }
+
+  /// Orders two values for comparisons, or returns `None` if they have no
+  /// order, as NaN has none.
+  fn order(&self, _a: &Self::Value, _b: &Self::Value) -> Result<Option<Ordering>, String> {
+    Err("Comparisons are not supported in this mode.".to_string())
+  }
+
+  /// Compares two values by their order.
+  fn compare(
+    &self,
+    comparison: Comparison,
+    a: &Self::Value,
+    b: &Self::Value,
+  ) -> Result<Self::Value, String> {
+    self.boolean(comparison.holds(self.order(a, b)?))
+  }
+
+  /// Decides whether a value counts as true, e.g. for `filter` or `if`.
+  /// Nonzero values are true by default.
+  fn truth(&self, value: &Self::Value) -> Result<bool, String> {
+    Ok(self.order(value, &self.number(0.0)?)? != Some(Ordering::Equal))
+  }
+
+  /// Builds the interval `[lo, hi]`.
+  fn interval(&self, _lo: &Self::Value, _hi: &Self::Value) -> Result<Self::Value, String> {
+    Err("Intervals are only supported in interval mode.".to_string())
+  }
+
+  /// Builds the value `value ± error`.
+  fn plus_minus(&self, _value: &Self::Value, _error: &Self::Value) -> Result<Self::Value, String> {
+    Err("'±' is not supported in this mode.".to_string())
+  }
+
+  /// Converts `value` to the unit `unit`, which is written `name`.
+  fn convert(
+    &self,
+    _value: &Self::Value,
+    _unit: &Self::Value,
+    _name: &str,
+  ) -> Result<Self::Value, String> {
+    Err("Unit conversions are only supported in units mode.".to_string())
+  }
+
+  /// Fits the parameters of `model`, an expression in `variable` and the
+  /// names that are not variables in `memory`, to the points `(xs, ys)`.
+  fn fit(
+    &self,
+    _model: &AstNode,
+    _variable: &str,
+    _xs: &Self::Value,
+    _ys: &Self::Value,
+    _memory: &HashMap<String, Self::Value>,
+  ) -> Result<Self::Value, String> {
+    Err("Curve fitting is only supported in floating point mode.".to_string())
+  }
+
+  /// Post-processes a result before it is shown or assigned to a variable.
+  fn finish(&self, value: Self::Value) -> Self::Value {
+    value
+  }
+
+  /// Evaluates the AST using the state defined in `memory`.
+  fn evaluate(
+    &self,
+    ast: &AstNode,
+    memory: &mut HashMap<String, Self::Value>,
+  ) -> Result<Self::Value, String> {
+    evaluate_node(self, ast, memory).map(|value| self.finish(value))
+  }
+}
+
+/// Evaluates an AST node without finishing the result.
+fn evaluate_node<A: Arithmetic + ?Sized>(
+  arithmetic: &A,
+  ast: &AstNode,
+  memory: &mut HashMap<String, A::Value>,
+) -> Result<A::Value, String> {
+  let tail = ast.tail();
+  let evaluate_tail = |memory: &mut HashMap<String, A::Value>| {
+    tail
+      .iter()
+      .map(|arg| evaluate_node(arithmetic, arg, memory))
+      .collect::<Result<Vec<A::Value>, String>>()
+  };
+  match ast.head() {
+    AstHead::Plus => {
+      let mut acc = match tail.first() {
+        Some(first) => evaluate_node(arithmetic, first, memory)?,
+        None => arithmetic.number(0.0)?,
+      };
+      for arg in tail.iter().skip(1) {
+        // Subtract directly instead of adding a negation.
+        acc = match negation_of(arg) {
+          Some(b) => arithmetic.subtract(&acc, &evaluate_node(arithmetic, b, memory)?)?,
+          None => arithmetic.add(&acc, &evaluate_node(arithmetic, arg, memory)?)?,
+        };
+      }
+      Ok(acc)
+    }
+    AstHead::Times => {
+      if let Some(x) = negation_of(ast) {
+        // A negative literal is a single number, as `-128` must be in `i8`.
+        return match x.head() {
+          AstHead::Number(value) => arithmetic.number(-value),
+          _ => arithmetic.negate(&evaluate_node(arithmetic, x, memory)?),
+        };
+      }
+      let mut acc = arithmetic.number(1.0)?;
+      for arg in tail.iter() {
+        // Divide directly instead of multiplying by a reciprocal.
+        acc = match reciprocal_of(arg) {
+          Some(denominator) => {
+            arithmetic.divide(&acc, &evaluate_node(arithmetic, denominator, memory)?)?
+          }
+          None => arithmetic.multiply(&acc, &evaluate_node(arithmetic, arg, memory)?)?,
+        };
+      }
+      Ok(acc)
+    }
+    AstHead::Power => {
+      let mut values = evaluate_tail(memory)?;
+      let last = match values.pop() {
+        Some(last) => last,
+        None => arithmetic.number(1.0)?,
+      };
+      values
+        .iter()
+        .rev()
+        .try_fold(last, |exponent, base| arithmetic.power(base, &exponent))
+    }
+    AstHead::PlusMinus => match evaluate_tail(memory)?.as_slice() {
+      [value, error] => arithmetic.plus_minus(value, error),
+      _ => unreachable!(),
+    },
+    AstHead::Modulo => match evaluate_tail(memory)?.as_slice() {
+      [a, b] => arithmetic.modulo(a, b),
+      _ => unreachable!(),
+    },
+    AstHead::FloorDivide => match evaluate_tail(memory)?.as_slice() {
+      [a, b] => arithmetic.floor_divide(a, b),
+      _ => unreachable!(),
+    },
+    AstHead::Percent => match evaluate_tail(memory)?.as_slice() {
+      [x] => arithmetic.percent(x),
+      _ => unreachable!(),
+    },
+    AstHead::Bitwise(operator) => match evaluate_tail(memory)?.as_slice() {
+      [a, b] => arithmetic.bitwise(*operator, a, b),
+      _ => unreachable!(),
+    },
+    AstHead::Complement => match evaluate_tail(memory)?.as_slice() {
+      [x] => arithmetic.complement(x),
+      _ => unreachable!(),
+    },
+    AstHead::Comparison(comparison) => match evaluate_tail(memory)?.as_slice() {
+      [a, b] => arithmetic.compare(*comparison, a, b),
+      _ => unreachable!(),
+    },
+    // The second operand of `and` and `or` is only evaluated if it decides
+    // the result.
+    AstHead::And | AstHead::Or => match tail {
+      [a, b] => {
+        let a = arithmetic.truth(&evaluate_node(arithmetic, a, memory)?)?;
+        if a == (*ast.head() == AstHead::Or) {
+          arithmetic.boolean(a)
+        } else {
+          arithmetic.boolean(arithmetic.truth(&evaluate_node(arithmetic, b, memory)?)?)
+        }
+      }
+      _ => unreachable!(),
+    },
+    AstHead::Not => match evaluate_tail(memory)?.as_slice() {
+      [x] => arithmetic.boolean(!arithmetic.truth(x)?),
+      _ => unreachable!(),
+    },
+    AstHead::Conditional => match tail {
+      [condition, then, otherwise] => {
+        if arithmetic.truth(&evaluate_node(arithmetic, condition, memory)?)? {
+          evaluate_node(arithmetic, then, memory)
+        } else {
+          evaluate_node(arithmetic, otherwise, memory)
+        }
+      }
+      _ => unreachable!(),
+    },
+    AstHead::Matrix(rows, columns) => arithmetic.matrix(*rows, *columns, &evaluate_tail(memory)?),
+    AstHead::List => arithmetic.list(evaluate_tail(memory)?),
+    AstHead::Range => match evaluate_tail(memory)?.as_slice() {
+      [start, end] => arithmetic.range(start, end, None),
+      [start, end, step] => arithmetic.range(start, end, Some(step)),
+      _ => unreachable!(),
+    },
+    AstHead::Index => match evaluate_tail(memory)?.as_slice() {
+      [value, index] => arithmetic.index(value, index),
+      _ => unreachable!(),
+    },
+    AstHead::Lambda(_) => Err(format!(
+      "The function {} can only be an argument of map, filter or reduce.",
+      ast
+    )),
+    AstHead::Convert => {
+      let (expr, target) = match tail {
+        [expr, target] => (expr, target),
+        _ => unreachable!(),
+      };
+      let value = evaluate_node(arithmetic, expr, memory)?;
+      // Names in the target are units even if a variable has the same name.
+      let unit = evaluate_node(arithmetic, target, &mut HashMap::new())?;
+      arithmetic.convert(&value, &unit, &target.to_unit_string())
+    }
+    AstHead::Assign => {
+      let name = match tail.first().map(AstNode::head) {
+        Some(AstHead::Identifier(name)) => name.clone(),
+        _ => unreachable!(),
+      };
+      let expr = tail
+        .get(1)
+        .expect("Should have been a value as the second child an assignment.");
+      let value = arithmetic.finish(evaluate_node(arithmetic, expr, memory)?);
+      memory.insert(name, value.clone());
+      Ok(value)
+    }
+    AstHead::Number(value) => arithmetic.number(*value),
+    AstHead::Boolean(value) => arithmetic.boolean(*value),
+    AstHead::Integer(value) => arithmetic.integer(*value),
+    AstHead::Decimal(text) => arithmetic.decimal(text),
+    // An angle on its own is in radians; `in_angle_mode` rewrites it for
+    // other modes.
+    AstHead::Angle(unit) => {
+      let value = evaluate_tail(memory)?.remove(0);
+      arithmetic.multiply(&value, &arithmetic.number(unit.radians())?)
+    }
+    AstHead::Constant(name) => arithmetic.constant(name),
+    AstHead::Trigonometric(name, unit) => {
+      arithmetic.trigonometric(name, &evaluate_tail(memory)?.remove(0), *unit)
+    }
+    AstHead::Function(name) if HIGHER_ORDER.contains(&name.as_str()) => {
+      higher_order(arithmetic, name, tail, memory)
+    }
+    AstHead::Function(name) if name == "fit" => match tail {
+      [model, variable, xs, ys] => match variable.head() {
+        AstHead::Identifier(variable) => {
+          let xs = evaluate_node(arithmetic, xs, memory)?;
+          let ys = evaluate_node(arithmetic, ys, memory)?;
+          arithmetic.fit(model, variable, &xs, &ys, memory)
+        }
+        _ => Err(format!("The second argument of fit must be a variable, not {}.", variable)),
+      },
+      _ => Err("fit takes a model, its variable and the points, as in \
+                fit(a*exp(b*x), x, xs, ys)."
+        .to_string()),
+    },
+    AstHead::Function(name) => arithmetic.call(name, &evaluate_tail(memory)?),
+    AstHead::Identifier(name) => match memory.get(name) {
+      Some(value) => Ok(value.clone()),
+      None => arithmetic.identifier(name),
+    },
+  }
+}
+
+/// The functions whose first argument is a function such as `x -> x^2`.
+const HIGHER_ORDER: [&str; 3] = ["filter", "map", "reduce"];
+
+/// Evaluates `map(f, xs)`, `filter(f, xs)` or `reduce(f, xs)`, where `f` is a
+/// function literal.
+fn higher_order<A: Arithmetic + ?Sized>(
+  arithmetic: &A,
+  name: &str,
+  arguments: &[AstNode],
+  memory: &mut HashMap<String, A::Value>,
+) -> Result<A::Value, String> {
+  let (parameters, body, list) = match arguments {
+    [function, list] => match (function.head(), function.tail()) {
+      (AstHead::Lambda(parameters), [body]) => (parameters, body, list),
+      _ => {
+        return Err(format!(
+          "The first argument of {} must be a function such as x -> x^2, not {}.",
+          name, function
+        ))
+      }
+    },
+    _ => {
+      return Err(format!(
+        "{} takes a function and a list, as in {}(x -> x^2, 1..10).",
+        name, name
+      ))
+    }
+  };
+  let items = arithmetic.items(&evaluate_node(arithmetic, list, memory)?)?;
+  let mut apply = |arguments: &[A::Value]| {
+    apply_lambda(arithmetic, parameters, body, arguments, memory)
+  };
+  match name {
+    "map" => {
+      let mapped = items
+        .iter()
+        .map(|item| apply(std::slice::from_ref(item)))
+        .collect::<Result<Vec<A::Value>, String>>()?;
+      arithmetic.list(mapped)
+    }
+    "filter" => {
+      let mut kept = Vec::new();
+      for item in items.into_iter() {
+        if arithmetic.truth(&apply(std::slice::from_ref(&item))?)? {
+          kept.push(item);
+        }
+      }
+      arithmetic.list(kept)
+    }
+    "reduce" => {
+      let mut items = items.into_iter();
+      let first = items
+        .next()
+        .ok_or_else(|| "Cannot reduce an empty list.".to_string())?;
+      items.try_fold(first, |acc, item| apply(&[acc, item]))
+    }
+    _ => unreachable!(),
+  }
+}
+
+/// Applies the function `parameters -> body` to `arguments`. The parameters
+/// hide variables of the same name while the body is evaluated.
+fn apply_lambda<A: Arithmetic + ?Sized>(
+  arithmetic: &A,
+  parameters: &[String],
+  body: &AstNode,
+  arguments: &[A::Value],
+  memory: &mut HashMap<String, A::Value>,
+) -> Result<A::Value, String> {
+  if parameters.len() != arguments.len() {
+    return Err(format!(
+      "The function of {} takes {} arguments, not {}.",
+      parameters.join(", "),
+      parameters.len(),
+      arguments.len()
+    ));
+  }
+  let hidden: Vec<Option<A::Value>> = parameters
+    .iter()
+    .zip(arguments.iter())
+    .map(|(parameter, argument)| memory.insert(parameter.clone(), argument.clone()))
+    .collect();
+  let result = evaluate_node(arithmetic, body, memory);
+  for (parameter, value) in parameters.iter().zip(hidden) {
+    match value {
+      Some(value) => memory.insert(parameter.clone(), value),
+      None => memory.remove(parameter),
+    };
+  }
+  result
+}
+
+/// If `node` is a negation `-1 * x`, as the parser produces for subtraction,
+/// returns `x`.
+fn negation_of(node: &AstNode) -> Option<&AstNode> {
+  match (node.head(), node.tail()) {
+    (AstHead::Times, [minus_one, x]) if *minus_one.head() == AstHead::Number(-1.0) => Some(x),
+    _ => None,
+  }
+}
+
+/// If `node` is a reciprocal `x^-1`, as the parser produces for division,
+/// returns `x`.
+fn reciprocal_of(node: &AstNode) -> Option<&AstNode> {
+  match (node.head(), node.tail()) {
+    (AstHead::Power, [denominator, exponent]) if *exponent.head() == AstHead::Number(-1.0) => {
+      Some(denominator)
+    }
+    _ => None,
+  }
+}
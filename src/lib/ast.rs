@@ -1,18 +1,129 @@
 //! The abstract syntax tree used for this calculator.
-use std::collections::HashMap;
+use std::cmp::Ordering;
 use std::f64;
 use std::fmt;
 
+use crate::lib::angle::AngleUnit;
+use crate::lib::special;
+
+/// A binary bitwise operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bitwise {
+  And,
+  Or,
+  Xor,
+  ShiftLeft,
+  ShiftRight,
+}
+
+impl fmt::Display for Bitwise {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Bitwise::And => write!(f, "&"),
+      Bitwise::Or => write!(f, "|"),
+      Bitwise::Xor => write!(f, "xor"),
+      Bitwise::ShiftLeft => write!(f, "<<"),
+      Bitwise::ShiftRight => write!(f, ">>"),
+    }
+  }
+}
+
+/// A comparison operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+  Equal,
+  NotEqual,
+}
+
+impl fmt::Display for Comparison {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Comparison::Less => write!(f, "<"),
+      Comparison::LessEqual => write!(f, "<="),
+      Comparison::Greater => write!(f, ">"),
+      Comparison::GreaterEqual => write!(f, ">="),
+      Comparison::Equal => write!(f, "=="),
+      Comparison::NotEqual => write!(f, "!="),
+    }
+  }
+}
+
+impl Comparison {
+  /// Whether the comparison holds of two values in the order `order`. Values
+  /// without an order, such as NaN, are only unequal.
+  pub fn holds(&self, order: Option<Ordering>) -> bool {
+    match (self, order) {
+      (Comparison::NotEqual, order) => order != Some(Ordering::Equal),
+      (_, None) => false,
+      (Comparison::Less, Some(order)) => order == Ordering::Less,
+      (Comparison::LessEqual, Some(order)) => order != Ordering::Greater,
+      (Comparison::Greater, Some(order)) => order == Ordering::Greater,
+      (Comparison::GreaterEqual, Some(order)) => order != Ordering::Less,
+      (Comparison::Equal, Some(order)) => order == Ordering::Equal,
+    }
+  }
+}
+
 /// An enumeration for the heads of the AST nodes.
 #[derive(Clone, Debug, PartialEq)]
 pub enum AstHead {
   Plus,
   Times,
   Power,
+  PlusMinus,
+  /// The remainder `a mod b`, which has the sign of `b`
+  Modulo,
+  /// The quotient `a // b` rounded down
+  FloorDivide,
+  /// A percentage `x%`, which is `x / 100`
+  Percent,
+  /// A bitwise operator such as `a & b` or `a << b`
+  Bitwise(Bitwise),
+  /// The bitwise complement `~x`
+  Complement,
+  /// A comparison such as `a < b`
+  Comparison(Comparison),
+  /// The conjunction `a and b`, which skips `b` if `a` is false
+  And,
+  /// The disjunction `a or b`, which skips `b` if `a` is true
+  Or,
+  /// The negation `not x`
+  Not,
+  /// The conditional `if c then a else b`, which evaluates only the branch
+  /// that is chosen
+  Conditional,
+  /// A matrix literal with the given numbers of rows and columns, whose
+  /// entries are the children in row-major order
+  Matrix(usize, usize),
+  /// A list literal `{a, b, c}`
+  List,
+  /// A range `start..end` with an optional step as a third child
+  Range,
+  /// Indexing `xs[i]`
+  Index,
+  /// A function `x -> body` of the named parameters, whose child is the body
+  Lambda(Vec<String>),
+  Convert,
   Assign,
   Number(f64),
+  /// A truth value, `true` or `false`
+  Boolean(bool),
+  /// An integer literal that is not exact as a `Number`, or is written in
+  /// hexadecimal, octal or binary
+  Integer(u128),
+  /// A number literal with more significant digits than a `Number` keeps
+  Decimal(String),
+  /// An angle literal, whose child is its size in the unit
+  Angle(AngleUnit),
   Constant(String),
   Function(String),
+  /// A trigonometric function, or an inverse one, measuring angles in the
+  /// unit, as `in_angle_mode` writes them
+  Trigonometric(String, AngleUnit),
   Identifier(String),
 }
 
@@ -22,7 +133,7 @@ pub struct AstNode {
   /// A tag to determine the type of AST node
   head: AstHead,
   /// A list of arguments/children of the node
-  tail: Box<Vec<AstNode>>,
+  tail: Vec<AstNode>,
 }
 
 impl fmt::Display for AstNode {
@@ -35,10 +146,32 @@ impl fmt::Display for AstNode {
       AstHead::Plus => write!(f, "(+{})", tail_string),
       AstHead::Times => write!(f, "(*{})", tail_string),
       AstHead::Power => write!(f, "(^{})", tail_string),
+      AstHead::PlusMinus => write!(f, "(±{})", tail_string),
+      AstHead::Modulo => write!(f, "(mod{})", tail_string),
+      AstHead::FloorDivide => write!(f, "(//{})", tail_string),
+      AstHead::Percent => write!(f, "(%{})", tail_string),
+      AstHead::Bitwise(operator) => write!(f, "({}{})", operator, tail_string),
+      AstHead::Complement => write!(f, "(~{})", tail_string),
+      AstHead::Comparison(operator) => write!(f, "({}{})", operator, tail_string),
+      AstHead::And => write!(f, "(and{})", tail_string),
+      AstHead::Or => write!(f, "(or{})", tail_string),
+      AstHead::Not => write!(f, "(not{})", tail_string),
+      AstHead::Conditional => write!(f, "(if{})", tail_string),
+      AstHead::Matrix(rows, columns) => write!(f, "([{}x{}]{})", rows, columns, tail_string),
+      AstHead::List => write!(f, "({{}}{})", tail_string),
+      AstHead::Range => write!(f, "(..{})", tail_string),
+      AstHead::Index => write!(f, "([]{})", tail_string),
+      AstHead::Lambda(parameters) => write!(f, "(-> ({}){})", parameters.join(" "), tail_string),
+      AstHead::Convert => write!(f, "(to{})", tail_string),
       AstHead::Assign => write!(f, "(={})", tail_string),
       AstHead::Number(value) => write!(f, "{}", value),
+      AstHead::Boolean(value) => write!(f, "{}", value),
+      AstHead::Integer(value) => write!(f, "{}", value),
+      AstHead::Decimal(text) => write!(f, "{}", text),
+      AstHead::Angle(unit) => write!(f, "({}{})", unit, tail_string),
       AstHead::Constant(name) => write!(f, "{}", name),
       AstHead::Function(name) => write!(f, "({}{})", name, tail_string),
+      AstHead::Trigonometric(name, unit) => write!(f, "({}[{}]{})", name, unit, tail_string),
       AstHead::Identifier(name) => write!(f, "{}", name),
     }
   }
@@ -49,7 +182,7 @@ impl AstNode {
   pub fn new(head: AstHead, tail: Vec<AstNode>) -> AstNode {
     AstNode {
       head,
-      tail: Box::new(tail),
+      tail,
     }
   }
 
@@ -63,7 +196,20 @@ impl AstNode {
     match (self.head.clone(), other.head.clone()) {
       (AstHead::Plus, AstHead::Plus)
       | (AstHead::Times, AstHead::Times)
-      | (AstHead::Power, AstHead::Power) => {
+      | (AstHead::Power, AstHead::Power)
+      | (AstHead::PlusMinus, AstHead::PlusMinus)
+      | (AstHead::Modulo, AstHead::Modulo)
+      | (AstHead::FloorDivide, AstHead::FloorDivide)
+      | (AstHead::Percent, AstHead::Percent)
+      | (AstHead::Complement, AstHead::Complement)
+      | (AstHead::And, AstHead::And)
+      | (AstHead::Or, AstHead::Or)
+      | (AstHead::Not, AstHead::Not)
+      | (AstHead::Conditional, AstHead::Conditional)
+      | (AstHead::List, AstHead::List)
+      | (AstHead::Range, AstHead::Range)
+      | (AstHead::Index, AstHead::Index)
+      | (AstHead::Convert, AstHead::Convert) => {
         if self.tail.len() == other.tail.len() {
           let mut zipped = self.tail.iter().zip(other.tail.iter());
           zipped.all(|(a, b)| a.ast_equality(b))
@@ -71,7 +217,31 @@ impl AstNode {
           false
         }
       }
+      (AstHead::Bitwise(operator1), AstHead::Bitwise(operator2)) => {
+        operator1 == operator2
+          && self.tail.iter().zip(other.tail.iter()).all(|(a, b)| a.ast_equality(b))
+      }
+      (AstHead::Comparison(operator1), AstHead::Comparison(operator2)) => {
+        operator1 == operator2
+          && self.tail.iter().zip(other.tail.iter()).all(|(a, b)| a.ast_equality(b))
+      }
+      (AstHead::Matrix(rows1, columns1), AstHead::Matrix(rows2, columns2)) => {
+        (rows1, columns1) == (rows2, columns2)
+          && self.tail.iter().zip(other.tail.iter()).all(|(a, b)| a.ast_equality(b))
+      }
+      (AstHead::Angle(unit1), AstHead::Angle(unit2)) => {
+        unit1 == unit2 && self.tail[0].ast_equality(&other.tail[0])
+      }
+      (AstHead::Trigonometric(name1, unit1), AstHead::Trigonometric(name2, unit2)) => {
+        (name1, unit1) == (name2, unit2) && self.tail[0].ast_equality(&other.tail[0])
+      }
+      (AstHead::Lambda(parameters1), AstHead::Lambda(parameters2)) => {
+        parameters1 == parameters2 && self.tail[0].ast_equality(&other.tail[0])
+      }
       (AstHead::Number(value1), AstHead::Number(value2)) => value1 == value2,
+      (AstHead::Boolean(value1), AstHead::Boolean(value2)) => value1 == value2,
+      (AstHead::Integer(value1), AstHead::Integer(value2)) => value1 == value2,
+      (AstHead::Decimal(text1), AstHead::Decimal(text2)) => text1 == text2,
       (AstHead::Identifier(id1), AstHead::Identifier(id2)) => id1 == id2,
       (AstHead::Function(name1), AstHead::Function(name2)) => {
         if name1 == name2 && self.tail.len() == other.tail.len() {
@@ -92,122 +262,222 @@ impl AstNode {
     AstNode::new(AstHead::Constant(name.to_owned()), Vec::new())
   }
 
-  /// Evaluates the AST using the state defined in `memory`.
-  pub fn evaluate(&self, memory: &mut HashMap<String, f64>) -> f64 {
-    let head = self.head.clone();
-    let mut tail_iter = self.tail.iter();
-    let mut identifier: Option<String> = None;
-    if head == AstHead::Assign {
-      let ident_node = tail_iter
-        .next()
-        .expect("Should have been an identifier as the first child of an assignment.");
-      match ident_node.head.clone() {
-        AstHead::Identifier(name) => {
-          identifier = Some(name);
-        }
-        _ => unreachable!(),
-      }
+  /// The names of the identifiers in the AST, in order of first appearance.
+  pub fn identifiers(&self) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    if let AstHead::Identifier(name) = &self.head {
+      names.push(name.clone());
     }
-    let evaled_tail = tail_iter
-      .map(|arg| arg.evaluate(memory))
-      .collect::<Vec<f64>>();
-    match head {
-      AstHead::Plus => evaled_tail.iter().sum(),
-      AstHead::Times => evaled_tail.iter().product(),
-      AstHead::Power => {
-        if evaled_tail.len() == 0 {
-          1.0_f64
-        } else {
-          let (first, rest) = evaled_tail.split_at(1);
-          let first = first[0];
-          rest.iter().rfold(first, |acc, &x| acc.powf(x))
+    for node in self.tail.iter() {
+      for name in node.identifiers() {
+        if !names.contains(&name) {
+          names.push(name);
         }
       }
-      AstHead::Number(number) => number,
-      AstHead::Constant(name) => match name.as_ref() {
-        "pi" => f64::consts::PI,
-        "e" => f64::consts::E,
-        _ => f64::NAN,
-      },
-      AstHead::Function(name) => {
-        let first = evaled_tail
-          .get(0)
-          .expect("Function should have been called with one argument");
-        match name.as_ref() {
-          "abs" => first.abs(),
-          "acos" => first.acos(),
-          "acosh" => first.acosh(),
-          "asin" => first.asin(),
-          "asinh" => first.asinh(),
-          "atan" => first.atan(),
-          "atanh" => first.atanh(),
-          "cos" => first.cos(),
-          "cosh" => first.cosh(),
-          "exp" => first.exp(),
-          "log" => first.ln(),
-          "sin" => first.sin(),
-          "sinh" => first.sinh(),
-          "sqrt" => first.sqrt(),
-          "tan" => first.tan(),
-          "tanh" => first.tanh(),
-          _ => f64::NAN,
+    }
+    names
+  }
+
+  /// Writes the AST as a unit, such as `km/h` or `kg*m^2`.
+  pub fn to_unit_string(&self) -> String {
+    match (&self.head, self.tail.as_slice()) {
+      (AstHead::Times, factors) => {
+        let mut unit = String::new();
+        for factor in factors.iter() {
+          match (&factor.head, factor.tail.as_slice()) {
+            (AstHead::Power, [base, exponent]) if exponent.head == AstHead::Number(-1.0) => {
+              unit = format!("{}/{}", unit, base.to_unit_string());
+            }
+            _ if unit.is_empty() => unit = factor.to_unit_string(),
+            _ => unit = format!("{}*{}", unit, factor.to_unit_string()),
+          }
         }
+        unit
       }
-      AstHead::Identifier(name) => *memory.get(&name).unwrap_or(&f64::NAN),
-      AstHead::Assign => {
-        let ident_name =
-          identifier.expect("Should have been an identifier as the first child to an assignment.");
-        let ident_value = *evaled_tail
-          .get(0)
-          .expect("Should have been a value as the second child an assignment.");
-        memory.insert(ident_name, ident_value);
-        ident_value
+      (AstHead::Power, [base, exponent]) => {
+        format!("{}^{}", base.to_unit_string(), exponent.to_unit_string())
       }
+      _ => self.to_string(),
     }
   }
 
+  /// The tag of this node.
+  pub fn head(&self) -> &AstHead {
+    &self.head
+  }
+
+  /// The arguments/children of this node.
+  pub fn tail(&self) -> &[AstNode] {
+    &self.tail
+  }
+
+  /// A helper function that creates an AST node for converting `expr` to the
+  /// unit `target`.
+  pub fn convert(expr: AstNode, target: AstNode) -> AstNode {
+    AstNode::new(AstHead::Convert, vec![expr, target])
+  }
+
   /// A helper function that creates an AST node for functions.
   pub fn function(name: &str, argument: AstNode) -> AstNode {
     AstNode::new(AstHead::Function(name.to_string()), vec![argument])
   }
 
+  /// A helper function that creates an AST node for a function of several
+  /// arguments.
+  pub fn call(name: &str, arguments: Vec<AstNode>) -> AstNode {
+    AstNode::new(AstHead::Function(name.to_string()), arguments)
+  }
+
   /// A helper function that creates an AST node for identifiers.
   pub fn identifier(name: &str) -> AstNode {
     AstNode::new(AstHead::Identifier(name.to_owned()), Vec::new())
   }
 
+  /// A helper function that creates an AST node for a matrix from its rows,
+  /// which must have the same length.
+  pub fn matrix(rows: Vec<Vec<AstNode>>) -> AstNode {
+    let columns = rows.first().map_or(0, Vec::len);
+    AstNode::new(
+      AstHead::Matrix(rows.len(), columns),
+      rows.into_iter().flatten().collect(),
+    )
+  }
+
+  /// A helper function that creates an AST node for the list `{items}`.
+  pub fn list(items: Vec<AstNode>) -> AstNode {
+    AstNode::new(AstHead::List, items)
+  }
+
+  /// A helper function that creates an AST node for the range `start..end`,
+  /// optionally with a step.
+  pub fn range(start: AstNode, end: AstNode, step: Option<AstNode>) -> AstNode {
+    let mut bounds = vec![start, end];
+    bounds.extend(step);
+    AstNode::new(AstHead::Range, bounds)
+  }
+
+  /// A helper function that creates an AST node for indexing `value[index]`.
+  pub fn index(value: AstNode, index: AstNode) -> AstNode {
+    AstNode::new(AstHead::Index, vec![value, index])
+  }
+
+  /// A helper function that creates an AST node for the function
+  /// `parameters -> body`.
+  pub fn lambda(parameters: Vec<String>, body: AstNode) -> AstNode {
+    AstNode::new(AstHead::Lambda(parameters), vec![body])
+  }
+
+  /// A helper function that creates an AST node for the remainder `a mod b`.
+  pub fn modulo(a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::Modulo, vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for the floor division
+  /// `a // b`.
+  pub fn floor_divide(a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::FloorDivide, vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for the percentage `x%`.
+  pub fn percent(x: AstNode) -> AstNode {
+    AstNode::new(AstHead::Percent, vec![x])
+  }
+
+  /// A helper function that creates an AST node for a bitwise operator.
+  pub fn bitwise(operator: Bitwise, a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::Bitwise(operator), vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for the complement `~x`.
+  pub fn complement(x: AstNode) -> AstNode {
+    AstNode::new(AstHead::Complement, vec![x])
+  }
+
+  /// A helper function that creates an AST node for the comparison `a
+  /// operator b`
+  pub fn comparison(operator: Comparison, a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::Comparison(operator), vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for `a and b`
+  pub fn and(a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::And, vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for `a or b`
+  pub fn or(a: AstNode, b: AstNode) -> AstNode {
+    AstNode::new(AstHead::Or, vec![a, b])
+  }
+
+  /// A helper function that creates an AST node for `not x`
+  pub fn logical_not(x: AstNode) -> AstNode {
+    AstNode::new(AstHead::Not, vec![x])
+  }
+
+  /// A helper function that creates an AST node for `if condition then
+  /// then else otherwise`
+  pub fn conditional(condition: AstNode, then: AstNode, otherwise: AstNode) -> AstNode {
+    AstNode::new(AstHead::Conditional, vec![condition, then, otherwise])
+  }
+
+  /// A helper function that creates an AST node for `true` or `false`
+  pub fn boolean(value: bool) -> AstNode {
+    AstNode::new(AstHead::Boolean(value), Vec::new())
+  }
+
   /// A helper function that creates an AST node for numbers
   pub fn number(value: f64) -> AstNode {
     AstNode::new(AstHead::Number(value), Vec::new())
   }
 
+  /// A helper function that creates an AST node for integer literals
+  pub fn integer(value: u128) -> AstNode {
+    AstNode::new(AstHead::Integer(value), Vec::new())
+  }
+
+  /// A helper function that creates an AST node for long number literals
+  pub fn decimal(text: String) -> AstNode {
+    AstNode::new(AstHead::Decimal(text), Vec::new())
+  }
+
+  /// A helper function that creates an AST node for an angle literal of
+  /// `value` in `unit`
+  pub fn angle(value: f64, unit: AngleUnit) -> AstNode {
+    AstNode::new(AstHead::Angle(unit), vec![AstNode::number(value)])
+  }
+
   /// A helper function that creates an AST node for addition
   pub fn plus(arguments: Vec<AstNode>) -> AstNode {
     let len = arguments.len();
     match len {
       0 => AstNode::number(0.0),
       1 => arguments
-        .get(0)
+        .first()
         .expect("Should be able to get 0th element of a non-empty vector.")
         .clone(),
       _ => AstNode::new(AstHead::Plus, arguments),
     }
   }
 
+  /// A helper function that creates an AST node for `value ± error`.
+  pub fn plus_minus(value: AstNode, error: AstNode) -> AstNode {
+    AstNode::new(AstHead::PlusMinus, vec![value, error])
+  }
+
   /// A helper function that creates an AST node for exponentiation
   pub fn power(arguments: Vec<AstNode>) -> AstNode {
     let len = arguments.len();
     match len {
       0 => AstNode::number(1.0),
       1 => arguments
-        .get(0)
+        .first()
         .expect("Should be able to get 0th element of a non-empty vector.")
         .clone(),
       _ => {
         let last_rest = arguments
           .split_last()
           .expect("Should be able to split the last element off a non-empty vector.");
-        let (last, rest) = (last_rest.0.clone(), last_rest.1.clone());
+        let (last, rest) = (last_rest.0.clone(), last_rest.1);
         rest.iter().rfold(last, |acc, x| {
           AstNode::new(AstHead::Power, vec![x.clone(), acc])
         })
@@ -221,10 +491,85 @@ impl AstNode {
     match len {
       0 => AstNode::number(1.0),
       1 => arguments
-        .get(0)
+        .first()
         .expect("Should be able to get 0th element of a non-empty vector.")
         .clone(),
       _ => AstNode::new(AstHead::Times, arguments),
     }
   }
 }
+
+/// The remainder of `a / b`, which has the sign of `b` so that
+/// `a = b (a // b) + a mod b`.
+pub fn modulo(a: f64, b: f64) -> f64 {
+  let remainder = a % b;
+  if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+    remainder + b
+  } else {
+    remainder
+  }
+}
+
+/// The quotient `a / b` rounded down, computed from the exact remainder.
+pub fn floor_divide(a: f64, b: f64) -> f64 {
+  ((a - modulo(a, b)) / b).round()
+}
+
+/// Applies the built-in function `name` of one number, or returns `None` if
+/// there is no such function.
+pub fn apply_function(name: &str, x: f64) -> Option<f64> {
+  let value = match name {
+    "abs" => x.abs(),
+    "acos" => x.acos(),
+    "acosh" => x.acosh(),
+    "acot" => x.recip().atan(),
+    "acoth" => x.recip().atanh(),
+    "acsc" => x.recip().asin(),
+    "acsch" => x.recip().asinh(),
+    "asec" => x.recip().acos(),
+    "asech" => x.recip().acosh(),
+    "asin" => x.asin(),
+    "asinh" => x.asinh(),
+    "atan" => x.atan(),
+    "atanh" => x.atanh(),
+    "cbrt" => x.cbrt(),
+    "ceil" => x.ceil(),
+    "cos" => x.cos(),
+    "cosh" => x.cosh(),
+    "cot" => x.tan().recip(),
+    "coth" => x.tanh().recip(),
+    "csc" => x.sin().recip(),
+    "csch" => x.sinh().recip(),
+    "digamma" => special::digamma(x),
+    "erf" => special::erf(x),
+    "erfc" => special::erfc(x),
+    "erfinv" => special::erf_inv(x),
+    "exp" => x.exp(),
+    "expm1" => x.exp_m1(),
+    "factorial" => special::gamma(x + 1.0),
+    "floor" => x.floor(),
+    "frac" => x.fract(),
+    "gamma" => special::gamma(x),
+    "lambertw" => special::lambert_w(0, x),
+    "lgamma" => special::ln_gamma(x),
+    "log" => x.ln(),
+    "log10" => x.log10(),
+    "log1p" => x.ln_1p(),
+    "log2" => x.log2(),
+    "round" => x.round(),
+    "sec" => x.cos().recip(),
+    "sech" => x.cosh().recip(),
+    // Unlike `signum`, zero has sign 0.
+    "sign" if x == 0.0 => x,
+    "sign" => x.signum(),
+    "sin" => x.sin(),
+    "sinh" => x.sinh(),
+    "sqrt" => x.sqrt(),
+    "tan" => x.tan(),
+    "tanh" => x.tanh(),
+    "trunc" => x.trunc(),
+    "zeta" => special::zeta(x),
+    _ => return None,
+  };
+  Some(value)
+}
@@ -0,0 +1,167 @@
+//! A library of physical and mathematical constants.
+//!
+//! Constants live in the namespaces `phys` and `math`, as in `phys.c` or
+//! `math.phi`, so they never clash with variables. Physical constants carry
+//! their CODATA 2018 value, standard uncertainty and unit.
+
+use std::fmt;
+
+/// A named constant.
+pub struct NamedConstant {
+  /// The name of the constant, including its namespace
+  pub name: &'static str,
+  /// The value in SI base units
+  pub value: f64,
+  /// The standard uncertainty of the value, zero if it is exact
+  pub uncertainty: f64,
+  /// The SI unit of the value, empty if it is a pure number
+  pub unit: &'static str,
+  /// The powers of the SI base units m, kg, s, A, K, mol and cd
+  pub dimension: [i8; 7],
+  /// What the constant is
+  pub description: &'static str,
+}
+
+impl fmt::Display for NamedConstant {
+  /// Shows the constant as a line of the listing, in scientific notation
+  /// unless its value is of a moderate size.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if (1e-3..1e9).contains(&self.value.abs()) {
+      write!(f, "{} = {}", self.name, self.value)?;
+    } else {
+      write!(f, "{} = {:e}", self.name, self.value)?;
+    }
+    if self.uncertainty > 0.0 {
+      write!(f, " ± {:e}", self.uncertainty)?;
+    }
+    if !self.unit.is_empty() {
+      write!(f, " {}", self.unit)?;
+    }
+    write!(f, "  {}", self.description)
+  }
+}
+
+/// The namespaces that constants are defined in.
+pub const NAMESPACES: [&str; 2] = ["math", "phys"];
+
+/// The constants, grouped by namespace.
+pub const CONSTANTS: [NamedConstant; 10] = [
+  NamedConstant {
+    name: "math.gamma",
+    value: 0.577_215_664_901_532_9,
+    uncertainty: 0.0,
+    unit: "",
+    dimension: [0, 0, 0, 0, 0, 0, 0],
+    description: "Euler–Mascheroni constant",
+  },
+  NamedConstant {
+    name: "math.phi",
+    value: 1.618_033_988_749_895,
+    uncertainty: 0.0,
+    unit: "",
+    dimension: [0, 0, 0, 0, 0, 0, 0],
+    description: "golden ratio",
+  },
+  NamedConstant {
+    name: "math.tau",
+    value: std::f64::consts::TAU,
+    uncertainty: 0.0,
+    unit: "",
+    dimension: [0, 0, 0, 0, 0, 0, 0],
+    description: "ratio of a circle's circumference to its radius, 2π",
+  },
+  NamedConstant {
+    name: "phys.N_A",
+    value: 6.022_140_76e23,
+    uncertainty: 0.0,
+    unit: "1/mol",
+    dimension: [0, 0, 0, 0, 0, -1, 0],
+    description: "Avogadro constant",
+  },
+  NamedConstant {
+    name: "phys.k_B",
+    value: 1.380_649e-23,
+    uncertainty: 0.0,
+    unit: "J/K",
+    dimension: [2, 1, -2, 0, -1, 0, 0],
+    description: "Boltzmann constant",
+  },
+  NamedConstant {
+    name: "phys.e_charge",
+    value: 1.602_176_634e-19,
+    uncertainty: 0.0,
+    unit: "C",
+    dimension: [0, 0, 1, 1, 0, 0, 0],
+    description: "elementary charge",
+  },
+  NamedConstant {
+    name: "phys.G",
+    value: 6.674_30e-11,
+    uncertainty: 0.000_15e-11,
+    unit: "m^3/(kg*s^2)",
+    dimension: [3, -1, -2, 0, 0, 0, 0],
+    description: "Newtonian constant of gravitation",
+  },
+  NamedConstant {
+    name: "phys.h",
+    value: 6.626_070_15e-34,
+    uncertainty: 0.0,
+    unit: "J*s",
+    dimension: [2, 1, -1, 0, 0, 0, 0],
+    description: "Planck constant",
+  },
+  NamedConstant {
+    name: "phys.hbar",
+    value: 1.054_571_817e-34,
+    uncertainty: 0.0,
+    unit: "J*s",
+    dimension: [2, 1, -1, 0, 0, 0, 0],
+    description: "reduced Planck constant, h/2π",
+  },
+  NamedConstant {
+    name: "phys.c",
+    value: 299_792_458.0,
+    uncertainty: 0.0,
+    unit: "m/s",
+    dimension: [1, 0, -1, 0, 0, 0, 0],
+    description: "speed of light in vacuum",
+  },
+];
+
+/// Looks up a constant by its full name, such as `phys.c`.
+pub fn find(name: &str) -> Option<&'static NamedConstant> {
+  CONSTANTS.iter().find(|constant| constant.name == name)
+}
+
+/// Finds the constants whose name or description contains `query`, ignoring
+/// case. An empty query matches every constant.
+pub fn search(query: &str) -> Vec<&'static NamedConstant> {
+  let query = query.to_lowercase();
+  CONSTANTS
+    .iter()
+    .filter(|constant| {
+      constant.name.to_lowercase().contains(&query)
+        || constant.description.to_lowercase().contains(&query)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::constants::{find, search};
+
+  #[test]
+  fn find_by_name() {
+    assert_eq!(find("phys.c").unwrap().value, 299_792_458.0);
+    assert!(find("phys.G").unwrap().uncertainty > 0.0);
+    assert!(find("c").is_none());
+  }
+
+  #[test]
+  fn search_names_and_descriptions() {
+    let planck: Vec<&str> = search("planck").iter().map(|c| c.name).collect();
+    assert_eq!(planck, vec!["phys.h", "phys.hbar"]);
+    assert_eq!(search("PHI").len(), 1);
+    assert_eq!(search("").len(), 10);
+  }
+}
@@ -0,0 +1,598 @@
+//! Fixed-point decimal arithmetic, used to evaluate expressions in base 10 so
+//! that amounts like `0.1 + 0.2` come out exactly.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::apply_function;
+use crate::lib::constants;
+use crate::lib::number_theory::{self, NUMBER_THEORY};
+
+/// The first 36 decimal places of &pi;.
+const PI: &str = "3.141592653589793238462643383279502884";
+/// The first 36 decimal places of *e*.
+const E: &str = "2.718281828459045235360287471352662497";
+
+/// How to round a result that has more decimal places than allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+  /// Round to the nearest value, and ties to the even neighbour (banker's rounding)
+  HalfEven,
+  /// Round to the nearest value, and ties away from zero
+  HalfUp,
+  /// Drop the extra digits, i.e. round towards zero
+  Truncate,
+}
+
+impl fmt::Display for Rounding {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Rounding::HalfEven => write!(f, "half-even"),
+      Rounding::HalfUp => write!(f, "half-up"),
+      Rounding::Truncate => write!(f, "truncate"),
+    }
+  }
+}
+
+impl FromStr for Rounding {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Rounding, String> {
+    match name {
+      "half-even" => Ok(Rounding::HalfEven),
+      "half-up" => Ok(Rounding::HalfUp),
+      "truncate" => Ok(Rounding::Truncate),
+      _ => Err(format!(
+        "Unknown rounding mode '{}'. Expected half-even, half-up or truncate.",
+        name
+      )),
+    }
+  }
+}
+
+/// A decimal number, `coefficient` &times; 10<sup>-`scale`</sup>.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+  /// The digits of the number as an integer
+  coefficient: i128,
+  /// The number of digits after the decimal point
+  scale: u32,
+}
+
+impl fmt::Display for Decimal {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let digits = self.coefficient.unsigned_abs().to_string();
+    let sign = if self.coefficient < 0 { "-" } else { "" };
+    let scale = self.scale as usize;
+    if scale == 0 {
+      write!(f, "{}{}", sign, digits)
+    } else if digits.len() > scale {
+      let (whole, fraction) = digits.split_at(digits.len() - scale);
+      write!(f, "{}{}.{}", sign, whole, fraction)
+    } else {
+      write!(f, "{}0.{:0>width$}", sign, digits, width = scale)
+    }
+  }
+}
+
+impl PartialEq for Decimal {
+  fn eq(&self, other: &Self) -> bool {
+    self.partial_cmp(other) == Some(Ordering::Equal)
+  }
+}
+
+impl PartialOrd for Decimal {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    let scale = self.scale.max(other.scale);
+    match (self.rescale(scale), other.rescale(scale)) {
+      (Ok(a), Ok(b)) => a.coefficient.partial_cmp(&b.coefficient),
+      _ => None,
+    }
+  }
+}
+
+impl FromStr for Decimal {
+  type Err = String;
+
+  /// Parses a plain decimal numeral such as `-12.340`, keeping every digit.
+  fn from_str(text: &str) -> Result<Decimal, String> {
+    let error = || format!("Failed to parse '{}' as a decimal.", text);
+    let (negative, unsigned) = match text.strip_prefix('-') {
+      Some(rest) => (true, rest),
+      None => (false, text),
+    };
+    let (whole, fraction) = match unsigned.find('.') {
+      Some(index) => (&unsigned[..index], &unsigned[index + 1..]),
+      None => (unsigned, ""),
+    };
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.len() + fraction.len() == 0 || !all_digits(whole) || !all_digits(fraction) {
+      return Err(error());
+    }
+    let mut coefficient: i128 = 0;
+    for digit in whole.chars().chain(fraction.chars()) {
+      coefficient = coefficient
+        .checked_mul(10)
+        .and_then(|c| c.checked_add(i128::from(digit.to_digit(10).unwrap_or(0))))
+        .ok_or_else(overflow)?;
+    }
+    Ok(Decimal::new(
+      if negative { -coefficient } else { coefficient },
+      fraction.len() as u32,
+    ))
+  }
+}
+
+impl Decimal {
+  /// Creates the decimal `coefficient` &times; 10<sup>-`scale`</sup>.
+  pub fn new(coefficient: i128, scale: u32) -> Decimal {
+    Decimal { coefficient, scale }
+  }
+
+  /// Converts a float to the shortest decimal that reads back as the same
+  /// float. A literal of up to 15 significant digits therefore converts back
+  /// to exactly the digits that were written; longer ones are lexed as
+  /// decimals and parsed directly.
+  pub fn from_f64(value: f64) -> Result<Decimal, String> {
+    if value.is_finite() {
+      format!("{}", value).parse()
+    } else {
+      Err(format!("{} is not a finite number.", value))
+    }
+  }
+
+  /// The digits of the number as an integer.
+  pub fn coefficient(self) -> i128 {
+    self.coefficient
+  }
+
+  /// The number of digits after the decimal point.
+  pub fn scale(self) -> u32 {
+    self.scale
+  }
+
+  /// The nearest float to this decimal.
+  pub fn to_f64(self) -> f64 {
+    self.to_string().parse().unwrap_or(f64::NAN)
+  }
+
+  /// Adds two decimals exactly.
+  pub fn add(&self, other: &Decimal) -> Result<Decimal, String> {
+    let scale = self.scale.max(other.scale);
+    let (a, b) = (self.rescale(scale)?, other.rescale(scale)?);
+    a.coefficient
+      .checked_add(b.coefficient)
+      .map(|c| Decimal::new(c, scale))
+      .ok_or_else(overflow)
+  }
+
+  /// Multiplies two decimals exactly.
+  pub fn multiply(&self, other: &Decimal) -> Result<Decimal, String> {
+    self
+      .coefficient
+      .checked_mul(other.coefficient)
+      .zip(self.scale.checked_add(other.scale))
+      .map(|(c, scale)| Decimal::new(c, scale))
+      .ok_or_else(overflow)
+  }
+
+  /// Divides two decimals, rounding the quotient to `scale` decimal places.
+  pub fn divide(&self, other: &Decimal, scale: u32, rounding: Rounding) -> Result<Decimal, String> {
+    if other.coefficient == 0 {
+      return Err("Division by zero.".to_string());
+    }
+    // self / other * 10^scale = self.coefficient * 10^shift / other.coefficient
+    let shift = i64::from(scale) + i64::from(other.scale) - i64::from(self.scale);
+    let (numerator, denominator) = if shift >= 0 {
+      (scale_up(self.coefficient, shift as u32)?, other.coefficient)
+    } else {
+      (self.coefficient, scale_up(other.coefficient, (-shift) as u32)?)
+    };
+    Ok(Decimal::new(
+      divide_rounded(numerator, denominator, rounding),
+      scale,
+    ))
+  }
+
+  /// Rounds to at most `scale` decimal places.
+  pub fn round(&self, scale: u32, rounding: Rounding) -> Decimal {
+    if self.scale <= scale {
+      *self
+    } else {
+      match 10_i128.checked_pow(self.scale - scale) {
+        Some(divisor) => Decimal::new(divide_rounded(self.coefficient, divisor, rounding), scale),
+        // The divisor exceeds every coefficient, so all digits are dropped.
+        None => Decimal::new(0, scale),
+      }
+    }
+  }
+
+  /// The absolute value.
+  pub fn abs(&self) -> Result<Decimal, String> {
+    let coefficient = self.coefficient.checked_abs().ok_or_else(overflow)?;
+    Ok(Decimal::new(coefficient, self.scale))
+  }
+
+  /// The decimal with the opposite sign.
+  pub fn negate(&self) -> Result<Decimal, String> {
+    let coefficient = self.coefficient.checked_neg().ok_or_else(overflow)?;
+    Ok(Decimal::new(coefficient, self.scale))
+  }
+
+  /// Raises the decimal to a whole power exactly, by repeated squaring.
+  pub fn powi(&self, exponent: u32) -> Result<Decimal, String> {
+    let (mut result, mut square, mut exponent) = (Decimal::new(1, 0), *self, exponent);
+    loop {
+      if exponent % 2 == 1 {
+        result = result.multiply(&square)?;
+      }
+      exponent /= 2;
+      if exponent == 0 {
+        return Ok(result);
+      }
+      square = square.multiply(&square)?;
+    }
+  }
+
+  /// The greatest integer not above the decimal.
+  pub fn floor(&self) -> Result<Decimal, String> {
+    let truncated = self.round(0, Rounding::Truncate);
+    if self.coefficient < 0 && self.to_integer().is_none() {
+      truncated.add(&Decimal::new(-1, 0))
+    } else {
+      Ok(truncated)
+    }
+  }
+
+  /// The least integer not below the decimal.
+  pub fn ceil(&self) -> Result<Decimal, String> {
+    let truncated = self.round(0, Rounding::Truncate);
+    if self.coefficient > 0 && self.to_integer().is_none() {
+      truncated.add(&Decimal::new(1, 0))
+    } else {
+      Ok(truncated)
+    }
+  }
+
+  /// The fractional part, which has the sign of the decimal.
+  pub fn fract(&self) -> Decimal {
+    match 10_i128.checked_pow(self.scale) {
+      Some(divisor) => Decimal::new(self.coefficient % divisor, self.scale),
+      // The divisor exceeds every coefficient, so there is no integer part.
+      None => *self,
+    }
+  }
+
+  /// Returns the value as an integer if it has no fractional part.
+  fn to_integer(self) -> Option<i128> {
+    let divisor = 10_i128.checked_pow(self.scale)?;
+    if self.coefficient % divisor == 0 {
+      Some(self.coefficient / divisor)
+    } else {
+      None
+    }
+  }
+
+  /// Rewrites the decimal with a larger scale without changing its value.
+  fn rescale(&self, scale: u32) -> Result<Decimal, String> {
+    if scale <= self.scale {
+      Ok(*self)
+    } else {
+      scale_up(self.coefficient, scale - self.scale).map(|c| Decimal::new(c, scale))
+    }
+  }
+}
+
+/// Settings for decimal evaluation: results that are not exact are rounded to
+/// `scale` decimal places using `rounding`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecimalContext {
+  /// The number of decimal places kept in rounded results
+  pub scale: u32,
+  /// How digits beyond `scale` are rounded away
+  pub rounding: Rounding,
+}
+
+impl Default for DecimalContext {
+  /// Two decimal places and banker's rounding, suitable for currency.
+  fn default() -> DecimalContext {
+    DecimalContext::new(2, Rounding::HalfEven)
+  }
+}
+
+impl fmt::Display for DecimalContext {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "scale {}, {}", self.scale, self.rounding)
+  }
+}
+
+impl DecimalContext {
+  /// Creates a new decimal context.
+  pub fn new(scale: u32, rounding: Rounding) -> DecimalContext {
+    DecimalContext { scale, rounding }
+  }
+
+  /// Converts an inexact float result into a rounded decimal.
+  fn inexact(&self, value: f64) -> Result<Decimal, String> {
+    Decimal::from_f64(value).map(|d| d.round(self.scale, self.rounding))
+  }
+}
+
+/// Decimal evaluation: sums, products and integer powers are exact; quotients
+/// and everything computed through a function are rounded to `scale` places,
+/// as is the final result.
+impl Arithmetic for DecimalContext {
+  type Value = Decimal;
+
+  fn number(&self, value: f64) -> Result<Decimal, String> {
+    Decimal::from_f64(value)
+  }
+
+  fn integer(&self, value: u128) -> Result<Decimal, String> {
+    i128::try_from(value).map(|n| Decimal::new(n, 0)).map_err(|_| overflow())
+  }
+
+  fn decimal(&self, text: &str) -> Result<Decimal, String> {
+    text.parse()
+  }
+
+  fn constant(&self, name: &str) -> Result<Decimal, String> {
+    match name {
+      "pi" => PI.parse().map(|pi: Decimal| pi.round(self.scale, self.rounding)),
+      "e" => E.parse().map(|e: Decimal| e.round(self.scale, self.rounding)),
+      _ => match constants::find(name) {
+        Some(constant) => Decimal::from_f64(constant.value),
+        None => Err(format!("Unknown constant {}", name)),
+      },
+    }
+  }
+
+  fn add(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    a.add(b)
+  }
+
+  fn multiply(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    a.multiply(b)
+  }
+
+  fn divide(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    a.divide(b, self.scale, self.rounding)
+  }
+
+  /// The quotient is exact, however many places it has.
+  fn floor_divide(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    let quotient = a.divide(b, 0, Rounding::Truncate)?;
+    let remainder = a.add(&b.multiply(&quotient)?.negate()?)?;
+    if remainder.coefficient != 0 && (remainder.coefficient < 0) != (b.coefficient < 0) {
+      quotient.add(&Decimal::new(-1, 0))
+    } else {
+      Ok(quotient)
+    }
+  }
+
+  /// The remainder is exact.
+  fn modulo(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    a.add(&b.multiply(&self.floor_divide(a, b)?)?.negate()?)
+  }
+
+  /// Multiplies by 0.01, which is exact, instead of rounding a quotient.
+  fn percent(&self, x: &Decimal) -> Result<Decimal, String> {
+    x.multiply(&Decimal::new(1, 2))
+  }
+
+  /// Integer powers are computed exactly, except that negative powers are
+  /// rounded; other powers go through `f64`.
+  fn power(&self, base: &Decimal, exponent: &Decimal) -> Result<Decimal, String> {
+    match exponent.to_integer() {
+      Some(n) if n.unsigned_abs() <= u128::from(u32::MAX) => {
+        let result = base.powi(n.unsigned_abs() as u32)?;
+        if n < 0 {
+          Decimal::new(1, 0).divide(&result, self.scale, self.rounding)
+        } else {
+          Ok(result)
+        }
+      }
+      _ => self.inexact(base.to_f64().powf(exponent.to_f64())),
+    }
+  }
+
+  /// The number theory functions take and give exact integers; `factor`,
+  /// whose result is not a number, is left to floating point mode.
+  fn call(&self, name: &str, arguments: &[Decimal]) -> Result<Decimal, String> {
+    if name == "factor" {
+      return Err("factor is only available in floating point mode.".to_string());
+    }
+    if !NUMBER_THEORY.contains(&name) {
+      return match arguments {
+        [argument] => self.function(name, argument),
+        _ => Err(format!("{} takes one argument, not {}.", name, arguments.len())),
+      };
+    }
+    let integers = arguments
+      .iter()
+      .map(|x| x.to_integer().ok_or_else(|| format!("{} takes whole numbers, not {}.", name, x)))
+      .collect::<Result<Vec<i128>, String>>()?;
+    number_theory::number_theory(name, &integers).map(|n| Decimal::new(n, 0))
+  }
+
+  /// `abs`, `sign`, the rounding functions and factorials of whole numbers
+  /// are exact, where `round` uses the rounding mode; the other functions are
+  /// computed in `f64` and rounded.
+  fn function(&self, name: &str, argument: &Decimal) -> Result<Decimal, String> {
+    match name {
+      "abs" => return argument.abs(),
+      "ceil" => return argument.ceil(),
+      "factorial" => {
+        if let Some(n @ 0..) = argument.to_integer() {
+          return (1..=n).try_fold(Decimal::new(1, 0), |acc, k| acc.multiply(&Decimal::new(k, 0)));
+        }
+      }
+      "floor" => return argument.floor(),
+      "frac" => return Ok(argument.fract()),
+      "round" => return Ok(argument.round(0, self.rounding)),
+      "sign" => return Ok(Decimal::new(argument.coefficient.signum(), 0)),
+      "trunc" => return Ok(argument.round(0, Rounding::Truncate)),
+      _ => (),
+    }
+    let value = apply_function(name, argument.to_f64())
+      .ok_or_else(|| format!("Unknown function {}", name))?;
+    self
+      .inexact(value)
+      .map_err(|_| format!("{}({}) is undefined.", name, argument))
+  }
+
+  fn order(&self, a: &Decimal, b: &Decimal) -> Result<Option<Ordering>, String> {
+    Ok(a.partial_cmp(b))
+  }
+
+  /// Rounds to `scale` places and pads with zeros up to `scale` places.
+  fn finish(&self, value: Decimal) -> Decimal {
+    let value = value.round(self.scale, self.rounding);
+    value.rescale(self.scale).unwrap_or(value)
+  }
+}
+
+/// Multiplies `coefficient` by 10<sup>`digits`</sup>.
+fn scale_up(coefficient: i128, digits: u32) -> Result<i128, String> {
+  10_i128
+    .checked_pow(digits)
+    .and_then(|factor| coefficient.checked_mul(factor))
+    .ok_or_else(overflow)
+}
+
+/// Divides two integers, rounding the quotient as directed.
+fn divide_rounded(numerator: i128, denominator: i128, rounding: Rounding) -> i128 {
+  let quotient = numerator / denominator;
+  let remainder = (numerator % denominator).unsigned_abs();
+  let sign = numerator.signum() * denominator.signum();
+  let twice_remainder = remainder.saturating_mul(2);
+  let round_away = match rounding {
+    Rounding::Truncate => false,
+    Rounding::HalfUp => twice_remainder >= denominator.unsigned_abs(),
+    Rounding::HalfEven => match twice_remainder.cmp(&denominator.unsigned_abs()) {
+      Ordering::Greater => true,
+      Ordering::Equal => quotient % 2 != 0,
+      Ordering::Less => false,
+    },
+  };
+  if round_away {
+    quotient + sign
+  } else {
+    quotient
+  }
+}
+
+/// The error reported when a result has too many digits.
+fn overflow() -> String {
+  "Decimal overflow: the result has more than 38 digits.".to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::decimal::{Decimal, DecimalContext, Rounding};
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str, context: DecimalContext) -> Result<Decimal, String> {
+    let tokens = Lexer::lex(input)?;
+    let ast = Parser::parse(&tokens)?;
+    context.evaluate(&ast, &mut HashMap::new())
+  }
+
+  #[test]
+  fn sums_are_exact() {
+    let result = evaluate("0.1 + 0.2", DecimalContext::default()).unwrap();
+    assert_eq!(result, "0.3".parse::<Decimal>().unwrap());
+    assert_eq!(result.to_string(), "0.30");
+    let result = evaluate("12345678901234567.89 + 0.01", DecimalContext::default()).unwrap();
+    assert_eq!(result.to_string(), "12345678901234567.90");
+  }
+
+  #[test]
+  fn quotients_are_rounded() {
+    let context = DecimalContext::new(2, Rounding::HalfEven);
+    assert_eq!(evaluate("10 / 3", context).unwrap().to_string(), "3.33");
+    assert_eq!(evaluate("-2 / 3", context).unwrap().to_string(), "-0.67");
+    assert!(evaluate("1 / 0", context).is_err());
+  }
+
+  #[test]
+  fn rounding_modes() {
+    let half = "2.345".parse::<Decimal>().unwrap();
+    assert_eq!(half.round(2, Rounding::HalfEven).to_string(), "2.34");
+    assert_eq!(half.round(2, Rounding::HalfUp).to_string(), "2.35");
+    assert_eq!(half.round(2, Rounding::Truncate).to_string(), "2.34");
+    let negative = "-2.355".parse::<Decimal>().unwrap();
+    assert_eq!(negative.round(2, Rounding::HalfEven).to_string(), "-2.36");
+    assert_eq!(negative.round(2, Rounding::HalfUp).to_string(), "-2.36");
+    assert_eq!(negative.round(2, Rounding::Truncate).to_string(), "-2.35");
+  }
+
+  #[test]
+  fn rounding_functions_are_exact() {
+    let context = DecimalContext::new(2, Rounding::HalfEven);
+    let result = |input: &str| evaluate(input, context).unwrap().to_string();
+    assert_eq!(result("floor(-2.25)"), "-3.00");
+    assert_eq!(result("ceil(-2.25)"), "-2.00");
+    assert_eq!(result("trunc(-2.75)"), "-2.00");
+    assert_eq!(result("frac(-2.25)"), "-0.25");
+    assert_eq!(result("round(2.5)"), "2.00");
+    assert_eq!(result("round(3.5)"), "4.00");
+    assert_eq!(result("sign(-0.01)"), "-1.00");
+    assert_eq!(result("log10(1000)"), "3.00");
+  }
+
+  #[test]
+  fn remainders_and_percentages_are_exact() {
+    let context = DecimalContext::new(2, Rounding::HalfEven);
+    let result = |input: &str| evaluate(input, context).unwrap().to_string();
+    assert_eq!(result("10.25 mod 0.1"), "0.05");
+    assert_eq!(result("-10.25 // 0.1"), "-103.00");
+    assert_eq!(result("19.99 + 12.5%"), "22.49");
+    assert_eq!(result("25!"), "15511210043330985984000000.00");
+    assert!(evaluate("1 mod 0", context).is_err());
+  }
+
+  #[test]
+  fn number_theory_is_exact() {
+    let context = DecimalContext::new(0, Rounding::HalfEven);
+    let result = |input: &str| evaluate(input, context).unwrap().to_string();
+    assert_eq!(result("nCr(100, 50)"), "100891344545564193334812497256");
+    assert_eq!(result("powmod(3, 2^70, 2^64 + 13)"), "2338062408529332737");
+    assert_eq!(result("isprime(2^61 - 1)"), "1");
+    assert_eq!(result("gcd(2^80, 6^40)"), "1099511627776");
+    assert_eq!(result("0xffff_ffff_ffff_ffff + 1"), "18446744073709551616");
+    assert!(evaluate("gcd(1.5, 3)", DecimalContext::default()).is_err());
+    assert!(evaluate("factor(12)", context).is_err());
+  }
+
+  #[test]
+  fn powers_and_overflow() {
+    let context = DecimalContext::new(0, Rounding::HalfEven);
+    let result = |input: &str| evaluate(input, context).map(|d| d.to_string());
+    assert_eq!(result("1^4000000000").unwrap(), "1");
+    assert_eq!(result("3^40").unwrap(), "12157665459056928801");
+    assert!(result("2^4000000000").is_err());
+    // The most negative coefficient has no opposite.
+    assert!(result("abs(-2^126 * 2)").is_err());
+    assert!(result("-(-2^126 * 2)").is_err());
+  }
+
+  #[test]
+  fn assignments_are_remembered() {
+    let context = DecimalContext::new(4, Rounding::HalfUp);
+    let mut memory = HashMap::new();
+    let tokens = Lexer::lex("price = 19.99").unwrap();
+    let ast = Parser::parse(&tokens).unwrap();
+    context.evaluate(&ast, &mut memory).unwrap();
+    let tokens = Lexer::lex("price * 3").unwrap();
+    let ast = Parser::parse(&tokens).unwrap();
+    let result = context.evaluate(&ast, &mut memory).unwrap();
+    assert_eq!(result.to_string(), "59.9700");
+  }
+}
@@ -0,0 +1,397 @@
+//! Probability distributions: densities or masses, cumulative distribution
+//! functions, and their inverses.
+//!
+//! Each distribution `d` has `dpdf` (or `dpmf` if it is discrete), `dcdf`
+//! and `dinv` functions, whose first argument is the point or probability
+//! and whose remaining arguments are the parameters, as in MATLAB.
+
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::lib::special::{beta_i, erfc, erfc_inv, gamma_p, gamma_q, ln_gamma};
+
+/// The names of the distribution functions.
+pub const DISTRIBUTIONS: [&str; 27] = [
+  "betacdf", "betainv", "betapdf", "binomcdf", "binominv", "binompmf", "chi2cdf", "chi2inv",
+  "chi2pdf", "expcdf", "expinv", "exppdf", "fcdf", "finv", "fpdf", "gammacdf", "gammainv",
+  "gammapdf", "normcdf", "norminv", "normpdf", "poisscdf", "poissinv", "poisspmf", "tcdf", "tinv",
+  "tpdf",
+];
+
+/// The most steps of bisection, enough to pin down any `f64`.
+const MAX_BISECTIONS: usize = 2200;
+
+/// How to call the functions of each distribution.
+fn usage(name: &str) -> &'static str {
+  let family = name.trim_end_matches("pdf").trim_end_matches("pmf");
+  let family = family.trim_end_matches("cdf").trim_end_matches("inv");
+  match family {
+    "beta" => "(x, a, b) with shapes a, b > 0",
+    "binom" => "(k, n, p) with n trials of probability p",
+    "chi2" => "(x, k) with k > 0 degrees of freedom",
+    "exp" => "(x, λ) with rate λ > 0",
+    "f" => "(x, d1, d2) with d1, d2 > 0 degrees of freedom",
+    "gamma" => "(x, k, θ) with shape k > 0 and scale θ > 0",
+    "norm" => "(x) or (x, μ, σ) with σ > 0",
+    "poiss" => "(k, λ) with mean λ > 0",
+    _ => "(x, ν) with ν > 0 degrees of freedom",
+  }
+}
+
+/// Requires a parameter to be positive.
+fn positive(name: &str, value: f64) -> Result<f64, String> {
+  if value > 0.0 {
+    Ok(value)
+  } else {
+    Err(format!("{} takes {}, not {}.", name, usage(name), value))
+  }
+}
+
+/// Requires a probability between 0 and 1.
+fn probability(name: &str, p: f64) -> Result<f64, String> {
+  if (0.0..=1.0).contains(&p) {
+    Ok(p)
+  } else {
+    Err(format!("{} needs a probability between 0 and 1, not {}.", name, p))
+  }
+}
+
+/// The smallest `x` in `[lo, hi]` with `cdf(x) ≥ p`, by bisection, first
+/// doubling `hi` until it is large enough if the support is unbounded.
+fn invert(cdf: impl Fn(f64) -> f64, p: f64, mut lo: f64, mut hi: f64, bounded: bool) -> f64 {
+  if !bounded {
+    while cdf(hi) < p {
+      lo = hi;
+      hi *= 2.0;
+      if hi.is_infinite() {
+        return hi;
+      }
+    }
+  }
+  for _ in 0..MAX_BISECTIONS {
+    let middle = lo + (hi - lo) / 2.0;
+    if middle <= lo || middle >= hi {
+      break;
+    }
+    if cdf(middle) < p {
+      lo = middle;
+    } else {
+      hi = middle;
+    }
+  }
+  hi
+}
+
+/// The cumulative distribution function of the standard normal distribution.
+fn normal_cdf(z: f64) -> f64 {
+  0.5 * erfc(-z / SQRT_2)
+}
+
+/// The density of the gamma distribution with shape `k` and scale `theta`.
+fn gamma_pdf(x: f64, k: f64, theta: f64) -> f64 {
+  if x < 0.0 {
+    0.0
+  } else if x == 0.0 {
+    match k.partial_cmp(&1.0) {
+      Some(std::cmp::Ordering::Less) => f64::INFINITY,
+      Some(std::cmp::Ordering::Equal) => 1.0 / theta,
+      _ => 0.0,
+    }
+  } else {
+    ((k - 1.0) * x.ln() - x / theta - ln_gamma(k) - k * theta.ln()).exp()
+  }
+}
+
+/// The logarithm of the beta function.
+fn ln_beta(a: f64, b: f64) -> f64 {
+  ln_gamma(a) + ln_gamma(b) - ln_gamma(a + b)
+}
+
+/// The cumulative distribution function of Student's t distribution.
+fn t_cdf(t: f64, nu: f64) -> f64 {
+  let tail = 0.5 * beta_i(nu / 2.0, 0.5, nu / (nu + t * t));
+  if t > 0.0 {
+    1.0 - tail
+  } else {
+    tail
+  }
+}
+
+/// The cumulative distribution function of the F distribution.
+fn f_cdf(x: f64, d1: f64, d2: f64) -> f64 {
+  if x <= 0.0 {
+    0.0
+  } else {
+    beta_i(d1 / 2.0, d2 / 2.0, d1 * x / (d1 * x + d2))
+  }
+}
+
+/// The probability of `k` successes in `n` trials of probability `p`.
+fn binomial_pmf(k: f64, n: f64, p: f64) -> f64 {
+  if k < 0.0 || k > n || k.fract() != 0.0 {
+    0.0
+  } else if p == 0.0 || p == 1.0 {
+    let certain = if p == 0.0 { 0.0 } else { n };
+    if k == certain {
+      1.0
+    } else {
+      0.0
+    }
+  } else if n <= 1000.0 {
+    // The coefficient by products is exact where the log-gamma is not.
+    let j = k.min(n - k);
+    let coefficient = (1..=j as u32).fold(1.0, |c, i| c * (n - j + i as f64) / i as f64);
+    coefficient.round() * p.powf(k) * (1.0 - p).powf(n - k)
+  } else {
+    (ln_gamma(n + 1.0) - ln_gamma(k + 1.0) - ln_gamma(n - k + 1.0)
+      + k * p.ln()
+      + (n - k) * (-p).ln_1p())
+    .exp()
+  }
+}
+
+/// The probability of at most `k` successes in `n` trials of probability `p`.
+fn binomial_cdf(k: f64, n: f64, p: f64) -> f64 {
+  let k = k.floor();
+  if k < 0.0 {
+    0.0
+  } else if k >= n {
+    1.0
+  } else {
+    beta_i(n - k, k + 1.0, 1.0 - p)
+  }
+}
+
+/// The probability of `k` events of a Poisson process with mean `lambda`.
+fn poisson_pmf(k: f64, lambda: f64) -> f64 {
+  if k < 0.0 || k.fract() != 0.0 {
+    0.0
+  } else {
+    (k * lambda.ln() - lambda - ln_gamma(k + 1.0)).exp()
+  }
+}
+
+/// The probability of at most `k` events of a Poisson process.
+fn poisson_cdf(k: f64, lambda: f64) -> f64 {
+  let k = k.floor();
+  if k < 0.0 {
+    0.0
+  } else {
+    gamma_q(k + 1.0, lambda)
+  }
+}
+
+/// The smallest whole number `k` with `cdf(k) ≥ p`, at most `limit`, found by
+/// bisecting the whole numbers so that large counts take few steps.
+fn discrete_inverse(cdf: impl Fn(f64) -> f64, p: f64, limit: f64) -> f64 {
+  // The distribution function is rounded, so allow for a few ULP when it
+  // should equal p exactly.
+  let p = p * (1.0 - 64.0 * f64::EPSILON);
+  // cdf(low) < p ≤ cdf(high), taking cdf(-1) = 0 and cdf(limit) = 1.
+  let (mut low, mut high) = (-1.0, limit);
+  while high - low > 1.0 {
+    let middle = ((low + high) / 2.0_f64).floor();
+    if cdf(middle) < p {
+      low = middle;
+    } else {
+      high = middle;
+    }
+  }
+  high
+}
+
+/// Requires the whole number of trials of a binomial distribution.
+fn trials(name: &str, n: f64, p: f64) -> Result<(), String> {
+  if n >= 0.0 && n.fract() == 0.0 && (0.0..=1.0).contains(&p) {
+    Ok(())
+  } else {
+    Err(format!("{} takes {}, not n = {} and p = {}.", name, usage(name), n, p))
+  }
+}
+
+/// Evaluates the distribution function `name` at its arguments.
+pub fn distribution(name: &str, arguments: &[f64]) -> Result<f64, String> {
+  let value = match (name, arguments) {
+    ("normpdf" | "normcdf" | "norminv", [x]) => return distribution(name, &[*x, 0.0, 1.0]),
+    ("normpdf", &[x, mu, sigma]) => {
+      let z = (x - mu) / positive(name, sigma)?;
+      (-z * z / 2.0).exp() / (sigma * (2.0 * PI).sqrt())
+    }
+    ("normcdf", &[x, mu, sigma]) => normal_cdf((x - mu) / positive(name, sigma)?),
+    ("norminv", &[p, mu, sigma]) => {
+      mu - positive(name, sigma)? * SQRT_2 * erfc_inv(2.0 * probability(name, p)?)
+    }
+    ("tpdf", &[t, nu]) => {
+      let nu = positive(name, nu)?;
+      (ln_gamma((nu + 1.0) / 2.0) - ln_gamma(nu / 2.0) - (nu + 1.0) / 2.0 * (t * t / nu).ln_1p())
+        .exp()
+        / (nu * PI).sqrt()
+    }
+    ("tcdf", &[t, nu]) => t_cdf(t, positive(name, nu)?),
+    ("tinv", &[p, nu]) => {
+      let (p, nu) = (probability(name, p)?, positive(name, nu)?);
+      // The distribution is symmetric, so only the upper half is searched.
+      let upper = |q: f64| invert(|t| t_cdf(t, nu), q, 0.0, 1.0, false);
+      match p.partial_cmp(&0.5) {
+        Some(std::cmp::Ordering::Greater) => upper(p),
+        Some(std::cmp::Ordering::Less) => -upper(1.0 - p),
+        _ => 0.0,
+      }
+    }
+    ("chi2pdf", &[x, k]) => gamma_pdf(x, positive(name, k)? / 2.0, 2.0),
+    ("chi2cdf", &[x, k]) => gamma_p(positive(name, k)? / 2.0, x / 2.0),
+    ("chi2inv", &[p, k]) => {
+      let (p, k) = (probability(name, p)?, positive(name, k)?);
+      invert(|x| gamma_p(k / 2.0, x / 2.0), p, 0.0, k.max(1.0), false)
+    }
+    ("fpdf", &[x, d1, d2]) => {
+      let (d1, d2) = (positive(name, d1)?, positive(name, d2)?);
+      if x <= 0.0 {
+        0.0
+      } else {
+        (0.5 * (d1 * (d1 * x).ln() + d2 * d2.ln() - (d1 + d2) * (d1 * x + d2).ln())
+          - x.ln()
+          - ln_beta(d1 / 2.0, d2 / 2.0))
+        .exp()
+      }
+    }
+    ("fcdf", &[x, d1, d2]) => f_cdf(x, positive(name, d1)?, positive(name, d2)?),
+    ("finv", &[p, d1, d2]) => {
+      let (p, d1, d2) = (probability(name, p)?, positive(name, d1)?, positive(name, d2)?);
+      invert(|x| f_cdf(x, d1, d2), p, 0.0, 1.0, false)
+    }
+    ("binompmf", &[k, n, p]) => {
+      trials(name, n, p)?;
+      binomial_pmf(k, n, p)
+    }
+    ("binomcdf", &[k, n, p]) => {
+      trials(name, n, p)?;
+      binomial_cdf(k, n, p)
+    }
+    ("binominv", &[q, n, p]) => {
+      trials(name, n, p)?;
+      discrete_inverse(|k| binomial_cdf(k, n, p), probability(name, q)?, n)
+    }
+    ("poisspmf", &[k, lambda]) => poisson_pmf(k, positive(name, lambda)?),
+    ("poisscdf", &[k, lambda]) => poisson_cdf(k, positive(name, lambda)?),
+    ("poissinv", &[p, lambda]) => {
+      let (p, lambda) = (probability(name, p)?, positive(name, lambda)?);
+      if p == 1.0 {
+        f64::INFINITY
+      } else {
+        let limit = lambda + 40.0 * lambda.sqrt() + 100.0;
+        discrete_inverse(|k| poisson_cdf(k, lambda), p, limit)
+      }
+    }
+    ("exppdf", &[x, lambda]) => {
+      let lambda = positive(name, lambda)?;
+      if x < 0.0 {
+        0.0
+      } else {
+        lambda * (-lambda * x).exp()
+      }
+    }
+    ("expcdf", &[x, lambda]) => {
+      let lambda = positive(name, lambda)?;
+      if x < 0.0 {
+        0.0
+      } else {
+        -(-lambda * x).exp_m1()
+      }
+    }
+    ("expinv", &[p, lambda]) => {
+      let lambda = positive(name, lambda)?;
+      -(-probability(name, p)?).ln_1p() / lambda
+    }
+    ("gammapdf", &[x, k, theta]) => gamma_pdf(x, positive(name, k)?, positive(name, theta)?),
+    ("gammacdf", &[x, k, theta]) => gamma_p(positive(name, k)?, x / positive(name, theta)?),
+    ("gammainv", &[p, k, theta]) => {
+      let (p, k, theta) = (probability(name, p)?, positive(name, k)?, positive(name, theta)?);
+      theta * invert(|x| gamma_p(k, x), p, 0.0, k.max(1.0), false)
+    }
+    ("betapdf", &[x, a, b]) => {
+      let (a, b) = (positive(name, a)?, positive(name, b)?);
+      if !(0.0..=1.0).contains(&x) {
+        0.0
+      } else {
+        x.powf(a - 1.0) * (1.0 - x).powf(b - 1.0) / ln_beta(a, b).exp()
+      }
+    }
+    ("betacdf", &[x, a, b]) => beta_i(positive(name, a)?, positive(name, b)?, x),
+    ("betainv", &[p, a, b]) => {
+      let (p, a, b) = (probability(name, p)?, positive(name, a)?, positive(name, b)?);
+      invert(|x| beta_i(a, b, x), p, 0.0, 1.0, true)
+    }
+    _ if DISTRIBUTIONS.contains(&name) => {
+      return Err(format!("{} takes {}.", name, usage(name)));
+    }
+    _ => return Err(format!("Unknown distribution function {}", name)),
+  };
+  Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::distributions::distribution;
+
+  fn check(name: &str, arguments: &[f64], expected: f64) {
+    let actual = distribution(name, arguments).unwrap();
+    assert!(
+      (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+      "{}({:?}) = {}, not {}",
+      name,
+      arguments,
+      actual,
+      expected
+    );
+  }
+
+  #[test]
+  fn continuous() {
+    check("normpdf", &[0.0], 0.398_942_280_401_432_7);
+    check("normcdf", &[1.96], 0.975_002_104_851_780);
+    check("normcdf", &[110.0, 100.0, 5.0], 0.977_249_868_051_820_8);
+    check("norminv", &[0.975], 1.959_963_984_540_054);
+    check("norminv", &[1e-10, 3.0, 2.0], 3.0 - 2.0 * 6.361_340_902_404_056);
+    check("tcdf", &[2.0, 5.0], 0.949_030_260_585_070_7);
+    check("tinv", &[0.975, 10.0], 2.228_138_851_986_274);
+    check("tinv", &[0.025, 10.0], -2.228_138_851_986_274);
+    check("tpdf", &[0.0, 1.0], 1.0 / std::f64::consts::PI);
+    check("chi2cdf", &[3.841_458_820_694_124, 1.0], 0.95);
+    check("chi2inv", &[0.95, 10.0], 18.307_038_053_275_146);
+    check("chi2pdf", &[2.0, 2.0], 0.5 * (-1.0_f64).exp());
+    check("fcdf", &[2.0, 3.0, 10.0], 0.821_992_592_624_82);
+    check("finv", &[0.95, 3.0, 10.0], 3.708_264_819_084_3);
+    check("expcdf", &[1.0, 2.0], 1.0 - (-2.0_f64).exp());
+    check("expinv", &[0.5, 1.0], 2.0_f64.ln());
+    check("gammacdf", &[2.0, 1.0, 2.0], 1.0 - (-1.0_f64).exp());
+    check("gammainv", &[0.5, 3.0, 2.0], 5.348_120_627_447_12);
+    check("betapdf", &[0.5, 2.0, 2.0], 1.5);
+    check("betacdf", &[0.5, 2.0, 3.0], 0.6875);
+    check("betainv", &[0.6875, 2.0, 3.0], 0.5);
+  }
+
+  #[test]
+  fn discrete() {
+    check("binompmf", &[2.0, 4.0, 0.5], 0.375);
+    check("binomcdf", &[2.0, 4.0, 0.5], 0.6875);
+    check("binominv", &[0.6875, 4.0, 0.5], 2.0);
+    check("binompmf", &[1.5, 4.0, 0.5], 0.0);
+    check("binompmf", &[3.0, 4.0, 0.25], 0.046_875);
+    check("poisspmf", &[2.0, 3.0], 4.5 * (-3.0_f64).exp());
+    check("poisscdf", &[2.0, 3.0], 8.5 * (-3.0_f64).exp());
+    check("poissinv", &[0.5, 3.0], 3.0);
+    // Inverses bisect, so large counts are quick.
+    check("binominv", &[0.5, 1e9, 0.5], 5e8);
+    check("binominv", &[0.025, 1e9, 0.3], 299_971_598.0);
+    check("poissinv", &[0.5, 1e8], 1e8);
+    check("poissinv", &[0.975, 1e8], 100_019_600.0);
+  }
+
+  #[test]
+  fn parameters_are_checked() {
+    assert!(distribution("normcdf", &[1.0, 0.0, -1.0]).is_err());
+    assert!(distribution("norminv", &[1.5]).is_err());
+    assert!(distribution("binompmf", &[1.0, 2.5, 0.5]).is_err());
+    assert!(distribution("tcdf", &[1.0]).is_err());
+  }
+}
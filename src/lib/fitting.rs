@@ -0,0 +1,285 @@
+//! Least-squares fits of models to data: straight lines, polynomials, and
+//! any expression in a variable and some parameters.
+//!
+//! Linear models are solved directly with the SVD; nonlinear models by the
+//! Levenberg–Marquardt method with a finite-difference Jacobian. Standard
+//! errors come from the covariance `s² (JᵀJ)⁻¹` at the solution.
+
+use std::fmt;
+
+use crate::lib::linalg;
+use crate::lib::matrix::Matrix;
+
+/// The most iterations of the Levenberg–Marquardt method.
+const MAX_ITERATIONS: usize = 500;
+
+/// The fitted parameters of a model, with their standard errors and the
+/// coefficient of determination R².
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fit {
+  names: Vec<String>,
+  parameters: Vec<f64>,
+  errors: Vec<f64>,
+  r_squared: f64,
+}
+
+impl fmt::Display for Fit {
+  /// With `{:#}`, each parameter is written on its own line.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let separator = if f.alternate() { "\n" } else { ", " };
+    if f.alternate() {
+      writeln!(f)?;
+    }
+    for ((name, parameter), error) in self.names.iter().zip(&self.parameters).zip(&self.errors) {
+      write!(f, "{} = {} ± {}{}", name, parameter, error, separator)?;
+    }
+    write!(f, "R² = {}", self.r_squared)
+  }
+}
+
+impl Fit {
+  /// The fitted parameters, in order.
+  pub fn parameters(&self) -> &[f64] {
+    &self.parameters
+  }
+
+  /// The names of the parameters.
+  pub fn names(&self) -> &[String] {
+    &self.names
+  }
+
+  /// The coefficient of determination.
+  pub fn r_squared(&self) -> f64 {
+    self.r_squared
+  }
+}
+
+/// Requires as many `ys` as `xs`, and more points than parameters so that
+/// the standard errors are defined.
+fn check_points(xs: &[f64], ys: &[f64], parameters: usize) -> Result<(), String> {
+  if xs.len() != ys.len() {
+    Err(format!(
+      "A fit needs as many x values as y values, not {} and {}.",
+      xs.len(),
+      ys.len()
+    ))
+  } else if xs.len() <= parameters {
+    Err(format!(
+      "A fit of {} parameters needs at least {} points, not {}.",
+      parameters,
+      parameters + 1,
+      xs.len()
+    ))
+  } else {
+    Ok(())
+  }
+}
+
+/// Summarizes a fit from the Jacobian of the model at the solution, whose
+/// columns are the derivatives by each parameter.
+fn summarize(
+  names: Vec<String>,
+  parameters: Vec<f64>,
+  jacobian: &Matrix,
+  residuals: &[f64],
+  ys: &[f64],
+) -> Result<Fit, String> {
+  let (n, p) = (jacobian.rows(), jacobian.columns());
+  // (JᵀJ)⁻¹ = V S⁻² Vᵀ, which fails cleanly when parameters are redundant.
+  let (_, s, v) = linalg::svd(jacobian);
+  let largest = (0..p).map(|k| s.get(k, 0)).fold(0.0, f64::max);
+  if (0..p).any(|k| s.get(k, 0) <= largest * f64::EPSILON * n as f64) {
+    return Err(format!(
+      "The parameters {} cannot all be determined from these points.",
+      names.join(", ")
+    ));
+  }
+  let ssr: f64 = residuals.iter().map(|r| r * r).sum();
+  let variance = ssr / (n - p) as f64;
+  let errors = (0..p)
+    .map(|j| {
+      let diagonal: f64 = (0..p).map(|k| (v.get(j, k) / s.get(k, 0)).powi(2)).sum();
+      (variance * diagonal).sqrt()
+    })
+    .collect();
+  let mean = ys.iter().sum::<f64>() / n as f64;
+  let sst: f64 = ys.iter().map(|y| (y - mean) * (y - mean)).sum();
+  Ok(Fit {
+    names,
+    parameters,
+    errors,
+    r_squared: if sst == 0.0 { 1.0 } else { 1.0 - ssr / sst },
+  })
+}
+
+/// Fits the polynomial `c0 + c1 x + … + cd x^d` of degree `d`.
+pub fn polynomial(xs: &[f64], ys: &[f64], degree: usize) -> Result<Fit, String> {
+  check_points(xs, ys, degree + 1)?;
+  let mut vandermonde = Matrix::zeros(xs.len(), degree + 1);
+  for (i, &x) in xs.iter().enumerate() {
+    for j in 0..=degree {
+      vandermonde.set(i, j, x.powi(j as i32));
+    }
+  }
+  let (solution, _) = linalg::lstsq(&vandermonde, &Matrix::new(ys.len(), 1, ys.to_vec()))?;
+  let parameters: Vec<f64> = (0..=degree).map(|j| solution.get(j, 0)).collect();
+  let residuals: Vec<f64> = xs
+    .iter()
+    .zip(ys)
+    .map(|(&x, y)| y - parameters.iter().rev().fold(0.0, |acc, c| acc * x + c))
+    .collect();
+  let names = (0..=degree).map(|j| format!("c{}", j)).collect();
+  summarize(names, parameters, &vandermonde, &residuals, ys)
+}
+
+/// Fits the straight line `intercept + slope x`.
+pub fn linear(xs: &[f64], ys: &[f64]) -> Result<Fit, String> {
+  let mut fit = polynomial(xs, ys, 1)?;
+  fit.names = vec!["intercept".to_string(), "slope".to_string()];
+  Ok(fit)
+}
+
+/// The residuals `y - model(parameters, x)`, or an error naming a point
+/// where the model is not a number.
+fn residuals_of(
+  model: &impl Fn(&[f64], f64) -> f64,
+  parameters: &[f64],
+  xs: &[f64],
+  ys: &[f64],
+) -> Result<Vec<f64>, String> {
+  xs.iter()
+    .zip(ys)
+    .map(|(&x, y)| {
+      let value = model(parameters, x);
+      if value.is_finite() {
+        Ok(y - value)
+      } else {
+        Err(format!("The model is {} at x = {}.", value, x))
+      }
+    })
+    .collect()
+}
+
+/// The derivatives of the model by each parameter at each point, by
+/// forward differences.
+fn jacobian_of(
+  model: &impl Fn(&[f64], f64) -> f64,
+  parameters: &[f64],
+  xs: &[f64],
+) -> Matrix {
+  let mut jacobian = Matrix::zeros(xs.len(), parameters.len());
+  let mut shifted = parameters.to_vec();
+  for j in 0..parameters.len() {
+    let h = f64::EPSILON.sqrt() * parameters[j].abs().max(1.0);
+    shifted[j] = parameters[j] + h;
+    for (i, &x) in xs.iter().enumerate() {
+      jacobian.set(i, j, (model(&shifted, x) - model(parameters, x)) / h);
+    }
+    shifted[j] = parameters[j];
+  }
+  jacobian
+}
+
+/// Fits the parameters named `names` of `model(parameters, x)` by the
+/// Levenberg–Marquardt method, starting from `initial`.
+pub fn nonlinear(
+  model: impl Fn(&[f64], f64) -> f64,
+  names: Vec<String>,
+  initial: Vec<f64>,
+  xs: &[f64],
+  ys: &[f64],
+) -> Result<Fit, String> {
+  check_points(xs, ys, names.len())?;
+  let mut parameters = initial;
+  let mut residuals = residuals_of(&model, &parameters, xs, ys)?;
+  let mut ssr: f64 = residuals.iter().map(|r| r * r).sum();
+  let mut damping = 1e-3;
+  for _ in 0..MAX_ITERATIONS {
+    let jacobian = jacobian_of(&model, &parameters, xs);
+    let transposed = jacobian.transpose();
+    let mut normal = transposed.multiply(&jacobian)?;
+    let gradient = transposed.multiply(&Matrix::new(residuals.len(), 1, residuals.clone()))?;
+    for j in 0..parameters.len() {
+      let diagonal = normal.get(j, j);
+      normal.set(j, j, diagonal + damping * diagonal.max(1e-12));
+    }
+    let step = linalg::solve(&normal, &gradient).ok();
+    let trial: Option<Vec<f64>> =
+      step.map(|step| parameters.iter().enumerate().map(|(j, p)| p + step.get(j, 0)).collect());
+    let accepted = match trial {
+      Some(trial) => match residuals_of(&model, &trial, xs, ys) {
+        Ok(trial_residuals) => {
+          let trial_ssr: f64 = trial_residuals.iter().map(|r| r * r).sum();
+          if trial_ssr <= ssr {
+            let converged = ssr - trial_ssr <= 1e-15 * ssr
+              || trial.iter().zip(&parameters).all(|(t, p)| (t - p).abs() <= 1e-14 * p.abs());
+            parameters = trial;
+            residuals = trial_residuals;
+            ssr = trial_ssr;
+            if converged || ssr == 0.0 {
+              let jacobian = jacobian_of(&model, &parameters, xs);
+              return summarize(names, parameters, &jacobian, &residuals, ys);
+            }
+            true
+          } else {
+            false
+          }
+        }
+        Err(_) => false,
+      },
+      None => false,
+    };
+    if accepted {
+      damping = (damping / 10.0).max(1e-15);
+    } else if damping > 1e15 {
+      // No step improves the fit, so this is a minimum.
+      let jacobian = jacobian_of(&model, &parameters, xs);
+      return summarize(names, parameters, &jacobian, &residuals, ys);
+    } else {
+      damping *= 10.0;
+    }
+  }
+  Err(format!(
+    "The fit of {} did not converge after {} iterations; try a model with fewer parameters.",
+    names.join(", "),
+    MAX_ITERATIONS
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::fitting::{linear, nonlinear, polynomial};
+
+  fn close(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() <= tolerance * b.abs().max(1.0)
+  }
+
+  #[test]
+  fn lines_and_polynomials() {
+    let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let ys = [2.1, 3.9, 6.2, 7.8, 10.1];
+    let fit = linear(&xs, &ys).unwrap();
+    assert!(close(fit.parameters()[0], 0.05, 1e-12));
+    assert!(close(fit.parameters()[1], 1.99, 1e-12));
+    // The standard error of the slope is sqrt(s² / Sxx) with s² = 0.107 / 3.
+    assert!(close(fit.errors[1], (0.107_f64 / 3.0 / 10.0).sqrt(), 1e-9));
+    assert!(close(fit.r_squared(), 1.0 - 0.107 / 39.708, 1e-12));
+    let fit = polynomial(&xs, &[1.0, 4.0, 9.0, 16.0, 25.0], 2).unwrap();
+    assert!(fit.parameters().iter().zip(&[0.0, 0.0, 1.0]).all(|(a, b)| close(*a, *b, 1e-12)));
+    assert!(close(fit.r_squared(), 1.0, 1e-12));
+    assert!(linear(&[1.0, 2.0], &[1.0, 2.0]).is_err());
+    assert!(linear(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0]).is_err());
+  }
+
+  #[test]
+  fn nonlinear_models() {
+    let xs: Vec<f64> = (0..10).map(|i| i as f64 / 2.0).collect();
+    let ys: Vec<f64> = xs.iter().map(|x| 3.0 * (-0.7 * x).exp()).collect();
+    let model = |p: &[f64], x: f64| p[0] * (p[1] * x).exp();
+    let names = vec!["a".to_string(), "b".to_string()];
+    let fit = nonlinear(model, names, vec![1.0, 1.0], &xs, &ys).unwrap();
+    assert!(close(fit.parameters()[0], 3.0, 1e-7));
+    assert!(close(fit.parameters()[1], -0.7, 1e-7));
+    assert!(close(fit.r_squared(), 1.0, 1e-12));
+  }
+}
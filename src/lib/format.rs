@@ -0,0 +1,358 @@
+//! Formats for writing numbers in results: fixed decimals, scientific and
+//! engineering notation, significant figures, and grouping of thousands.
+//!
+//! A format is written as words, as in `fixed 2 group ,` or `eng 3 si`, both
+//! in the `:format` command and after `to` at the end of an expression.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The words that start a format.
+pub const FORMATS: [&str; 6] = ["eng", "fixed", "group", "plain", "sci", "sig"];
+
+/// The SI prefixes for the powers of 1000 from 10<sup>-24</sup> to
+/// 10<sup>24</sup>.
+const SI_PREFIXES: [&str; 17] =
+  ["y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y"];
+
+/// The most digits a format may ask for, the most a double holds.
+pub const MAX_DIGITS: usize = 17;
+
+/// How the digits of a number are laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+  /// The shortest decimal that reads back as the same number
+  Plain,
+  /// A fixed number of decimal places
+  Fixed(usize),
+  /// A mantissa and a power of ten, as in `1.5e-7`, with the given number of
+  /// decimal places or else as few as needed
+  Scientific(Option<usize>),
+  /// Scientific notation whose exponent is a multiple of 3, with the given
+  /// number of significant figures, written with an SI prefix such as `4.7k`
+  /// for the exponent if `si`
+  Engineering { digits: Option<usize>, si: bool },
+  /// The given number of significant figures, in scientific notation if the
+  /// number is too small or too large to show them positionally
+  Significant(usize),
+}
+
+/// How numbers are written in results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NumberFormat {
+  pub notation: Notation,
+  /// The separator of groups of thousands, if any. With `.` as the
+  /// separator, the decimal point is written `,`.
+  pub separator: Option<char>,
+}
+
+impl Default for NumberFormat {
+  fn default() -> NumberFormat {
+    NumberFormat {
+      notation: Notation::Plain,
+      separator: None,
+    }
+  }
+}
+
+impl fmt::Display for NumberFormat {
+  /// Writes the format as the words that select it.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let digits = |digits: Option<usize>| digits.map_or(String::new(), |d| format!(" {}", d));
+    match self.notation {
+      Notation::Plain => write!(f, "plain")?,
+      Notation::Fixed(places) => write!(f, "fixed {}", places)?,
+      Notation::Scientific(places) => write!(f, "sci{}", digits(places))?,
+      Notation::Engineering { digits: figures, si } => {
+        write!(f, "eng{}{}", digits(figures), if si { " si" } else { "" })?
+      }
+      Notation::Significant(figures) => write!(f, "sig {}", figures)?,
+    }
+    match self.separator {
+      Some(' ') => write!(f, " group space"),
+      Some(separator) => write!(f, " group {}", separator),
+      None => Ok(()),
+    }
+  }
+}
+
+impl NumberFormat {
+  /// Changes the format by the words of `spec`, such as `sci 3` or
+  /// `group off`, leaving what they do not mention as it was.
+  pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+    let mut words = spec.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+      let mut number = || words.peek().and_then(|w| w.parse::<usize>().ok()).inspect(|_| {
+        words.next();
+      });
+      let mut number = || match number() {
+        Some(digits) if digits > MAX_DIGITS => Err(format!(
+          "{} takes at most {} digits, not {}.",
+          word, MAX_DIGITS, digits
+        )),
+        digits => Ok(digits),
+      };
+      match word {
+        "plain" => self.notation = Notation::Plain,
+        "fixed" => self.notation = Notation::Fixed(number()?.unwrap_or(2)),
+        "sci" => self.notation = Notation::Scientific(number()?),
+        "eng" => {
+          let digits = number()?.filter(|&d| d > 0);
+          let si = words.peek() == Some(&"si");
+          if si {
+            words.next();
+          }
+          self.notation = Notation::Engineering { digits, si };
+        }
+        "sig" => match number()? {
+          Some(figures) if figures > 0 => self.notation = Notation::Significant(figures),
+          _ => return Err("sig takes a number of significant figures, as in sig 4.".to_string()),
+        },
+        "group" => {
+          self.separator = match words.next() {
+            Some("off") => None,
+            Some("space") => Some(' '),
+            Some(separator @ ("," | "." | "'" | "_")) => separator.chars().next(),
+            _ => {
+              return Err("group takes a separator ',', '.', ''', '_' or space, or off.".to_string())
+            }
+          }
+        }
+        _ => {
+          return Err(format!(
+            "Unknown format '{}'; expected {}.",
+            word,
+            FORMATS.join(", ")
+          ))
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Writes a number in this format.
+  pub fn number(&self, x: f64) -> String {
+    if !x.is_finite() {
+      return x.to_string();
+    }
+    let digits = match self.notation {
+      Notation::Plain => x.to_string(),
+      Notation::Fixed(places) => format!("{:.*}", places, x),
+      Notation::Scientific(Some(places)) => format!("{:.*e}", places, x),
+      Notation::Scientific(None) => format!("{:e}", x),
+      Notation::Engineering { digits, si } => engineering(x, digits, si),
+      Notation::Significant(figures) => significant(x, figures),
+    };
+    match self.separator {
+      Some(separator) => group(&digits, separator),
+      None => digits,
+    }
+  }
+
+  /// Writes an exact integer in this format, with all its digits in plain
+  /// and fixed notation.
+  pub fn integer(&self, n: i128) -> String {
+    let digits = match self.notation {
+      Notation::Plain => n.to_string(),
+      Notation::Fixed(0) => n.to_string(),
+      Notation::Fixed(places) => format!("{}.{}", n, "0".repeat(places)),
+      _ => return self.number(n as f64),
+    };
+    match self.separator {
+      Some(separator) => group(&digits, separator),
+      None => digits,
+    }
+  }
+}
+
+/// Splits scientific notation such as `-4.70e3` into its mantissa and
+/// exponent.
+fn split_exponent(scientific: &str) -> (&str, i32) {
+  match scientific.split_once('e') {
+    Some((mantissa, exponent)) => (mantissa, exponent.parse().unwrap_or(0)),
+    None => (scientific, 0),
+  }
+}
+
+/// Writes `x` with `figures` significant figures, positionally unless the
+/// exponent is below -6 or at least `figures`, as JavaScript's
+/// `toPrecision` does.
+fn significant(x: f64, figures: usize) -> String {
+  let scientific = format!("{:.*e}", figures - 1, x);
+  let (_, exponent) = split_exponent(&scientific);
+  if exponent < -6 || exponent >= figures as i32 {
+    scientific
+  } else {
+    // The rounded value is exact in `figures` digits, so this only moves the
+    // decimal point.
+    let rounded: f64 = scientific.parse().unwrap_or(x);
+    format!("{:.*}", (figures as i32 - 1 - exponent) as usize, rounded)
+  }
+}
+
+/// Writes `x` in engineering notation by moving the decimal point of its
+/// scientific notation right by up to two places.
+fn engineering(x: f64, figures: Option<usize>, si: bool) -> String {
+  let scientific = match figures {
+    Some(figures) => format!("{:.*e}", figures - 1, x),
+    None => format!("{:e}", x),
+  };
+  let (mantissa, exponent) = split_exponent(&scientific);
+  let shift = exponent.rem_euclid(3) as usize;
+  let (sign, mantissa) = match mantissa.strip_prefix('-') {
+    Some(magnitude) => ("-", magnitude),
+    None => ("", mantissa),
+  };
+  let mut digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+  while digits.len() < shift + 1 {
+    digits.push('0');
+  }
+  let (whole, fraction) = digits.split_at(shift + 1);
+  let point = if fraction.is_empty() { "" } else { "." };
+  let mantissa = format!("{}{}{}{}", sign, whole, point, fraction);
+  let exponent = exponent - shift as i32;
+  let prefix = usize::try_from(exponent / 3 + 8).ok().and_then(|k| SI_PREFIXES.get(k));
+  match prefix {
+    _ if exponent == 0 => mantissa,
+    Some(prefix) if si => format!("{}{}", mantissa, prefix),
+    _ => format!("{}e{}", mantissa, exponent),
+  }
+}
+
+/// Separates the thousands of the integer part of a positional number by
+/// `separator`. A `.` separator makes the decimal point a `,`.
+fn group(number: &str, separator: char) -> String {
+  let (sign, unsigned) = match number.strip_prefix('-') {
+    Some(unsigned) => ("-", unsigned),
+    None => ("", number),
+  };
+  let end = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+  let (whole, rest) = unsigned.split_at(end);
+  let mut grouped = String::from(sign);
+  for (k, digit) in whole.chars().enumerate() {
+    if k > 0 && (whole.len() - k).is_multiple_of(3) {
+      grouped.push(separator);
+    }
+    grouped.push(digit);
+  }
+  if separator == '.' {
+    grouped.push_str(&rest.replacen('.', ",", 1));
+  } else {
+    grouped.push_str(rest);
+  }
+  grouped
+}
+
+/// Splits a trailing format off an input line, as in `1/7 to fixed 4` or
+/// `x in sci`, returning the expression and the words of the format.
+pub fn split_format(input: &str) -> Option<(&str, &str)> {
+  input
+    .match_indices(" to ")
+    .chain(input.match_indices(" in "))
+    .map(|(k, keyword)| (k, &input[k + keyword.len()..]))
+    .filter(|(_, spec)| {
+      spec.split_whitespace().next().is_some_and(|word| FORMATS.contains(&word))
+    })
+    .max_by_key(|&(k, _)| k)
+    .map(|(k, spec)| (input[..k].trim(), spec))
+}
+
+/// Values whose numbers can be written in a `NumberFormat`.
+pub trait Format {
+  /// Writes the value as its `Display` does, including the layout chosen by
+  /// `{:#}`, but with its numbers in `numbers`.
+  fn format(&self, f: &mut fmt::Formatter, numbers: &NumberFormat) -> fmt::Result;
+}
+
+impl<T: Format> Format for &T {
+  fn format(&self, f: &mut fmt::Formatter, numbers: &NumberFormat) -> fmt::Result {
+    (*self).format(f, numbers)
+  }
+}
+
+/// A value to be displayed with its numbers in a format.
+pub struct Formatted<T>(pub T, pub NumberFormat);
+
+impl<T: Format> fmt::Display for Formatted<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.0.format(f, &self.1)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::format::{split_format, NumberFormat};
+
+  fn format(spec: &str, x: f64) -> String {
+    let mut format = NumberFormat::default();
+    format.apply(spec).unwrap();
+    format.number(x)
+  }
+
+  #[test]
+  fn notations() {
+    assert_eq!(format("plain", 0.1 + 0.2), "0.30000000000000004");
+    assert_eq!(format("fixed 2", 2.0 / 3.0), "0.67");
+    assert_eq!(format("fixed", -1.005), "-1.00");
+    assert_eq!(format("sci", 123456.0), "1.23456e5");
+    assert_eq!(format("sci 2", 0.000123456), "1.23e-4");
+    assert_eq!(format("sig 3", 2.0 / 3.0), "0.667");
+    assert_eq!(format("sig 4", 1.5), "1.500");
+    assert_eq!(format("sig 3", 123456.0), "1.23e5");
+    assert_eq!(format("sig 3", 0.0), "0.00");
+    assert_eq!(format("sig 2", 99.9), "1.0e2");
+    assert_eq!(format("fixed 2", f64::INFINITY), "inf");
+  }
+
+  #[test]
+  fn engineering_notation() {
+    assert_eq!(format("eng", 4700.0), "4.7e3");
+    assert_eq!(format("eng", 47000.0), "47e3");
+    assert_eq!(format("eng", 470000.0), "470e3");
+    assert_eq!(format("eng", -0.0047), "-4.7e-3");
+    assert_eq!(format("eng", 12.5), "12.5");
+    assert_eq!(format("eng si", 4700.0), "4.7k");
+    assert_eq!(format("eng 3 si", 0.000_001_5), "1.50µ");
+    assert_eq!(format("eng 2 si", 999_999.0), "1.0M");
+    assert_eq!(format("eng si", 1e30), "1e30");
+  }
+
+  #[test]
+  fn grouping() {
+    assert_eq!(format("group ,", 1234567.0), "1,234,567");
+    assert_eq!(format("fixed 2 group ,", -1234.5), "-1,234.50");
+    assert_eq!(format("fixed 2 group .", 1234567.891), "1.234.567,89");
+    assert_eq!(format("group space", 999.0), "999");
+    assert_eq!(format("sci group ,", 1234.0), "1.234e3");
+    let mut numbers = NumberFormat::default();
+    numbers.apply("group ,").unwrap();
+    assert_eq!(numbers.integer(118_264_581_564_861_424), "118,264,581,564,861,424");
+    let mut numbers = NumberFormat::default();
+    numbers.apply("sig 3 group '").unwrap();
+    assert_eq!(numbers.to_string(), "sig 3 group '");
+    numbers.apply("group off").unwrap();
+    assert_eq!(numbers.to_string(), "sig 3");
+    assert!(numbers.apply("sig 0").is_err());
+    assert!(numbers.apply("group ;").is_err());
+    assert!(numbers.apply("hex").is_err());
+  }
+
+  #[test]
+  fn formats_after_expressions() {
+    assert_eq!(split_format("1/7 to fixed 4"), Some(("1/7", "fixed 4")));
+    assert_eq!(split_format("60 mph to km/h"), None);
+    assert_eq!(split_format("(x to m) in sci"), Some(("(x to m)", "sci")));
+    assert_eq!(split_format("2 + 2"), None);
+  }
+
+  #[test]
+  fn digits_are_bounded() {
+    let mut format = NumberFormat::default();
+    assert!(format.apply("fixed 17").is_ok());
+    let error = "fixed takes at most 17 digits, not 400.".to_string();
+    assert_eq!(format.apply("fixed 400"), Err(error));
+    assert!(format.apply("sci 18").is_err());
+    assert!(format.apply("eng 20 si").is_err());
+    assert!(format.apply("sig 100").is_err());
+  }
+}
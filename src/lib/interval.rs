@@ -0,0 +1,629 @@
+//! Interval arithmetic with outward rounding. Every value is an interval
+//! `[lo, hi]` that is guaranteed to contain the exact value of the expression.
+//!
+//! Sums, products, quotients and square roots are rounded to the tightest
+//! enclosing floats by computing their exact rounding errors. The other
+//! functions come from the platform's math library, which is accurate to about
+//! an ulp, so their bounds are widened by two ulps. Arguments outside a
+//! function's domain are discarded, e.g. `sqrt([-1, 4])` is `[0, 2]`.
+
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::apply_function;
+use crate::lib::constants;
+use crate::lib::decimal::Decimal;
+use crate::lib::special;
+
+/// Where the factorial is least, rounded up, beyond which it increases.
+const MIN_FACTORIAL: f64 = 0.461_632_144_968_362_4;
+
+/// A bound on the error of the gamma function, in units in the last place.
+const GAMMA_ULPS: usize = 8;
+
+/// A closed interval of real numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+  /// The lower bound
+  lo: f64,
+  /// The upper bound
+  hi: f64,
+}
+
+impl fmt::Display for Interval {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "[{}, {}]", self.lo, self.hi)
+  }
+}
+
+impl Interval {
+  /// Creates the interval `[lo, hi]`.
+  pub fn new(lo: f64, hi: f64) -> Result<Interval, String> {
+    if lo <= hi {
+      Ok(Interval { lo, hi })
+    } else {
+      Err(format!("[{}, {}] is not an interval.", lo, hi))
+    }
+  }
+
+  /// The interval containing only `value`.
+  pub fn point(value: f64) -> Interval {
+    Interval {
+      lo: value,
+      hi: value,
+    }
+  }
+
+  /// The lower bound.
+  pub fn lo(&self) -> f64 {
+    self.lo
+  }
+
+  /// The upper bound.
+  pub fn hi(&self) -> f64 {
+    self.hi
+  }
+
+  /// Tests whether `value` lies in the interval.
+  pub fn contains(&self, value: f64) -> bool {
+    self.lo <= value && value <= self.hi
+  }
+
+  /// The sum of two intervals.
+  pub fn add(&self, other: &Interval) -> Interval {
+    Interval {
+      lo: round_down(self.lo + other.lo, sum_error(self.lo, other.lo)),
+      hi: round_up(self.hi + other.hi, sum_error(self.hi, other.hi)),
+    }
+  }
+
+  /// The product of two intervals.
+  pub fn multiply(&self, other: &Interval) -> Interval {
+    let products = [
+      (self.lo, other.lo),
+      (self.lo, other.hi),
+      (self.hi, other.lo),
+      (self.hi, other.hi),
+    ];
+    let mut result = Interval {
+      lo: f64::INFINITY,
+      hi: f64::NEG_INFINITY,
+    };
+    for &(a, b) in products.iter() {
+      // 0 × ∞ only arises from unbounded intervals, where it counts as 0.
+      let (product, error) = if a == 0.0 || b == 0.0 {
+        (0.0, 0.0)
+      } else {
+        let product = a * b;
+        (product, exact_error(product, a.mul_add(b, -product)))
+      };
+      result.lo = result.lo.min(round_down(product, error));
+      result.hi = result.hi.max(round_up(product, error));
+    }
+    result
+  }
+
+  /// The quotient of two intervals.
+  pub fn divide(&self, other: &Interval) -> Result<Interval, String> {
+    if other.contains(0.0) {
+      return Err(format!("Division by {}, which contains zero.", other));
+    }
+    let quotients = [
+      (self.lo, other.lo),
+      (self.lo, other.hi),
+      (self.hi, other.lo),
+      (self.hi, other.hi),
+    ];
+    let mut result = Interval {
+      lo: f64::INFINITY,
+      hi: f64::NEG_INFINITY,
+    };
+    for &(a, b) in quotients.iter() {
+      let quotient = a / b;
+      // The exact quotient is q + (a - q b) / b.
+      let error = exact_error(quotient, (-quotient).mul_add(b, a)) * b.signum();
+      result.lo = result.lo.min(round_down(quotient, error));
+      result.hi = result.hi.max(round_up(quotient, error));
+    }
+    Ok(result)
+  }
+
+  /// Raises the interval to an integer power.
+  pub fn powi(&self, n: i64) -> Result<Interval, String> {
+    if n < 0 {
+      return Interval::point(1.0).divide(&self.powi(-n)?);
+    }
+    // Encloses x^n by repeated squaring of |x|, where products are monotone.
+    let endpoint_power = |x: f64| {
+      let mut result = Interval::point(1.0);
+      let mut square = Interval::point(x.abs());
+      let mut k = n;
+      while k > 0 {
+        if k % 2 == 1 {
+          result = result.multiply(&square);
+        }
+        square = square.multiply(&square);
+        k /= 2;
+      }
+      if x < 0.0 && n % 2 == 1 {
+        Interval {
+          lo: -result.hi,
+          hi: -result.lo,
+        }
+      } else {
+        result
+      }
+    };
+    if n % 2 == 1 || self.lo >= 0.0 {
+      // Odd powers, and all powers of non-negative numbers, are increasing.
+      Ok(Interval {
+        lo: endpoint_power(self.lo).lo,
+        hi: endpoint_power(self.hi).hi,
+      })
+    } else if self.hi <= 0.0 {
+      Ok(Interval {
+        lo: endpoint_power(self.hi).lo,
+        hi: endpoint_power(self.lo).hi,
+      })
+    } else {
+      Ok(Interval {
+        lo: 0.0,
+        hi: endpoint_power(self.lo.abs().max(self.hi)).hi,
+      })
+    }
+  }
+
+  /// Raises the interval to the power `exponent`.
+  pub fn pow(&self, exponent: &Interval) -> Result<Interval, String> {
+    match exponent.to_integer() {
+      Some(n) => self.powi(n),
+      None => Ok(exponent.multiply(&self.ln()?).exp()),
+    }
+  }
+
+  /// The absolute value.
+  pub fn abs(&self) -> Interval {
+    if self.lo >= 0.0 {
+      *self
+    } else if self.hi <= 0.0 {
+      Interval {
+        lo: -self.hi,
+        hi: -self.lo,
+      }
+    } else {
+      Interval {
+        lo: 0.0,
+        hi: self.hi.max(-self.lo),
+      }
+    }
+  }
+
+  /// The square root.
+  pub fn sqrt(&self) -> Result<Interval, String> {
+    let x = self.restrict("sqrt", 0.0, f64::INFINITY)?;
+    let root = |x: f64| {
+      let root = x.sqrt();
+      (root, exact_error(root, (-root).mul_add(root, x)))
+    };
+    let (lo, lo_error) = root(x.lo);
+    let (hi, hi_error) = root(x.hi);
+    Ok(Interval {
+      lo: round_down(lo, lo_error),
+      hi: round_up(hi, hi_error),
+    })
+  }
+
+  /// The exponential function.
+  pub fn exp(&self) -> Interval {
+    self.increasing(f64::exp).clamp(0.0, f64::INFINITY)
+  }
+
+  /// The natural logarithm.
+  pub fn ln(&self) -> Result<Interval, String> {
+    Ok(self.restrict("log", 0.0, f64::INFINITY)?.increasing(f64::ln))
+  }
+
+  /// The sine.
+  pub fn sin(&self) -> Interval {
+    self.periodic(f64::sin, FRAC_PI_2, -FRAC_PI_2)
+  }
+
+  /// The cosine.
+  pub fn cos(&self) -> Interval {
+    self.periodic(f64::cos, 0.0, PI)
+  }
+
+  /// The tangent, which is unbounded on intervals containing a pole.
+  pub fn tan(&self) -> Interval {
+    if self.hi - self.lo >= PI || may_contain(self, FRAC_PI_2, PI) {
+      Interval {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+      }
+    } else {
+      self.increasing(f64::tan)
+    }
+  }
+
+  /// The factorial Γ(x + 1), which increases from its minimum near x =
+  /// 0.4616. Factorials of whole numbers up to 22 are exact; otherwise the
+  /// bounds are widened by the error of the gamma function.
+  pub fn factorial(&self) -> Result<Interval, String> {
+    if self.lo < MIN_FACTORIAL {
+      return Err(format!(
+        "In interval mode, ! takes numbers of at least {}, not {}.",
+        MIN_FACTORIAL, self
+      ));
+    }
+    let shifted = self.add(&Interval::point(1.0));
+    let bound = |x: f64, next: fn(f64) -> f64| {
+      let value = special::gamma(x);
+      if x.fract() == 0.0 && x <= 23.0 {
+        value
+      } else {
+        (0..GAMMA_ULPS).fold(value, |value, _| next(value))
+      }
+    };
+    Ok(Interval {
+      lo: bound(shifted.lo, f64::next_down),
+      hi: bound(shifted.hi, f64::next_up),
+    })
+  }
+
+  /// The hyperbolic cosine.
+  pub fn cosh(&self) -> Interval {
+    self.abs().increasing(f64::cosh).clamp(1.0, f64::INFINITY)
+  }
+
+  /// Tests whether the interval is a single integer, returning that integer.
+  fn to_integer(self) -> Option<i64> {
+    if self.lo == self.hi && self.lo.fract() == 0.0 && self.lo.abs() < 9.0e15 {
+      Some(self.lo as i64)
+    } else {
+      None
+    }
+  }
+
+  /// Discards the part of the interval outside the domain `[lo, hi]` of the
+  /// function `name`.
+  fn restrict(&self, name: &str, lo: f64, hi: f64) -> Result<Interval, String> {
+    if self.hi < lo || self.lo > hi {
+      Err(format!("{} is undefined on {}.", name, self))
+    } else {
+      Ok(Interval {
+        lo: self.lo.max(lo),
+        hi: self.hi.min(hi),
+      })
+    }
+  }
+
+  /// Clamps the bounds to the known range `[lo, hi]` of a function.
+  fn clamp(&self, lo: f64, hi: f64) -> Interval {
+    Interval {
+      lo: self.lo.max(lo),
+      hi: self.hi.min(hi),
+    }
+  }
+
+  /// Applies an increasing library function to the bounds.
+  fn increasing(&self, f: fn(f64) -> f64) -> Interval {
+    Interval {
+      lo: widen_down(f(self.lo)),
+      hi: widen_up(f(self.hi)),
+    }
+  }
+
+  /// Applies a rounding function or `sign`, which are increasing and exact,
+  /// so the bounds need no widening.
+  fn step(&self, name: &str) -> Interval {
+    let f = |x: f64| apply_function(name, x).unwrap_or(f64::NAN);
+    Interval {
+      lo: f(self.lo),
+      hi: f(self.hi),
+    }
+  }
+
+  /// The fractional part `x - trunc(x)`, which increases between the
+  /// integers and across zero.
+  fn fract(&self) -> Interval {
+    if self.lo.trunc() == self.hi.trunc() {
+      Interval {
+        lo: self.lo.fract(),
+        hi: self.hi.fract(),
+      }
+    } else {
+      Interval {
+        lo: if self.lo < 0.0 { -1.0 } else { 0.0 },
+        hi: if self.hi > 0.0 { 1.0 } else { 0.0 },
+      }
+    }
+  }
+
+  /// Applies a decreasing library function to the bounds.
+  fn decreasing(&self, f: fn(f64) -> f64) -> Interval {
+    Interval {
+      lo: widen_down(f(self.hi)),
+      hi: widen_up(f(self.lo)),
+    }
+  }
+
+  /// Applies a 2&pi;-periodic function with range `[-1, 1]` that takes its
+  /// maxima at `maximum + 2kπ` and minima at `minimum + 2kπ`.
+  fn periodic(&self, f: fn(f64) -> f64, maximum: f64, minimum: f64) -> Interval {
+    let full = Interval { lo: -1.0, hi: 1.0 };
+    if self.hi - self.lo >= 2.0 * PI || self.lo.is_nan() || self.lo.abs().max(self.hi.abs()) > 1.0e15 {
+      return full;
+    }
+    let (a, b) = (f(self.lo), f(self.hi));
+    let mut result = Interval {
+      lo: widen_down(a.min(b)),
+      hi: widen_up(a.max(b)),
+    };
+    if may_contain(self, maximum, 2.0 * PI) {
+      result.hi = 1.0;
+    }
+    if may_contain(self, minimum, 2.0 * PI) {
+      result.lo = -1.0;
+    }
+    result.clamp(-1.0, 1.0)
+  }
+}
+
+/// Tests whether `x` might contain a point `offset + k period` for an integer
+/// `k`. Points near the bounds count as contained, since &pi; is inexact.
+fn may_contain(x: &Interval, offset: f64, period: f64) -> bool {
+  let first = (x.lo - offset) / period;
+  let last = (x.hi - offset) / period;
+  let margin = 1.0e-9 * (1.0 + first.abs().max(last.abs()));
+  (first - margin).ceil() <= (last + margin).floor()
+}
+
+/// The rounding error `(a + b) - fl(a + b)` of a sum, by Knuth's TwoSum.
+fn sum_error(a: f64, b: f64) -> f64 {
+  let sum = a + b;
+  let b_virtual = sum - a;
+  let a_virtual = sum - b_virtual;
+  exact_error(sum, (a - a_virtual) + (b - b_virtual))
+}
+
+/// Checks a computed rounding error. The error is only exact when the rounded
+/// result is a normal float; otherwise its sign is unknown, signalled by NaN.
+fn exact_error(result: f64, error: f64) -> f64 {
+  if result.is_finite() && (result == 0.0 || result.abs() >= f64::MIN_POSITIVE) {
+    error
+  } else {
+    f64::NAN
+  }
+}
+
+/// Rounds down a result given the sign of its rounding error.
+fn round_down(result: f64, error: f64) -> f64 {
+  if error < 0.0 || error.is_nan() {
+    result.next_down()
+  } else {
+    result
+  }
+}
+
+/// Rounds up a result given the sign of its rounding error.
+fn round_up(result: f64, error: f64) -> f64 {
+  if error > 0.0 || error.is_nan() {
+    result.next_up()
+  } else {
+    result
+  }
+}
+
+/// Widens a lower bound computed by the math library.
+fn widen_down(value: f64) -> f64 {
+  value.next_down().next_down()
+}
+
+/// Widens an upper bound computed by the math library.
+fn widen_up(value: f64) -> f64 {
+  value.next_up().next_up()
+}
+
+/// Interval evaluation. `[lo, hi]` and `x ± dx` create intervals.
+pub struct IntervalArithmetic;
+
+impl Arithmetic for IntervalArithmetic {
+  type Value = Interval;
+
+  /// Literals that floats represent exactly, like `2` or `0.5`, become points;
+  /// the others, like `0.1`, become the narrowest interval of floats around
+  /// them.
+  fn number(&self, value: f64) -> Result<Interval, String> {
+    let exact = match Decimal::from_f64(value) {
+      Ok(decimal) => is_dyadic(decimal, value),
+      Err(_) => false,
+    };
+    if exact {
+      Ok(Interval::point(value))
+    } else {
+      Ok(Interval {
+        lo: value.next_down(),
+        hi: value.next_up(),
+      })
+    }
+  }
+
+  /// A measured constant such as `phys.G` spans one standard uncertainty on
+  /// either side of its value.
+  fn constant(&self, name: &str) -> Result<Interval, String> {
+    let (value, uncertainty) = match name {
+      "pi" => (std::f64::consts::PI, 0.0),
+      "e" => (std::f64::consts::E, 0.0),
+      _ => match constants::find(name) {
+        Some(constant) => (constant.value, constant.uncertainty),
+        None => return Err(format!("Unknown constant {}", name)),
+      },
+    };
+    Ok(Interval {
+      lo: (value - uncertainty).next_down(),
+      hi: (value + uncertainty).next_up(),
+    })
+  }
+
+  fn add(&self, a: &Interval, b: &Interval) -> Result<Interval, String> {
+    Ok(a.add(b))
+  }
+
+  fn multiply(&self, a: &Interval, b: &Interval) -> Result<Interval, String> {
+    Ok(a.multiply(b))
+  }
+
+  fn divide(&self, a: &Interval, b: &Interval) -> Result<Interval, String> {
+    a.divide(b)
+  }
+
+  fn power(&self, base: &Interval, exponent: &Interval) -> Result<Interval, String> {
+    base.pow(exponent)
+  }
+
+  fn function(&self, name: &str, x: &Interval) -> Result<Interval, String> {
+    let reciprocal = |x: &Interval| Interval::point(1.0).divide(x);
+    match name {
+      "abs" => Ok(x.abs()),
+      "acos" => Ok(x.restrict(name, -1.0, 1.0)?.decreasing(f64::acos).clamp(0.0, PI)),
+      "acosh" => Ok(
+        x.restrict(name, 1.0, f64::INFINITY)?
+          .increasing(f64::acosh)
+          .clamp(0.0, f64::INFINITY),
+      ),
+      "acot" => self.function("atan", &reciprocal(x)?),
+      "acoth" => self.function("atanh", &reciprocal(x)?),
+      "acsc" => self.function("asin", &reciprocal(x)?),
+      "acsch" => self.function("asinh", &reciprocal(x)?),
+      "asec" => self.function("acos", &reciprocal(x)?),
+      "asech" => self.function("acosh", &reciprocal(x)?),
+      "asin" => Ok(
+        x.restrict(name, -1.0, 1.0)?
+          .increasing(f64::asin)
+          .clamp(-FRAC_PI_2.next_up(), FRAC_PI_2.next_up()),
+      ),
+      "asinh" => Ok(x.increasing(f64::asinh)),
+      "atan" => Ok(
+        x.increasing(f64::atan)
+          .clamp(-FRAC_PI_2.next_up(), FRAC_PI_2.next_up()),
+      ),
+      "atanh" => Ok(x.restrict(name, -1.0, 1.0)?.increasing(f64::atanh)),
+      "cbrt" => Ok(x.increasing(f64::cbrt)),
+      "ceil" | "floor" | "round" | "sign" | "trunc" => Ok(x.step(name)),
+      "cos" => Ok(x.cos()),
+      "cosh" => Ok(x.cosh()),
+      "cot" => reciprocal(&x.tan()),
+      "coth" => reciprocal(&x.increasing(f64::tanh)),
+      "csc" => reciprocal(&x.sin()),
+      "csch" => reciprocal(&x.increasing(f64::sinh)),
+      "exp" => Ok(x.exp()),
+      "factorial" => x.factorial(),
+      "expm1" => Ok(x.increasing(f64::exp_m1).clamp(-1.0, f64::INFINITY)),
+      "frac" => Ok(x.fract()),
+      "log" => x.ln(),
+      "log10" => Ok(x.restrict(name, 0.0, f64::INFINITY)?.increasing(f64::log10)),
+      "log1p" => Ok(x.restrict(name, -1.0, f64::INFINITY)?.increasing(f64::ln_1p)),
+      "log2" => Ok(x.restrict(name, 0.0, f64::INFINITY)?.increasing(f64::log2)),
+      "sec" => reciprocal(&x.cos()),
+      "sech" => reciprocal(&x.cosh()),
+      "sin" => Ok(x.sin()),
+      "sinh" => Ok(x.increasing(f64::sinh)),
+      "sqrt" => x.sqrt(),
+      "tan" => Ok(x.tan()),
+      "tanh" => Ok(x.increasing(f64::tanh).clamp(-1.0, 1.0)),
+      _ => Err(format!("Unknown function {}", name)),
+    }
+  }
+
+  /// The smallest interval containing both `lo` and `hi`.
+  fn interval(&self, lo: &Interval, hi: &Interval) -> Result<Interval, String> {
+    Interval::new(lo.lo, hi.hi)
+  }
+
+  /// The interval `[value - |error|, value + |error|]`.
+  fn plus_minus(&self, value: &Interval, error: &Interval) -> Result<Interval, String> {
+    let radius = error.abs().hi;
+    Ok(value.add(&Interval {
+      lo: -radius,
+      hi: radius,
+    }))
+  }
+}
+
+/// Tests whether the decimal is exactly the float `value`.
+fn is_dyadic(decimal: Decimal, value: f64) -> bool {
+  // c / 10^s is a dyadic rational exactly when 5^s divides c.
+  let power_of_five = match 5_i128.checked_pow(decimal.scale()) {
+    Some(power) => power,
+    None => return false,
+  };
+  if decimal.coefficient() % power_of_five != 0 {
+    return false;
+  }
+  let numerator = decimal.coefficient() / power_of_five;
+  numerator.unsigned_abs() < (1 << 53)
+    && decimal.scale() < 1000
+    && numerator as f64 / 2_f64.powi(decimal.scale() as i32) == value
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::interval::{Interval, IntervalArithmetic};
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str) -> Result<Interval, String> {
+    let tokens = Lexer::lex(input)?;
+    let ast = Parser::parse(&tokens)?;
+    IntervalArithmetic.evaluate(&ast, &mut HashMap::new())
+  }
+
+  #[test]
+  fn exact_operations_stay_exact() {
+    assert_eq!(evaluate("2 * 3 + 0.5").unwrap(), Interval::point(6.5));
+    assert_eq!(evaluate("[-2, 1]^2").unwrap(), Interval::new(0.0, 4.0).unwrap());
+  }
+
+  #[test]
+  fn inexact_operations_round_outward() {
+    let third = evaluate("1 / 3").unwrap();
+    assert!(third.lo() < third.hi());
+    assert_eq!(third.lo().next_up(), third.hi());
+    let tenth = evaluate("0.1 + 0.2").unwrap();
+    assert!(tenth.lo() < 0.3 && 0.3 < tenth.hi());
+  }
+
+  #[test]
+  fn interval_syntax() {
+    let sum = evaluate("[1.9, 2.1] + 2 ± 0.1").unwrap();
+    assert!(sum.contains(3.8) && sum.contains(4.2));
+    assert!(!sum.contains(3.79) && !sum.contains(4.21));
+    assert!(evaluate("[2, 1]").is_err());
+    assert!(evaluate("1 / [-1, 1]").is_err());
+  }
+
+  #[test]
+  fn functions_enclose_their_range() {
+    let sine = evaluate("sin([0, 4])").unwrap();
+    assert_eq!(sine.hi(), 1.0);
+    assert!(sine.lo() <= 4.0_f64.sin());
+    let root = evaluate("sqrt([-1, 4])").unwrap();
+    assert_eq!(root, Interval::new(0.0, 2.0).unwrap());
+    assert!(evaluate("log([-2, -1])").is_err());
+    assert_eq!(evaluate("floor([-1.5, 2.5])").unwrap(), Interval::new(-2.0, 2.0).unwrap());
+    let fraction = evaluate("frac([2.25, 2.75])").unwrap();
+    assert!(fraction.contains(0.25) && fraction.contains(0.75) && !fraction.contains(0.8));
+    assert_eq!(evaluate("frac([-0.5, 1.5])").unwrap(), Interval::new(-1.0, 1.0).unwrap());
+    let secant = evaluate("sec([-0.1, 0.1])").unwrap();
+    assert!(secant.contains(1.0) && secant.contains(0.1_f64.cos().recip()));
+    assert!(evaluate("csc([-0.1, 0.1])").is_err());
+    assert!(evaluate("log10([1, 1000])").unwrap().contains(3.0));
+    assert_eq!(evaluate("[3, 5]!").unwrap(), Interval::new(6.0, 120.0).unwrap());
+    assert!(evaluate("2.5!").unwrap().contains(3.323_350_970_447_842_6));
+    assert!(evaluate("[-0.5, 1]!").is_err());
+    assert!(evaluate("7 mod 3").unwrap().contains(1.0));
+  }
+}
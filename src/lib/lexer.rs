@@ -1,8 +1,23 @@
 //! Lexical analyzer for the calculator
 
+use crate::lib::angle::AngleUnit;
+use crate::lib::constants::{self, NAMESPACES};
 use crate::lib::token::{recognize_identifier, Token};
 use crate::lib::unlines;
 
+/// The superscript digits from 0 to 9.
+const SUPERSCRIPT_DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+/// The subscript digits from 0 to 9.
+const SUBSCRIPT_DIGITS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+
+/// Integers above this are not all exact in `f64`.
+const MAX_EXACT: u128 = 1 << 53;
+
+/// The most significant digits that every `f64` keeps, so that a literal with
+/// more is lexed as a `Decimal`.
+const MAX_DIGITS: usize = 15;
+
 /// Lexer state
 pub struct Lexer {
   /// The beginning index of a token's first code point in the source string
@@ -24,7 +39,7 @@ impl Lexer {
   /// 
   /// ```
   /// assert_eq!(Lexer::lex("("), Ok(Token::LParen));
-  /// assert_eq!(Lexer::lex("2.71828182845904523536"), Ok(Token::Number(2.71828182845904523536)));
+  /// assert_eq!(Lexer::lex("2.71828182845905"), Ok(Token::Number(2.71828182845905)));
   /// assert!(Lexer::lex("0.1.0").is_err());
   /// ```
   pub fn lex(input: &str) -> Result<Vec<Token>, String> {
@@ -37,7 +52,7 @@ impl Lexer {
         Err(message) => messages.push(message),
       }
     }
-    if messages.len() == 0 {
+    if messages.is_empty() {
       tokens.push(Token::Eoi);
       Ok(tokens)
     } else {
@@ -70,32 +85,189 @@ impl Lexer {
         self.advance();
         Ok(Token::RParen)
       }
+      '[' => {
+        self.advance();
+        Ok(Token::LBracket)
+      }
+      ']' => {
+        self.advance();
+        Ok(Token::RBracket)
+      }
+      '{' => {
+        self.advance();
+        Ok(Token::LBrace)
+      }
+      '}' => {
+        self.advance();
+        Ok(Token::RBrace)
+      }
+      ',' => {
+        self.advance();
+        Ok(Token::Comma)
+      }
+      ';' => {
+        self.advance();
+        Ok(Token::Semicolon)
+      }
       '+' => {
         self.advance();
         Ok(Token::Plus)
       }
-      '-' => {
+      '-' if self.peek() == '>' => {
+        self.advance();
+        self.advance();
+        Ok(Token::Arrow)
+      }
+      // The Unicode minus sign, as copied from typeset text
+      '-' | '−' => {
         self.advance();
         Ok(Token::Minus)
       }
-      '*' => {
+      '*' | '×' | '·' | '⋅' => {
         self.advance();
         Ok(Token::Star)
       }
+      '÷' => {
+        self.advance();
+        Ok(Token::Slash)
+      }
+      '√' => {
+        self.advance();
+        Ok(Token::Function("sqrt".to_string()))
+      }
+      '∛' => {
+        self.advance();
+        Ok(Token::Function("cbrt".to_string()))
+      }
+      '⌊' => {
+        self.advance();
+        Ok(Token::LFloor)
+      }
+      '⌋' => {
+        self.advance();
+        Ok(Token::RFloor)
+      }
+      '⌈' => {
+        self.advance();
+        Ok(Token::LCeil)
+      }
+      '⌉' => {
+        self.advance();
+        Ok(Token::RCeil)
+      }
+      c if is_superscript(c) => self.lex_superscript(),
+      '_' if self.peek().is_ascii_digit() => {
+        self.advance();
+        let base = self.lex_decimal();
+        base
+          .parse()
+          .map(Token::Subscript)
+          .map_err(|_| format!("Failed to parse '{}' as a number.", base))
+      }
+      c if SUBSCRIPT_DIGITS.contains(&c) => {
+        let mut base = 0.0;
+        while let Some(digit) = SUBSCRIPT_DIGITS.iter().position(|&d| d == self.current) {
+          base = 10.0 * base + digit as f64;
+          self.advance();
+        }
+        Ok(Token::Subscript(base))
+      }
+      '/' if self.peek() == '/' => {
+        self.advance();
+        self.advance();
+        Ok(Token::SlashSlash)
+      }
       '/' => {
         self.advance();
         Ok(Token::Slash)
       }
+      '%' => {
+        self.advance();
+        Ok(Token::Percent)
+      }
+      '!' if self.peek() == '=' => {
+        self.advance();
+        self.advance();
+        Ok(Token::BangEqual)
+      }
+      '!' => {
+        self.advance();
+        Ok(Token::Bang)
+      }
       '^' => {
         self.advance();
         Ok(Token::Caret)
       }
+      '&' => {
+        self.advance();
+        Ok(Token::Ampersand)
+      }
+      '|' => {
+        self.advance();
+        Ok(Token::Bar)
+      }
+      '~' => {
+        self.advance();
+        Ok(Token::Tilde)
+      }
+      '<' | '>' | '=' if "<>=".contains(self.peek()) => {
+        let operator = (self.current, self.peek());
+        self.advance();
+        self.advance();
+        match operator {
+          ('<', '<') => Ok(Token::LessLess),
+          ('>', '>') => Ok(Token::GreaterGreater),
+          ('<', '=') => Ok(Token::LessEqual),
+          ('>', '=') => Ok(Token::GreaterEqual),
+          ('=', '=') => Ok(Token::EqualEqual),
+          (c, d) => Err(format!("Unrecognized operator {}{}", c, d)),
+        }
+      }
+      '<' => {
+        self.advance();
+        Ok(Token::Less)
+      }
+      '>' => {
+        self.advance();
+        Ok(Token::Greater)
+      }
+      '≤' => {
+        self.advance();
+        Ok(Token::LessEqual)
+      }
+      '≥' => {
+        self.advance();
+        Ok(Token::GreaterEqual)
+      }
+      '≠' => {
+        self.advance();
+        Ok(Token::BangEqual)
+      }
       '=' => {
         self.advance();
         Ok(Token::Equals)
       }
-      c if c.is_ascii_digit() || c == '.' => self.lex_number().map(|n| Token::Number(n)),
-      c if c.is_alphabetic() => self.lex_identifier().map(|id| recognize_identifier(&id)),
+      '?' => {
+        self.advance();
+        Ok(Token::Question)
+      }
+      ':' => {
+        self.advance();
+        Ok(Token::Colon)
+      }
+      '±' => {
+        self.advance();
+        Ok(Token::PlusMinus)
+      }
+      '.' if self.peek() == '.' => {
+        self.advance();
+        self.advance();
+        Ok(Token::DotDot)
+      }
+      c if c.is_ascii_digit() || c == '.' => self.lex_number(),
+      c if c.is_alphabetic() || c == '°' => {
+        self.lex_identifier().map(|id| recognize_identifier(&id))
+      }
       c => {
         self.advance();
         Err(format!("Unrecognized character {}", c))
@@ -117,6 +289,11 @@ impl Lexer {
     }
   }
 
+  /// The code point after the current one, or `'\0'` at the end of input.
+  fn peek(&self) -> char {
+    self.source[self.current_end..].chars().next().unwrap_or('\0')
+  }
+
   /// Skips over whitespace in the source string.
   fn skip_whitespace(&mut self) {
     while self.current.is_whitespace() {
@@ -129,32 +306,211 @@ impl Lexer {
     self.current_start >= self.current_end
   }
 
-  /// Lexes and parses a number into a `f64` float.
-  fn lex_number(&mut self) -> Result<f64, String> {
+  /// Lexes and parses a number into a `f64` float. A number ends before the
+  /// `..` of a range, so `1..10` is `1`, `..`, `10`. Integers written in
+  /// hexadecimal, octal or binary, and those too large to be exact in `f64`,
+  /// are lexed as `Integer`s instead, other numbers with more than 15
+  /// significant digits as `Decimal`s, and a number followed by a unit of
+  /// angle is an `Angle`. A number in scientific notation, as in `1e-3`, is
+  /// always a `Number`.
+  fn lex_number(&mut self) -> Result<Token, String> {
+    if let Some(radix) = self.radix_prefix() {
+      return self.lex_radix(radix);
+    }
+    let numeric_string = self.lex_decimal();
+    let exponent = self.lex_exponent();
+    if !exponent.is_empty() {
+      let numeral = numeric_string + &exponent;
+      return match numeral.parse::<f64>() {
+        Ok(number) => self.lex_angle(number),
+        Err(_) => Err(format!("Failed to parse '{}' as a number.", numeral)),
+      };
+    }
+    match numeric_string.parse::<u128>() {
+      Ok(integer) if integer > MAX_EXACT => Ok(Token::Integer(integer)),
+      _ => match numeric_string.parse::<f64>() {
+        Ok(number) => match self.lex_angle(number)? {
+          Token::Number(_) if significant_digits(&numeric_string) > MAX_DIGITS => {
+            Ok(Token::Decimal(numeric_string))
+          }
+          token => Ok(token),
+        },
+        Err(_) => Err(format!("Failed to parse '{}' as a number.", numeric_string)),
+      },
+    }
+  }
+
+  /// Lexes the digits and decimal point of a number.
+  fn lex_decimal(&mut self) -> String {
     let mut numeric_chars: Vec<char> = Vec::new();
-    while (self.current.is_ascii_digit() || self.current == '.') && !self.hit_eoi() {
+    while (self.current.is_ascii_digit() || (self.current == '.' && self.peek() != '.'))
+      && !self.hit_eoi()
+    {
       numeric_chars.push(self.current);
       self.advance();
     }
-    let numeric_string = numeric_chars.iter().collect::<String>();
-    numeric_string
-      .parse::<f64>()
-      .map_err(|_| format!("Failed to parse '{}' as a number.", numeric_string))
+    numeric_chars.iter().collect::<String>()
+  }
+
+  /// Lexes the exponent of scientific notation after the digits of a number:
+  /// `e` or `E`, an optional sign and digits, as in `1e-3` or `6.02E23`. An
+  /// `e` followed by anything else is left as a name, so `2e` is `2 e`.
+  fn lex_exponent(&mut self) -> String {
+    let mut chars = self.source[self.current_start..].chars();
+    let exponent = match (chars.next(), chars.next(), chars.next()) {
+      (Some('e' | 'E'), Some('+' | '-'), Some(digit)) => digit.is_ascii_digit(),
+      (Some('e' | 'E'), Some(digit), _) => digit.is_ascii_digit(),
+      _ => false,
+    };
+    let mut text = String::new();
+    if exponent {
+      text.push(self.current);
+      self.advance();
+      if self.current == '+' || self.current == '-' {
+        text.push(self.current);
+        self.advance();
+      }
+      while self.current.is_ascii_digit() && !self.hit_eoi() {
+        text.push(self.current);
+        self.advance();
+      }
+    }
+    text
+  }
+
+  /// Lexes the unit of angle directly after the number `value`, if there is
+  /// one: `deg`, `rad`, `grad`, or `°`, which may be followed by minutes and
+  /// seconds as in `12°30'15"`. A `°` starting a unit such as `°C` is not an
+  /// angle.
+  fn lex_angle(&mut self, value: f64) -> Result<Token, String> {
+    if self.current == '°' && !self.peek().is_alphabetic() {
+      self.advance();
+      let (mut degrees, mut minutes, mut seconds) = (value, false, false);
+      while self.current.is_ascii_digit() {
+        let part = self.lex_decimal();
+        let part: f64 = part
+          .parse()
+          .map_err(|_| format!("Failed to parse '{}' as a number.", part))?;
+        match self.current {
+          '\'' | '′' if !minutes && !seconds => {
+            minutes = true;
+            degrees += part / 60.0;
+          }
+          '"' | '″' if !seconds => {
+            seconds = true;
+            degrees += part / 3600.0;
+          }
+          _ => {
+            let text = &self.source[self.initial..self.current_start];
+            return Err(format!("Expected minutes ' or seconds \" after {}", text));
+          }
+        }
+        self.advance();
+      }
+      return Ok(Token::Angle(degrees, AngleUnit::Degrees));
+    }
+    let rest = &self.source[self.current_start..];
+    for unit in &["deg", "rad", "grad"] {
+      // The unit must end where a name would, so `2radius` is not an angle.
+      let next = rest.strip_prefix(unit).map(|after| after.chars().next());
+      if matches!(next, Some(next) if !next.is_some_and(char::is_alphanumeric)) {
+        for _ in 0..unit.len() {
+          self.advance();
+        }
+        return Ok(Token::Angle(value, unit.parse()?));
+      }
+    }
+    Ok(Token::Number(value))
+  }
+
+  /// The radix of a `0x`, `0o` or `0b` prefix starting at the current code
+  /// point, if a digit in that radix follows it.
+  fn radix_prefix(&self) -> Option<u32> {
+    let mut chars = self.source[self.current_start..].chars();
+    let radix = match (chars.next(), chars.next()) {
+      (Some('0'), Some('x')) => 16,
+      (Some('0'), Some('o')) => 8,
+      (Some('0'), Some('b')) => 2,
+      _ => return None,
+    };
+    chars.next().filter(|c| c.is_digit(radix)).map(|_| radix)
+  }
+
+  /// Lexes an integer with a radix prefix, such as `0xff`, `0o17` or
+  /// `0b1010_0101`, whose digits may be grouped by underscores.
+  fn lex_radix(&mut self, radix: u32) -> Result<Token, String> {
+    self.advance();
+    self.advance();
+    let mut digits = String::new();
+    while (self.current.is_digit(radix) || self.current == '_') && !self.hit_eoi() {
+      if self.current != '_' {
+        digits.push(self.current);
+      }
+      self.advance();
+    }
+    if self.current.is_alphanumeric() && !ends_name(self.current) {
+      while self.current.is_alphanumeric() && !self.hit_eoi() {
+        self.advance();
+      }
+      let text = &self.source[self.initial..self.current_start];
+      return Err(format!("Failed to parse '{}' as a number.", text));
+    }
+    let text = &self.source[self.initial..self.current_start];
+    match u128::from_str_radix(&digits, radix) {
+      Ok(integer) => Ok(Token::Integer(integer)),
+      Err(_) => Err(format!("The integer {} has more than 128 bits.", text)),
+    }
+  }
+
+  /// Lexes an exponent written in superscript, such as `²` or `⁻¹`.
+  fn lex_superscript(&mut self) -> Result<Token, String> {
+    let sign = match self.current {
+      '⁻' => -1.0,
+      _ => 1.0,
+    };
+    if self.current == '⁻' || self.current == '⁺' {
+      self.advance();
+    }
+    let mut exponent: Option<f64> = None;
+    while let Some(digit) = SUPERSCRIPT_DIGITS.iter().position(|&d| d == self.current) {
+      exponent = Some(10.0 * exponent.unwrap_or(0.0) + digit as f64);
+      self.advance();
+    }
+    match exponent {
+      Some(exponent) => Ok(Token::Superscript(sign * exponent)),
+      None => Err("Expected superscript digits after a superscript sign.".to_string()),
+    }
   }
 
-  /// Lexes an identifier
+  /// Lexes an identifier. Identifiers may also start with a degree sign, as
+  /// in the unit `°C`, and the names of library constants are qualified by a
+  /// namespace, as in `phys.k_B`.
   fn lex_identifier(&mut self) -> Result<String, String> {
     let mut chars: Vec<char> = Vec::new();
-    if self.current.is_alphabetic() {
+    if self.current.is_alphabetic() || self.current == '°' {
       chars.push(self.current);
       self.advance();
     }
-    while self.current.is_alphanumeric() && !self.hit_eoi() {
+    while self.current.is_alphanumeric() && !ends_name(self.current) && !self.hit_eoi() {
       chars.push(self.current);
       self.advance();
     }
+    let prefix = chars.iter().collect::<String>();
+    if NAMESPACES.contains(&prefix.as_str()) && self.current == '.' {
+      chars.push(self.current);
+      self.advance();
+      while (self.current.is_alphanumeric() || self.current == '_') && !self.hit_eoi() {
+        chars.push(self.current);
+        self.advance();
+      }
+      let name = chars.iter().collect::<String>();
+      return match constants::find(&name) {
+        Some(_) => Ok(name),
+        None => Err(format!("Unknown constant {}", name)),
+      };
+    }
     let identifier = chars.iter().collect::<String>();
-    if identifier.len() > 0 {
+    if !identifier.is_empty() {
       Ok(identifier)
     } else {
       Err("Identifiers must be alphanumeric and start with an alphabetic character.".to_string())
@@ -162,21 +518,44 @@ impl Lexer {
   }
 }
 
+/// Tests whether a code point is part of a superscript exponent.
+fn is_superscript(c: char) -> bool {
+  c == '⁻' || c == '⁺' || SUPERSCRIPT_DIGITS.contains(&c)
+}
+
+/// Tests whether an alphanumeric code point ends a name, as superscripts
+/// and subscripts do in `x²` or `log₂`.
+fn ends_name(c: char) -> bool {
+  is_superscript(c) || SUBSCRIPT_DIGITS.contains(&c)
+}
+
+/// The number of significant digits in a decimal numeral, from the first
+/// nonzero digit to the last.
+fn significant_digits(numeral: &str) -> usize {
+  let digits: String = numeral.chars().filter(char::is_ascii_digit).collect();
+  digits.trim_matches('0').len()
+}
+
 #[cfg(test)]
 mod test {
+  use crate::lib::angle::AngleUnit;
   use crate::lib::lexer::Lexer;
   use crate::lib::lexer::Token;
 
   #[test]
+  #[allow(clippy::approx_constant)]
   fn test_parse_number() {
-    let tokens = Lexer::lex("2.71828182845904523536");
+    let tokens = Lexer::lex("2.71828182845905 2.71828182845904523536 0.100000000000000000");
     assert!(tokens.is_ok());
     let tokens = tokens.unwrap();
     let mut tokens = tokens.iter();
+    assert_eq!(tokens.next().unwrap(), &Token::Number(2.71828182845905));
+    // Longer numbers keep their digits.
     assert_eq!(
       tokens.next().unwrap(),
-      &Token::Number(2.71828182845904523536)
+      &Token::Decimal("2.71828182845904523536".to_string())
     );
+    assert_eq!(tokens.next().unwrap(), &Token::Number(0.1));
   }
 
   #[test]
@@ -223,7 +602,180 @@ mod test {
     let mut token = token.iter();
     assert_eq!(token.next().unwrap(), &Token::Caret);
 
-    let token = Lexer::lex("&");
+    let token = Lexer::lex("$");
     assert!(token.is_err());
   }
+
+  #[test]
+  fn test_ranges_and_lambdas() {
+    let tokens = Lexer::lex("1..10 step .5").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Number(1.0),
+        Token::DotDot,
+        Token::Number(10.0),
+        Token::Step,
+        Token::Number(0.5),
+        Token::Eoi
+      ]
+    );
+    let tokens = Lexer::lex("x -> x - 1").unwrap();
+    assert_eq!(tokens[1], Token::Arrow);
+    assert_eq!(tokens[3], Token::Minus);
+    assert!(Lexer::lex("1.2.3").is_err());
+  }
+
+  #[test]
+  fn test_remainders_and_factorials() {
+    let tokens = Lexer::lex("7 // 2 % 3 mod 4!").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Number(7.0),
+        Token::SlashSlash,
+        Token::Number(2.0),
+        Token::Percent,
+        Token::Number(3.0),
+        Token::Mod,
+        Token::Number(4.0),
+        Token::Bang,
+        Token::Eoi
+      ]
+    );
+  }
+
+  #[test]
+  fn test_bitwise_operators_and_radix_integers() {
+    let tokens = Lexer::lex("~0xff_ff & 0b1010 | 0o17 xor 1 << 2 >> 3").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Tilde,
+        Token::Integer(0xffff),
+        Token::Ampersand,
+        Token::Integer(0b1010),
+        Token::Bar,
+        Token::Integer(0o17),
+        Token::Xor,
+        Token::Number(1.0),
+        Token::LessLess,
+        Token::Number(2.0),
+        Token::GreaterGreater,
+        Token::Number(3.0),
+        Token::Eoi
+      ]
+    );
+    let tokens = Lexer::lex("18446744073709551615").unwrap();
+    assert_eq!(tokens[0], Token::Integer(u64::MAX as u128));
+    assert!(Lexer::lex("0b102").is_err());
+    assert!(Lexer::lex("0x1g").is_err());
+  }
+
+  #[test]
+  fn test_angles() {
+    let tokens = Lexer::lex("30° + 12°30'15\" - 1.2rad * 50grad / 2deg").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Angle(30.0, AngleUnit::Degrees),
+        Token::Plus,
+        Token::Angle(12.0 + 30.0 / 60.0 + 15.0 / 3600.0, AngleUnit::Degrees),
+        Token::Minus,
+        Token::Angle(1.2, AngleUnit::Radians),
+        Token::Star,
+        Token::Angle(50.0, AngleUnit::Gradians),
+        Token::Slash,
+        Token::Angle(2.0, AngleUnit::Degrees),
+        Token::Eoi
+      ]
+    );
+    let seconds = Token::Angle(10.0 + 15.0 / 3600.0, AngleUnit::Degrees);
+    assert_eq!(Lexer::lex("10°15\"").unwrap()[0], seconds);
+    assert_eq!(Lexer::lex("20°C").unwrap()[1], Token::Identifier("°C".to_string()));
+    assert_eq!(Lexer::lex("2radius").unwrap()[1], Token::Identifier("radius".to_string()));
+    assert_eq!(Lexer::lex("2 deg").unwrap()[1], Token::Identifier("deg".to_string()));
+    assert!(Lexer::lex("12°30").is_err());
+    assert!(Lexer::lex("12°30\"15'").is_err());
+  }
+
+  #[test]
+  fn test_unicode_notation() {
+    let tokens = Lexer::lex("2×3÷4−x²·√y⁻¹ ⌊⌋⌈⌉").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Number(2.0),
+        Token::Star,
+        Token::Number(3.0),
+        Token::Slash,
+        Token::Number(4.0),
+        Token::Minus,
+        Token::Identifier("x".to_string()),
+        Token::Superscript(2.0),
+        Token::Star,
+        Token::Function("sqrt".to_string()),
+        Token::Identifier("y".to_string()),
+        Token::Superscript(-1.0),
+        Token::LFloor,
+        Token::RFloor,
+        Token::LCeil,
+        Token::RCeil,
+        Token::Eoi
+      ]
+    );
+    assert_eq!(Lexer::lex("10¹²").unwrap()[1], Token::Superscript(12.0));
+    assert!(Lexer::lex("x⁻").is_err());
+    let tokens = Lexer::lex("log₁₀ log_2.5 x_1").unwrap();
+    assert_eq!(tokens[1], Token::Subscript(10.0));
+    assert_eq!(tokens[3], Token::Subscript(2.5));
+  }
+
+  #[test]
+  fn test_comparisons_and_conditions() {
+    let tokens = Lexer::lex("a<b<=c>d>=k==f!=g≤≥≠ if not x then true else c ? 1 : 0").unwrap();
+    assert_eq!(
+      tokens,
+      vec![
+        Token::Identifier("a".to_string()),
+        Token::Less,
+        Token::Identifier("b".to_string()),
+        Token::LessEqual,
+        Token::Identifier("c".to_string()),
+        Token::Greater,
+        Token::Identifier("d".to_string()),
+        Token::GreaterEqual,
+        Token::Identifier("k".to_string()),
+        Token::EqualEqual,
+        Token::Identifier("f".to_string()),
+        Token::BangEqual,
+        Token::Identifier("g".to_string()),
+        Token::LessEqual,
+        Token::GreaterEqual,
+        Token::BangEqual,
+        Token::If,
+        Token::Not,
+        Token::Identifier("x".to_string()),
+        Token::Then,
+        Token::Boolean(true),
+        Token::Else,
+        Token::Identifier("c".to_string()),
+        Token::Question,
+        Token::Number(1.0),
+        Token::Colon,
+        Token::Number(0.0),
+        Token::Eoi
+      ]
+    );
+    assert_eq!(Lexer::lex("1<<2").unwrap()[1], Token::LessLess);
+    assert!(Lexer::lex("a <> b").is_err());
+  }
+
+  #[test]
+  fn test_namespaced_constants() {
+    let tokens = Lexer::lex("phys.k_B*T").unwrap();
+    assert_eq!(tokens[0], Token::Constant("phys.k_B".to_string()));
+    assert_eq!(tokens[2], Token::Identifier("T".to_string()));
+    assert!(Lexer::lex("phys.nothing").is_err());
+  }
 }
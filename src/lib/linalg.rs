@@ -0,0 +1,340 @@
+//! Solvers and decompositions for matrices: linear systems by LU
+//! decomposition, least squares, QR and singular value decompositions, and
+//! the eigenvalues of symmetric matrices.
+
+use crate::lib::matrix::Matrix;
+
+/// The most sweeps of Jacobi rotations before giving up on convergence.
+const MAX_SWEEPS: usize = 100;
+
+/// Systems whose condition number exceeds this may lose more than half of
+/// their significant digits.
+pub const ILL_CONDITIONED: f64 = 6.7e7;
+
+/// The sign of `x`, taking the sign of zero as positive.
+fn sign(x: f64) -> f64 {
+  if x < 0.0 {
+    -1.0
+  } else {
+    1.0
+  }
+}
+
+/// Reads the right-hand side `b` of the system `a x = b`: a vector with one
+/// entry per equation in either orientation, or a matrix with one row per
+/// equation.
+fn right_hand_side(a: &Matrix, b: &Matrix) -> Result<Matrix, String> {
+  if b.rows() == a.rows() {
+    Ok(b.clone())
+  } else if b.rows() == 1 && b.columns() == a.rows() {
+    Ok(b.transpose())
+  } else {
+    Err(format!(
+      "Cannot solve with {} and {}: the right-hand side needs {} rows.",
+      a.describe(),
+      b.describe(),
+      a.rows()
+    ))
+  }
+}
+
+/// Solves `a x = b` for a square matrix `a` by LU decomposition with partial
+/// pivoting.
+pub fn solve(a: &Matrix, b: &Matrix) -> Result<Matrix, String> {
+  let n = a.rows();
+  if a.columns() != n {
+    return Err(format!(
+      "Cannot solve with {}: it is not square; use lstsq for least squares.",
+      a.describe()
+    ));
+  }
+  let mut lu = a.clone();
+  let mut x = right_hand_side(a, b)?;
+  for k in 0..n {
+    let pivot = (k..n)
+      .max_by(|&i, &j| lu.get(i, k).abs().total_cmp(&lu.get(j, k).abs()))
+      .unwrap_or(k);
+    if lu.get(pivot, k) == 0.0 {
+      return Err(format!(
+        "Cannot solve with {}: it is singular (condition number {:e}).",
+        a.describe(),
+        condition_number(a)
+      ));
+    }
+    swap_rows(&mut lu, k, pivot);
+    swap_rows(&mut x, k, pivot);
+    for i in k + 1..n {
+      let ratio = lu.get(i, k) / lu.get(k, k);
+      lu.set(i, k, ratio);
+      for j in k + 1..n {
+        lu.set(i, j, lu.get(i, j) - ratio * lu.get(k, j));
+      }
+      for j in 0..x.columns() {
+        x.set(i, j, x.get(i, j) - ratio * x.get(k, j));
+      }
+    }
+  }
+  for k in (0..n).rev() {
+    for j in 0..x.columns() {
+      let sum: f64 = (k + 1..n).map(|l| lu.get(k, l) * x.get(l, j)).sum();
+      x.set(k, j, (x.get(k, j) - sum) / lu.get(k, k));
+    }
+  }
+  Ok(x)
+}
+
+/// Swaps two rows of a matrix.
+fn swap_rows(matrix: &mut Matrix, i: usize, k: usize) {
+  if i != k {
+    for j in 0..matrix.columns() {
+      let entry = matrix.get(i, j);
+      matrix.set(i, j, matrix.get(k, j));
+      matrix.set(k, j, entry);
+    }
+  }
+}
+
+/// The QR decomposition `a = q r` by Householder reflections, with `q`
+/// orthogonal and `r` upper triangular.
+pub fn qr(a: &Matrix) -> (Matrix, Matrix) {
+  let (m, n) = (a.rows(), a.columns());
+  let mut q = Matrix::identity(m);
+  let mut r = a.clone();
+  for k in 0..n.min(m.saturating_sub(1)) {
+    let mut v: Vec<f64> = (k..m).map(|i| r.get(i, k)).collect();
+    let length = v.iter().fold(0.0, |acc, x| acc + x * x).sqrt();
+    if length == 0.0 {
+      continue;
+    }
+    v[0] += sign(v[0]) * length;
+    let v_length = v.iter().fold(0.0, |acc, x| acc + x * x).sqrt();
+    v.iter_mut().for_each(|x| *x /= v_length);
+    for j in 0..n {
+      let dot: f64 = (0..v.len()).map(|l| v[l] * r.get(k + l, j)).sum();
+      for (l, vl) in v.iter().enumerate() {
+        r.set(k + l, j, r.get(k + l, j) - 2.0 * dot * vl);
+      }
+    }
+    for i in 0..m {
+      let dot: f64 = (0..v.len()).map(|l| q.get(i, k + l) * v[l]).sum();
+      for (l, vl) in v.iter().enumerate() {
+        q.set(i, k + l, q.get(i, k + l) - 2.0 * dot * vl);
+      }
+    }
+    for i in k + 1..m {
+      r.set(i, k, 0.0);
+    }
+  }
+  (q, r)
+}
+
+/// The thin singular value decomposition `a = u diag(s) vᵀ` by one-sided
+/// Jacobi rotations. The singular values `s` are a column vector in
+/// decreasing order.
+pub fn svd(a: &Matrix) -> (Matrix, Matrix, Matrix) {
+  if a.rows() < a.columns() {
+    let (u, s, v) = svd(&a.transpose());
+    return (v, s, u);
+  }
+  let (m, n) = (a.rows(), a.columns());
+  let mut u = a.clone();
+  let mut v = Matrix::identity(n);
+  for _ in 0..MAX_SWEEPS {
+    let mut rotated = false;
+    for p in 0..n {
+      for q in p + 1..n {
+        let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, 0.0);
+        for i in 0..m {
+          alpha += u.get(i, p) * u.get(i, p);
+          beta += u.get(i, q) * u.get(i, q);
+          gamma += u.get(i, p) * u.get(i, q);
+        }
+        if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+          continue;
+        }
+        rotated = true;
+        let zeta = (beta - alpha) / (2.0 * gamma);
+        let t = sign(zeta) / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+        let c = 1.0 / (1.0 + t * t).sqrt();
+        rotate_columns(&mut u, p, q, c, c * t);
+        rotate_columns(&mut v, p, q, c, c * t);
+      }
+    }
+    if !rotated {
+      break;
+    }
+  }
+  let mut order: Vec<(usize, f64)> = (0..n)
+    .map(|j| (j, (0..m).fold(0.0, |acc, i| acc + u.get(i, j) * u.get(i, j)).sqrt()))
+    .collect();
+  order.sort_by(|a, b| b.1.total_cmp(&a.1));
+  let mut sorted_u = Matrix::zeros(m, n);
+  let mut sorted_v = Matrix::zeros(n, n);
+  let mut s = Matrix::zeros(n, 1);
+  for (k, (j, sigma)) in order.into_iter().enumerate() {
+    s.set(k, 0, sigma);
+    for i in 0..m {
+      sorted_u.set(i, k, if sigma > 0.0 { u.get(i, j) / sigma } else { 0.0 });
+    }
+    for i in 0..n {
+      sorted_v.set(i, k, v.get(i, j));
+    }
+  }
+  (sorted_u, s, sorted_v)
+}
+
+/// Applies the Jacobi rotation by `c = cos θ` and `s = sin θ` to columns `p`
+/// and `q` of a matrix.
+fn rotate_columns(matrix: &mut Matrix, p: usize, q: usize, c: f64, s: f64) {
+  for i in 0..matrix.rows() {
+    let (x, y) = (matrix.get(i, p), matrix.get(i, q));
+    matrix.set(i, p, c * x - s * y);
+    matrix.set(i, q, s * x + c * y);
+  }
+}
+
+/// The eigenvalues, in increasing order as a column vector, and the
+/// eigenvectors, as the columns of a matrix, of a symmetric matrix by the
+/// cyclic Jacobi method.
+pub fn symmetric_eigen(a: &Matrix) -> Result<(Matrix, Matrix), String> {
+  let n = a.rows();
+  let symmetric = a.columns() == n
+    && (0..n).all(|i| {
+      (0..i).all(|j| (a.get(i, j) - a.get(j, i)).abs() <= 1e-12 * a.get(i, j).abs().max(1.0))
+    });
+  if !symmetric {
+    return Err(format!(
+      "Cannot find the eigenvalues of {}: eig needs a symmetric matrix.",
+      a.describe()
+    ));
+  }
+  let mut d = a.clone();
+  let mut v = Matrix::identity(n);
+  for _ in 0..MAX_SWEEPS {
+    let off: f64 = (0..n)
+      .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+      .fold(0.0, |acc, (i, j)| acc + d.get(i, j) * d.get(i, j));
+    if off <= f64::EPSILON * f64::EPSILON * d.norm() * d.norm() {
+      break;
+    }
+    for p in 0..n {
+      for q in p + 1..n {
+        if d.get(p, q) == 0.0 {
+          continue;
+        }
+        let theta = (d.get(q, q) - d.get(p, p)) / (2.0 * d.get(p, q));
+        let t = sign(theta) / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (1.0 + t * t).sqrt();
+        let s = c * t;
+        rotate_columns(&mut d, p, q, c, s);
+        for k in 0..n {
+          let (x, y) = (d.get(p, k), d.get(q, k));
+          d.set(p, k, c * x - s * y);
+          d.set(q, k, s * x + c * y);
+        }
+        rotate_columns(&mut v, p, q, c, s);
+      }
+    }
+  }
+  let mut order: Vec<usize> = (0..n).collect();
+  order.sort_by(|&i, &j| d.get(i, i).total_cmp(&d.get(j, j)));
+  let mut values = Matrix::zeros(n, 1);
+  let mut vectors = Matrix::zeros(n, n);
+  for (k, &j) in order.iter().enumerate() {
+    values.set(k, 0, d.get(j, j));
+    for i in 0..n {
+      vectors.set(i, k, v.get(i, j));
+    }
+  }
+  Ok((values, vectors))
+}
+
+/// The 2-norm condition number, the ratio of the largest to the smallest
+/// singular value. It is infinite for a singular matrix.
+pub fn condition_number(a: &Matrix) -> f64 {
+  let (_, s, _) = svd(a);
+  s.get(0, 0) / s.get(s.rows() - 1, 0)
+}
+
+/// The minimum-norm least-squares solution of `a x = b`, by the singular
+/// value decomposition. Also returns the numerical rank of `a`.
+pub fn lstsq(a: &Matrix, b: &Matrix) -> Result<(Matrix, usize), String> {
+  let b = right_hand_side(a, b)?;
+  let (u, s, v) = svd(a);
+  let tolerance = a.rows().max(a.columns()) as f64 * f64::EPSILON * s.get(0, 0);
+  let rank = (0..s.rows()).filter(|&k| s.get(k, 0) > tolerance).count();
+  // x = v diag(1/s) uᵀ b over the singular values above the tolerance
+  let projected = u.transpose().multiply(&b)?;
+  let mut scaled = Matrix::zeros(s.rows(), b.columns());
+  for k in 0..rank {
+    for j in 0..b.columns() {
+      scaled.set(k, j, projected.get(k, j) / s.get(k, 0));
+    }
+  }
+  Ok((v.multiply(&scaled)?, rank))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::linalg::{condition_number, lstsq, qr, solve, svd, symmetric_eigen};
+  use crate::lib::matrix::Matrix;
+
+  /// The norm of the difference of two matrices.
+  fn distance(a: &Matrix, b: &Matrix) -> f64 {
+    a.add(&b.scale(-1.0)).unwrap().norm()
+  }
+
+  #[test]
+  fn solve_by_lu() {
+    let a = Matrix::new(3, 3, vec![2.0, 1.0, -1.0, -3.0, -1.0, 2.0, -2.0, 1.0, 2.0]);
+    let b = Matrix::new(1, 3, vec![8.0, -11.0, -3.0]);
+    let x = solve(&a, &b).unwrap();
+    assert!(distance(&x, &Matrix::new(3, 1, vec![2.0, 3.0, -1.0])) < 1e-12);
+    let singular = Matrix::new(2, 2, vec![1.0, 2.0, 2.0, 4.0]);
+    let error = solve(&singular, &Matrix::new(2, 1, vec![1.0, 1.0])).unwrap_err();
+    assert!(error.contains("condition number"), "{}", error);
+  }
+
+  #[test]
+  fn decompositions_reconstruct() {
+    let a = Matrix::new(3, 2, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    let (q, r) = qr(&a);
+    assert!(distance(&q.multiply(&r).unwrap(), &a) < 1e-12);
+    assert!(distance(&q.transpose().multiply(&q).unwrap(), &Matrix::identity(3)) < 1e-12);
+    assert!(r.get(1, 0) == 0.0 && r.get(2, 0) == 0.0 && r.get(2, 1) == 0.0);
+    let (u, s, v) = svd(&a);
+    let mut sigma = Matrix::zeros(2, 2);
+    sigma.set(0, 0, s.get(0, 0));
+    sigma.set(1, 1, s.get(1, 0));
+    let product = u.multiply(&sigma).unwrap().multiply(&v.transpose()).unwrap();
+    assert!(distance(&product, &a) < 1e-12);
+    assert!(s.get(0, 0) >= s.get(1, 0));
+  }
+
+  #[test]
+  fn symmetric_eigenvalues() {
+    let a = Matrix::new(2, 2, vec![2.0, 1.0, 1.0, 2.0]);
+    let (values, vectors) = symmetric_eigen(&a).unwrap();
+    assert!(distance(&values, &Matrix::new(2, 1, vec![1.0, 3.0])) < 1e-12);
+    let av = a.multiply(&vectors).unwrap();
+    for k in 0..2 {
+      for i in 0..2 {
+        assert!((av.get(i, k) - values.get(k, 0) * vectors.get(i, k)).abs() < 1e-12);
+      }
+    }
+    assert!(symmetric_eigen(&Matrix::new(2, 2, vec![1.0, 2.0, 3.0, 4.0])).is_err());
+  }
+
+  #[test]
+  fn least_squares_and_conditioning() {
+    // Fits a line through (0, 1), (1, 3) and (2, 5) exactly.
+    let a = Matrix::new(3, 2, vec![1.0, 0.0, 1.0, 1.0, 1.0, 2.0]);
+    let b = Matrix::new(3, 1, vec![1.0, 3.0, 5.0]);
+    let (x, rank) = lstsq(&a, &b).unwrap();
+    assert_eq!(rank, 2);
+    assert!(distance(&x, &Matrix::new(2, 1, vec![1.0, 2.0])) < 1e-12);
+    assert!((condition_number(&Matrix::identity(3)) - 1.0).abs() < 1e-12);
+    let nearly_singular = Matrix::new(2, 2, vec![1.0, 1.0, 1.0, 1.0 + 1e-10]);
+    assert!(condition_number(&nearly_singular) > 1e9);
+  }
+}
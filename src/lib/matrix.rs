@@ -0,0 +1,411 @@
+//! Dense matrices of floats, written `[1, 2; 3, 4]`, and vectors, written
+//! `[1, 2, 3]` as a row or `[1; 2; 3]` as a column.
+
+use std::fmt;
+
+use crate::lib::format::{Format, NumberFormat};
+
+/// A matrix stored in row-major order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+  /// The number of rows
+  rows: usize,
+  /// The number of columns
+  columns: usize,
+  /// The entries, row by row
+  entries: Vec<f64>,
+}
+
+impl fmt::Display for Matrix {
+  /// Writes the matrix as it would be typed, e.g. `[1, 2; 3, 4]`, or with
+  /// `{:#}` one row per line with the columns aligned.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.format(f, &NumberFormat::default())
+  }
+}
+
+impl Format for Matrix {
+  fn format(&self, f: &mut fmt::Formatter, numbers: &NumberFormat) -> fmt::Result {
+    if f.alternate() {
+      let cells: Vec<String> = self.entries.iter().map(|&a| numbers.number(a)).collect();
+      let widths: Vec<usize> = (0..self.columns)
+        .map(|j| {
+          (0..self.rows)
+            .map(|i| cells[i * self.columns + j].chars().count())
+            .max()
+            .unwrap_or(0)
+        })
+        .collect();
+      for i in 0..self.rows {
+        if i > 0 {
+          writeln!(f)?;
+        }
+        write!(f, "[")?;
+        for (j, width) in widths.iter().enumerate() {
+          write!(f, " {:>width$}", cells[i * self.columns + j], width = width)?;
+        }
+        write!(f, " ]")?;
+      }
+      return Ok(());
+    }
+    write!(f, "[")?;
+    for i in 0..self.rows {
+      if i > 0 {
+        write!(f, "; ")?;
+      }
+      for j in 0..self.columns {
+        if j > 0 {
+          write!(f, ", ")?;
+        }
+        write!(f, "{}", numbers.number(self.get(i, j)))?;
+      }
+    }
+    write!(f, "]")
+  }
+}
+
+impl Matrix {
+  /// Creates a matrix from its entries in row-major order.
+  pub fn new(rows: usize, columns: usize, entries: Vec<f64>) -> Matrix {
+    assert_eq!(rows * columns, entries.len(), "A matrix needs rows * columns entries.");
+    Matrix {
+      rows,
+      columns,
+      entries,
+    }
+  }
+
+  /// The `rows`×`columns` matrix of zeros.
+  pub fn zeros(rows: usize, columns: usize) -> Matrix {
+    Matrix::new(rows, columns, vec![0.0; rows * columns])
+  }
+
+  /// The `n`×`n` identity matrix.
+  pub fn identity(n: usize) -> Matrix {
+    let mut entries = vec![0.0; n * n];
+    for i in 0..n {
+      entries[i * n + i] = 1.0;
+    }
+    Matrix::new(n, n, entries)
+  }
+
+  /// The number of rows.
+  pub fn rows(&self) -> usize {
+    self.rows
+  }
+
+  /// The number of columns.
+  pub fn columns(&self) -> usize {
+    self.columns
+  }
+
+  /// The entry in row `i` and column `j`, counting from zero.
+  pub fn get(&self, i: usize, j: usize) -> f64 {
+    self.entries[i * self.columns + j]
+  }
+
+  /// The entries in row-major order.
+  pub fn entries(&self) -> &[f64] {
+    &self.entries
+  }
+
+  /// Sets the entry in row `i` and column `j`, counting from zero.
+  pub fn set(&mut self, i: usize, j: usize, value: f64) {
+    self.entries[i * self.columns + j] = value;
+  }
+
+  /// Whether the matrix is a row or a column vector.
+  pub fn is_vector(&self) -> bool {
+    self.rows == 1 || self.columns == 1
+  }
+
+  /// Names the matrix in an error message, e.g. `the 2×2 matrix [1, 2; 3, 4]`.
+  pub fn describe(&self) -> String {
+    if self.is_vector() {
+      format!("the vector {}", self)
+    } else {
+      format!("the {}×{} matrix {}", self.rows, self.columns, self)
+    }
+  }
+
+  /// Requires a square matrix for the operation `operation`.
+  fn require_square(&self, operation: &str) -> Result<(), String> {
+    if self.rows == self.columns {
+      Ok(())
+    } else {
+      Err(format!(
+        "Cannot take the {} of {}: it is not square.",
+        operation,
+        self.describe()
+      ))
+    }
+  }
+
+  /// Adds two matrices of the same shape.
+  pub fn add(&self, other: &Matrix) -> Result<Matrix, String> {
+    if (self.rows, self.columns) != (other.rows, other.columns) {
+      return Err(format!(
+        "Cannot add {} and {}: their shapes differ.",
+        self.describe(),
+        other.describe()
+      ));
+    }
+    let entries = self
+      .entries
+      .iter()
+      .zip(other.entries.iter())
+      .map(|(a, b)| a + b)
+      .collect();
+    Ok(Matrix::new(self.rows, self.columns, entries))
+  }
+
+  /// Multiplies every entry by `factor`.
+  pub fn scale(&self, factor: f64) -> Matrix {
+    let entries = self.entries.iter().map(|a| a * factor).collect();
+    Matrix::new(self.rows, self.columns, entries)
+  }
+
+  /// The matrix product `self * other`.
+  pub fn multiply(&self, other: &Matrix) -> Result<Matrix, String> {
+    if self.columns != other.rows {
+      return Err(format!(
+        "Cannot multiply {} by {}: the first has {} columns but the second has {} rows.",
+        self.describe(),
+        other.describe(),
+        self.columns,
+        other.rows
+      ));
+    }
+    let mut entries = vec![0.0; self.rows * other.columns];
+    for i in 0..self.rows {
+      for j in 0..other.columns {
+        entries[i * other.columns + j] = (0..self.columns)
+          .map(|k| self.get(i, k) * other.get(k, j))
+          .sum();
+      }
+    }
+    Ok(Matrix::new(self.rows, other.columns, entries))
+  }
+
+  /// Raises a square matrix to an integer power; negative powers invert it.
+  pub fn powi(&self, exponent: i64) -> Result<Matrix, String> {
+    self.require_square("power")?;
+    let mut base = if exponent < 0 {
+      self.inverse()?
+    } else {
+      self.clone()
+    };
+    let mut n = exponent.unsigned_abs();
+    let mut result = Matrix::identity(self.rows);
+    while n > 0 {
+      if n % 2 == 1 {
+        result = result.multiply(&base)?;
+      }
+      base = base.multiply(&base)?;
+      n /= 2;
+    }
+    Ok(result)
+  }
+
+  /// The transpose.
+  pub fn transpose(&self) -> Matrix {
+    let mut entries = Vec::with_capacity(self.entries.len());
+    for j in 0..self.columns {
+      for i in 0..self.rows {
+        entries.push(self.get(i, j));
+      }
+    }
+    Matrix::new(self.columns, self.rows, entries)
+  }
+
+  /// The sum of the diagonal of a square matrix.
+  pub fn trace(&self) -> Result<f64, String> {
+    self.require_square("trace")?;
+    Ok((0..self.rows).map(|i| self.get(i, i)).sum())
+  }
+
+  /// The Frobenius norm, which is the Euclidean norm of a vector.
+  pub fn norm(&self) -> f64 {
+    self.entries.iter().fold(0.0, |acc, a| acc + a * a).sqrt()
+  }
+
+  /// The size below which a pivot of this matrix counts as zero, relative to
+  /// its largest entry.
+  fn tolerance(&self) -> f64 {
+    let largest = self.entries.iter().fold(0.0_f64, |acc, a| acc.max(a.abs()));
+    self.rows.max(self.columns) as f64 * f64::EPSILON * largest
+  }
+
+  /// Reduces the matrix to row echelon form by Gaussian elimination with
+  /// partial pivoting, skipping pivots of at most `tolerance`. Returns the
+  /// reduced matrix, the columns of its pivots and whether an odd number of
+  /// rows were swapped.
+  fn echelon(&self, tolerance: f64) -> (Matrix, Vec<usize>, bool) {
+    let mut reduced = self.clone();
+    let mut pivots = Vec::new();
+    let mut odd = false;
+    for j in 0..self.columns {
+      let row = pivots.len();
+      if row == self.rows {
+        break;
+      }
+      let pivot = (row..self.rows)
+        .max_by(|&a, &b| reduced.get(a, j).abs().total_cmp(&reduced.get(b, j).abs()))
+        .unwrap_or(row);
+      if reduced.get(pivot, j).abs() <= tolerance {
+        continue;
+      }
+      if pivot != row {
+        for k in 0..self.columns {
+          reduced.entries.swap(pivot * self.columns + k, row * self.columns + k);
+        }
+        odd = !odd;
+      }
+      for i in row + 1..self.rows {
+        let ratio = reduced.get(i, j) / reduced.get(row, j);
+        for k in j..self.columns {
+          reduced.entries[i * self.columns + k] -= ratio * reduced.get(row, k);
+        }
+      }
+      pivots.push(j);
+    }
+    (reduced, pivots, odd)
+  }
+
+  /// The determinant of a square matrix.
+  pub fn determinant(&self) -> Result<f64, String> {
+    self.require_square("determinant")?;
+    let (reduced, pivots, odd) = self.echelon(self.tolerance());
+    if pivots.len() < self.rows {
+      return Ok(0.0);
+    }
+    let product: f64 = (0..self.rows).map(|i| reduced.get(i, i)).product();
+    Ok(if odd { -product } else { product })
+  }
+
+  /// The rank, the number of linearly independent rows.
+  pub fn rank(&self) -> usize {
+    self.echelon(self.tolerance()).1.len()
+  }
+
+  /// The inverse of a square matrix, by Gauss-Jordan elimination.
+  pub fn inverse(&self) -> Result<Matrix, String> {
+    self.require_square("inverse")?;
+    let n = self.rows;
+    let mut augmented = Matrix::new(n, 2 * n, vec![0.0; 2 * n * n]);
+    for i in 0..n {
+      for j in 0..n {
+        augmented.entries[i * 2 * n + j] = self.get(i, j);
+      }
+      augmented.entries[i * 2 * n + n + i] = 1.0;
+    }
+    // The pivots are measured against the matrix, not the identity beside it.
+    let (mut reduced, pivots, _) = augmented.echelon(self.tolerance());
+    if pivots.iter().take_while(|&&j| j < n).count() < n {
+      return Err(format!("Cannot invert {}: it is singular.", self.describe()));
+    }
+    for i in (0..n).rev() {
+      let pivot = reduced.get(i, i);
+      for k in 0..2 * n {
+        reduced.entries[i * 2 * n + k] /= pivot;
+      }
+      for row in 0..i {
+        let ratio = reduced.get(row, i);
+        for k in 0..2 * n {
+          reduced.entries[row * 2 * n + k] -= ratio * reduced.get(i, k);
+        }
+      }
+    }
+    let mut entries = Vec::with_capacity(n * n);
+    for i in 0..n {
+      for j in n..2 * n {
+        entries.push(reduced.get(i, j));
+      }
+    }
+    Ok(Matrix::new(n, n, entries))
+  }
+
+  /// The dot product of two vectors of the same length.
+  pub fn dot(&self, other: &Matrix) -> Result<f64, String> {
+    if !self.is_vector() || !other.is_vector() || self.entries.len() != other.entries.len() {
+      return Err(format!(
+        "Cannot take the dot product of {} and {}: it needs two vectors of the same length.",
+        self.describe(),
+        other.describe()
+      ));
+    }
+    Ok(self.entries.iter().zip(other.entries.iter()).map(|(a, b)| a * b).sum())
+  }
+
+  /// The cross product of two vectors of length 3, shaped like `self`.
+  pub fn cross(&self, other: &Matrix) -> Result<Matrix, String> {
+    let vectors = self.is_vector() && other.is_vector();
+    if !vectors || self.entries.len() != 3 || other.entries.len() != 3 {
+      return Err(format!(
+        "Cannot take the cross product of {} and {}: it needs two vectors of length 3.",
+        self.describe(),
+        other.describe()
+      ));
+    }
+    let (a, b) = (&self.entries, &other.entries);
+    let entries = vec![
+      a[1] * b[2] - a[2] * b[1],
+      a[2] * b[0] - a[0] * b[2],
+      a[0] * b[1] - a[1] * b[0],
+    ];
+    Ok(Matrix::new(self.rows, self.columns, entries))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::matrix::Matrix;
+
+  #[test]
+  fn products_and_powers() {
+    let a = Matrix::new(2, 2, vec![1.0, 2.0, 3.0, 4.0]);
+    let squared = Matrix::new(2, 2, vec![7.0, 10.0, 15.0, 22.0]);
+    assert_eq!(a.multiply(&a).unwrap(), squared);
+    assert_eq!(a.powi(2).unwrap(), squared);
+    assert_eq!(a.powi(0).unwrap(), Matrix::identity(2));
+    let row = Matrix::new(1, 3, vec![1.0, 2.0, 3.0]);
+    let error = a.multiply(&row).unwrap_err();
+    assert!(error.contains("[1, 2; 3, 4]") && error.contains("[1, 2, 3]"));
+  }
+
+  #[test]
+  fn determinant_inverse_and_rank() {
+    let a = Matrix::new(2, 2, vec![4.0, 7.0, 2.0, 6.0]);
+    assert!((a.determinant().unwrap() - 10.0).abs() < 1e-12);
+    let product = a.multiply(&a.inverse().unwrap()).unwrap();
+    assert!(product.add(&Matrix::identity(2).scale(-1.0)).unwrap().norm() < 1e-12);
+    let singular = Matrix::new(2, 2, vec![1.0, 2.0, 2.0, 4.0]);
+    assert_eq!(singular.determinant().unwrap(), 0.0);
+    assert_eq!(singular.rank(), 1);
+    assert!(singular.inverse().is_err());
+    let swapped = Matrix::new(2, 2, vec![0.0, 1.0, 1.0, 0.0]);
+    assert_eq!(swapped.determinant().unwrap(), -1.0);
+    // Tiny entries do not make a well-conditioned matrix singular.
+    let tiny = Matrix::new(2, 2, vec![1e-20, 0.0, 0.0, 1e-20]);
+    assert_eq!(tiny.inverse().unwrap(), Matrix::new(2, 2, vec![1e20, 0.0, 0.0, 1e20]));
+    let tiny = a.scale(1e-20);
+    let product = tiny.multiply(&tiny.inverse().unwrap()).unwrap();
+    assert!(product.add(&Matrix::identity(2).scale(-1.0)).unwrap().norm() < 1e-12);
+  }
+
+  #[test]
+  fn aligned_display() {
+    let a = Matrix::new(2, 2, vec![1.0, -20.0, 3.5, 4.0]);
+    assert_eq!(format!("{:#}", a), "[   1 -20 ]\n[ 3.5   4 ]");
+  }
+
+  #[test]
+  fn vector_products() {
+    let x = Matrix::new(1, 3, vec![1.0, 0.0, 0.0]);
+    let y = Matrix::new(3, 1, vec![0.0, 1.0, 0.0]);
+    assert_eq!(x.dot(&y).unwrap(), 0.0);
+    assert_eq!(x.cross(&y).unwrap(), Matrix::new(1, 3, vec![0.0, 0.0, 1.0]));
+    assert_eq!(Matrix::new(1, 2, vec![3.0, 4.0]).norm(), 5.0);
+    assert!(x.dot(&Matrix::new(1, 2, vec![1.0, 2.0])).is_err());
+  }
+}
@@ -1,10 +1,29 @@
 //! The library consists of modules for the lexical tokens, lexical analyzer,
 //! parser, and abstract syntax tree used in this calculator.
 
+pub mod angle;
+pub mod arithmetic;
 pub mod ast;
+pub mod constants;
+pub mod decimal;
+pub mod distributions;
+pub mod fitting;
+pub mod format;
+pub mod interval;
 pub mod lexer;
+pub mod linalg;
+pub mod matrix;
+pub mod modular;
+pub mod number_theory;
 pub mod parser;
+pub mod programmer;
+pub mod recognize;
+pub mod special;
+pub mod statistics;
 pub mod token;
+pub mod uncertainty;
+pub mod units;
+pub mod value;
 
 /// Takes a vector of results and splits into two
 /// vectors, the first for successes (`Ok`s) and the second for errors.
@@ -0,0 +1,188 @@
+//! Modular arithmetic in the integers modulo n, where every sum, product and
+//! power is reduced modulo n and division multiplies by a modular inverse.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::number_theory::{inverse, mul_mod, pow_mod};
+
+/// Every whole number up to this magnitude is exactly a `f64`.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+/// A residue modulo n.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Residue {
+  /// The least nonnegative representative
+  value: i128,
+  modulus: i128,
+  /// The integer written, while it is built from literals by sums, products
+  /// and powers, so that it can be an exponent
+  integer: Option<i128>,
+}
+
+impl fmt::Display for Residue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} (mod {})", self.value, self.modulus)
+  }
+}
+
+impl Residue {
+  /// The least nonnegative representative.
+  pub fn value(&self) -> i128 {
+    self.value
+  }
+
+  /// The modulus.
+  pub fn modulus(&self) -> i128 {
+    self.modulus
+  }
+}
+
+/// Evaluation in the integers modulo `modulus`. Exponents are integers
+/// rather than residues, so they must be written as integers: `2^10` is
+/// `2^10 mod n` even if 10 exceeds n.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModularArithmetic {
+  modulus: i128,
+}
+
+impl ModularArithmetic {
+  /// Creates the arithmetic modulo `modulus`, which must be at least 2.
+  pub fn new(modulus: i128) -> Result<ModularArithmetic, String> {
+    if modulus >= 2 {
+      Ok(ModularArithmetic { modulus })
+    } else {
+      Err(format!("The modulus must be at least 2, not {}.", modulus))
+    }
+  }
+
+  /// The modulus.
+  pub fn modulus(&self) -> i128 {
+    self.modulus
+  }
+
+  /// The residue of `value`, which remembers `integer` if there is one.
+  fn residue(&self, value: i128, integer: Option<i128>) -> Residue {
+    Residue {
+      value: value.rem_euclid(self.modulus),
+      modulus: self.modulus,
+      integer,
+    }
+  }
+
+  /// The product of two residues.
+  fn times(&self, a: &Residue, b: &Residue) -> i128 {
+    let m = self.modulus.unsigned_abs();
+    mul_mod(a.value.unsigned_abs(), b.value.unsigned_abs(), m) as i128
+  }
+}
+
+impl Arithmetic for ModularArithmetic {
+  type Value = Residue;
+
+  fn number(&self, value: f64) -> Result<Residue, String> {
+    if value.fract() == 0.0 && value.abs() <= MAX_EXACT {
+      Ok(self.residue(value as i128, Some(value as i128)))
+    } else {
+      Err(format!("Modular arithmetic takes whole numbers of at most 2^53, not {}.", value))
+    }
+  }
+
+  fn constant(&self, name: &str) -> Result<Residue, String> {
+    Err(format!("The constant {} is not an integer.", name))
+  }
+
+  fn add(&self, a: &Residue, b: &Residue) -> Result<Residue, String> {
+    let m = self.modulus.unsigned_abs();
+    let sum = (a.value.unsigned_abs() + b.value.unsigned_abs()) % m;
+    let integer = a.integer.zip(b.integer).and_then(|(a, b)| a.checked_add(b));
+    Ok(self.residue(sum as i128, integer))
+  }
+
+  fn multiply(&self, a: &Residue, b: &Residue) -> Result<Residue, String> {
+    let integer = a.integer.zip(b.integer).and_then(|(a, b)| a.checked_mul(b));
+    Ok(self.residue(self.times(a, b), integer))
+  }
+
+  /// Multiplies by the inverse of `b`, which exists if `b` is coprime to the
+  /// modulus.
+  fn divide(&self, a: &Residue, b: &Residue) -> Result<Residue, String> {
+    let reciprocal = self.residue(inverse(b.value, self.modulus)?, None);
+    Ok(self.residue(self.times(a, &reciprocal), None))
+  }
+
+  /// Negative exponents are powers of the inverse.
+  fn power(&self, base: &Residue, exponent: &Residue) -> Result<Residue, String> {
+    let n = exponent.integer.ok_or_else(|| {
+      format!("The exponent {} must be an integer, not a quotient of residues.", exponent)
+    })?;
+    let base = if n < 0 {
+      self.residue(inverse(base.value, self.modulus)?, None)
+    } else {
+      *base
+    };
+    let m = self.modulus.unsigned_abs();
+    let value = pow_mod(base.value.unsigned_abs(), n.unsigned_abs(), m) as i128;
+    let integer = match (base.integer, u32::try_from(n)) {
+      (Some(b), Ok(n)) => b.checked_pow(n),
+      _ => None,
+    };
+    Ok(self.residue(value, integer))
+  }
+
+  fn function(&self, name: &str, _argument: &Residue) -> Result<Residue, String> {
+    Err(format!("The function {} is not available in modular mode.", name))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::modular::{ModularArithmetic, Residue};
+  use crate::lib::parser::Parser;
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str, modulus: i128) -> Result<Residue, String> {
+    let tokens = Lexer::lex(input)?;
+    let ast = Parser::parse(&tokens)?;
+    ModularArithmetic::new(modulus)?.evaluate(&ast, &mut HashMap::new())
+  }
+
+  fn value(input: &str, modulus: i128) -> i128 {
+    evaluate(input, modulus).unwrap().value()
+  }
+
+  #[test]
+  fn residues() {
+    assert_eq!(evaluate("100 + 5", 101).unwrap().to_string(), "4 (mod 101)");
+    assert_eq!(value("-1", 101), 100);
+    assert_eq!(value("3 - 5", 7), 5);
+    assert_eq!(value("12 * 13", 101), 55);
+    assert!(evaluate("1.5", 7).is_err());
+    assert!(evaluate("pi", 7).is_err());
+    assert!(ModularArithmetic::new(1).is_err());
+  }
+
+  #[test]
+  fn division_uses_inverses() {
+    assert_eq!(value("1 / 3", 7), 5);
+    assert_eq!(value("3 / 3", 7), 1);
+    assert_eq!(value("2^-1", 101), 51);
+    assert!(evaluate("1 / 2", 10).is_err());
+    assert!(evaluate("1 / 7", 7).is_err());
+  }
+
+  #[test]
+  fn exponents_are_integers() {
+    // Fermat's little theorem: the exponent 100 is not reduced mod 101.
+    assert_eq!(value("3^100", 101), 1);
+    assert_eq!(value("2^10", 7), 2);
+    assert_eq!(value("2^(5 * 2)", 7), 2);
+    assert_eq!(value("2^3^2", 1000), 512);
+    assert!(evaluate("2^(1/3)", 7).is_err());
+    // Products of residues mod a prime near 2^64 need 128-bit arithmetic.
+    assert_eq!(value("(2^63)^2 + 1", 18_446_744_073_709_551_557), 13_835_058_055_282_164_539);
+  }
+}
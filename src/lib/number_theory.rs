@@ -0,0 +1,401 @@
+//! Number theory on exact integers: divisibility, counting, primality,
+//! factorization and modular arithmetic.
+//!
+//! Everything is computed in `i128` and `u128`, never in floating point.
+//! Primality is decided by the Miller–Rabin test with the first thirteen
+//! primes as bases, which is exact below 3.3 × 10²⁴, and factorization uses
+//! trial division followed by Pollard's rho method with Brent's cycle
+//! detection.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The names of the number theory functions that give a number.
+pub const NUMBER_THEORY: [&str; 10] = [
+  "binom", "gcd", "isprime", "lcm", "modinv", "nCr", "nPr", "nextprime", "powmod", "totient",
+];
+
+/// The bases of the Miller–Rabin test, which are also tried as divisors.
+const BASES: [u128; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// The least strong pseudoprime to all of `BASES`, below which the
+/// Miller–Rabin test is exact.
+const DETERMINISTIC_BOUND: u128 = 3_317_044_064_679_887_385_961_981;
+
+/// Trial division finds the prime factors below this before Pollard's rho.
+const TRIAL_DIVISION: u128 = 1000;
+
+/// The steps of Pollard's rho between greatest common divisors.
+const BATCH: u64 = 128;
+
+/// The prime factorization of a nonzero integer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Factorization {
+  negative: bool,
+  /// The primes in increasing order with their multiplicities
+  powers: Vec<(u128, u32)>,
+}
+
+impl fmt::Display for Factorization {
+  /// Writes the factorization like `-1 * 2^3 * 3 * 5`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut factors: Vec<String> = Vec::new();
+    if self.negative {
+      factors.push("-1".to_string());
+    }
+    for &(prime, exponent) in self.powers.iter() {
+      if exponent == 1 {
+        factors.push(prime.to_string());
+      } else {
+        factors.push(format!("{}^{}", prime, exponent));
+      }
+    }
+    if factors.is_empty() {
+      write!(f, "1")
+    } else {
+      write!(f, "{}", factors.join(" * "))
+    }
+  }
+}
+
+impl Factorization {
+  /// The prime factors, repeated by their multiplicities.
+  pub fn primes(&self) -> Vec<u128> {
+    self
+      .powers
+      .iter()
+      .flat_map(|&(prime, exponent)| std::iter::repeat_n(prime, exponent as usize))
+      .collect()
+  }
+}
+
+/// How to call each function.
+fn usage(name: &str) -> &'static str {
+  match name {
+    "binom" | "nCr" | "nPr" => "(n, k) with n ≥ 0",
+    "gcd" | "lcm" => "one or more whole numbers",
+    "modinv" => "(a, m) with m ≥ 1",
+    "powmod" => "(a, b, m) with m ≥ 1",
+    "totient" => "a whole number n ≥ 1",
+    _ => "a whole number",
+  }
+}
+
+/// The error for an argument out of range or a result that overflows.
+fn overflow(name: &str) -> String {
+  format!("The result of {} is too large.", name)
+}
+
+/// The greatest common divisor of two magnitudes.
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  a
+}
+
+/// `a + b mod m` for `a, b < m`, without overflow.
+fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+  if a >= m - b {
+    a - (m - b)
+  } else {
+    a + b
+  }
+}
+
+/// `a b mod m` for `a, b < m`, without overflow.
+pub fn mul_mod(mut a: u128, mut b: u128, m: u128) -> u128 {
+  if m <= u128::from(u64::MAX) {
+    return a * b % m;
+  }
+  let mut product = 0;
+  while b > 0 {
+    if b & 1 == 1 {
+      product = add_mod(product, a, m);
+    }
+    a = add_mod(a, a, m);
+    b >>= 1;
+  }
+  product
+}
+
+/// `base^exponent mod m` by repeated squaring.
+pub fn pow_mod(mut base: u128, mut exponent: u128, m: u128) -> u128 {
+  let mut result = 1 % m;
+  base %= m;
+  while exponent > 0 {
+    if exponent & 1 == 1 {
+      result = mul_mod(result, base, m);
+    }
+    base = mul_mod(base, base, m);
+    exponent >>= 1;
+  }
+  result
+}
+
+/// Decides whether `n` is prime. Composites are always recognized, but a
+/// number above `DETERMINISTIC_BOUND` that passes the test is an error.
+fn is_prime(n: u128) -> Result<bool, String> {
+  if n < 2 {
+    return Ok(false);
+  }
+  if let Some(&p) = BASES.iter().find(|&&p| n.is_multiple_of(p)) {
+    return Ok(n == p);
+  }
+  let (mut d, mut s) = (n - 1, 0);
+  while d % 2 == 0 {
+    d /= 2;
+    s += 1;
+  }
+  let witness = |a: u128| {
+    let mut x = pow_mod(a, d, n);
+    if x == 1 || x == n - 1 {
+      return false;
+    }
+    for _ in 1..s {
+      x = mul_mod(x, x, n);
+      if x == n - 1 {
+        return false;
+      }
+    }
+    true
+  };
+  if BASES.iter().any(|&a| witness(a)) {
+    Ok(false)
+  } else if n < DETERMINISTIC_BOUND {
+    Ok(true)
+  } else {
+    Err(format!(
+      "{} is probably prime, but primality is only decided below {}.",
+      n, DETERMINISTIC_BOUND
+    ))
+  }
+}
+
+/// Finds a divisor of the composite `n` by Pollard's rho method with the
+/// polynomial `x^2 + c`, using Brent's cycle detection. Returns `n` if this
+/// polynomial fails.
+fn pollard_brent(n: u128, c: u128) -> u128 {
+  let f = |x: u128| add_mod(mul_mod(x, x, n), c, n);
+  let (mut x, mut y, mut saved) = (2, 2, 2);
+  let (mut length, mut product, mut divisor) = (1, 1, 1);
+  while divisor == 1 {
+    x = y;
+    for _ in 0..length {
+      y = f(y);
+    }
+    let mut k = 0;
+    while k < length && divisor == 1 {
+      saved = y;
+      for _ in 0..BATCH.min(length - k) {
+        y = f(y);
+        product = mul_mod(product, x.abs_diff(y), n);
+      }
+      divisor = gcd(product, n);
+      k += BATCH;
+    }
+    length *= 2;
+  }
+  if divisor == n {
+    // The batch overshot, so retrace it one step at a time.
+    loop {
+      saved = f(saved);
+      divisor = gcd(x.abs_diff(saved), n);
+      if divisor > 1 {
+        break;
+      }
+    }
+  }
+  divisor
+}
+
+/// Appends the prime factors of `n`, which has no factors below
+/// `TRIAL_DIVISION`, to `primes`.
+fn split(n: u128, primes: &mut Vec<u128>) -> Result<(), String> {
+  if n == 1 {
+    return Ok(());
+  }
+  if is_prime(n)? {
+    primes.push(n);
+    return Ok(());
+  }
+  let divisor = (1..).map(|c| pollard_brent(n, c)).find(|&d| d != n).unwrap_or(n);
+  split(divisor, primes)?;
+  split(n / divisor, primes)
+}
+
+/// The prime factorization of `n`.
+pub fn factor(n: i128) -> Result<Factorization, String> {
+  if n == 0 {
+    return Err("0 has no prime factorization.".to_string());
+  }
+  let mut remaining = n.unsigned_abs();
+  let mut primes = Vec::new();
+  for p in 2..TRIAL_DIVISION {
+    while remaining.is_multiple_of(p) {
+      primes.push(p);
+      remaining /= p;
+    }
+  }
+  split(remaining, &mut primes)?;
+  primes.sort_unstable();
+  let mut powers: Vec<(u128, u32)> = Vec::new();
+  for p in primes {
+    match powers.last_mut() {
+      Some((prime, exponent)) if *prime == p => *exponent += 1,
+      _ => powers.push((p, 1)),
+    }
+  }
+  Ok(Factorization {
+    negative: n < 0,
+    powers,
+  })
+}
+
+/// The number of ways to choose `k` of `n` things, in order if `ordered`.
+fn choose(name: &str, n: i128, k: i128, ordered: bool) -> Result<i128, String> {
+  if n < 0 {
+    return Err(format!("{} takes {}, not n = {}.", name, usage(name), n));
+  }
+  if k < 0 || k > n {
+    return Ok(0);
+  }
+  let k = if ordered { k } else { k.min(n - k) };
+  let mut result: i128 = 1;
+  for i in 0..k {
+    result = result.checked_mul(n - i).ok_or_else(|| overflow(name))?;
+    if !ordered {
+      // The product of i + 1 consecutive numbers is divisible by (i + 1)!.
+      result /= i + 1;
+    }
+  }
+  Ok(result)
+}
+
+/// The inverse of `a` modulo `m ≥ 1`, by the extended Euclidean algorithm.
+pub fn inverse(a: i128, m: i128) -> Result<i128, String> {
+  let (mut r0, mut r1) = (m, a.rem_euclid(m));
+  let (mut t0, mut t1) = (0_i128, 1_i128);
+  while r1 != 0 {
+    let q = r0 / r1;
+    (r0, r1) = (r1, r0 - q * r1);
+    (t0, t1) = (t1, t0 - q * t1);
+  }
+  if r0 == 1 {
+    Ok(t0.rem_euclid(m))
+  } else {
+    Err(format!("{} has no inverse mod {}, since both are divisible by {}.", a, m, r0))
+  }
+}
+
+/// Evaluates the number theory function `name` of whole numbers.
+pub fn number_theory(name: &str, arguments: &[i128]) -> Result<i128, String> {
+  let magnitude = |x: u128| i128::try_from(x).map_err(|_| overflow(name));
+  match (name, arguments) {
+    ("gcd", [first, rest @ ..]) => {
+      magnitude(rest.iter().fold(first.unsigned_abs(), |acc, x| gcd(acc, x.unsigned_abs())))
+    }
+    ("lcm", [first, rest @ ..]) => {
+      let mut result = first.unsigned_abs();
+      for x in rest.iter().map(|x| x.unsigned_abs()) {
+        result = match gcd(result, x) {
+          0 => 0,
+          divisor => (result / divisor).checked_mul(x).ok_or_else(|| overflow(name))?,
+        };
+      }
+      magnitude(result)
+    }
+    ("binom" | "nCr", &[n, k]) => choose(name, n, k, false),
+    ("nPr", &[n, k]) => choose(name, n, k, true),
+    ("isprime", &[n]) => Ok(i128::from(n > 0 && is_prime(n.unsigned_abs())?)),
+    ("nextprime", &[n]) => {
+      let mut candidate = n.max(1).checked_add(1).ok_or_else(|| overflow(name))?;
+      while !is_prime(candidate.unsigned_abs())? {
+        candidate = candidate.checked_add(1).ok_or_else(|| overflow(name))?;
+      }
+      Ok(candidate)
+    }
+    ("totient", &[n]) if n >= 1 => {
+      let factorization = factor(n)?;
+      let totient = factorization
+        .powers
+        .iter()
+        .fold(n.unsigned_abs(), |acc, &(p, _)| acc / p * (p - 1));
+      magnitude(totient)
+    }
+    ("powmod", &[a, b, m]) if m >= 1 => {
+      let m = m.unsigned_abs();
+      let base = if b < 0 { inverse(a, m as i128)? } else { a.rem_euclid(m as i128) };
+      magnitude(pow_mod(base.unsigned_abs(), b.unsigned_abs(), m))
+    }
+    ("modinv", &[a, m]) if m >= 1 => inverse(a, m),
+    _ => Err(format!("{} takes {}.", name, usage(name))),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::number_theory::{factor, number_theory};
+
+  fn check(name: &str, arguments: &[i128], expected: i128) {
+    assert_eq!(number_theory(name, arguments), Ok(expected), "{}({:?})", name, arguments);
+  }
+
+  #[test]
+  fn divisibility_and_counting() {
+    check("gcd", &[12, -18], 6);
+    check("gcd", &[12, 18, 8], 2);
+    check("gcd", &[0, 0], 0);
+    check("lcm", &[4, 6, 10], 60);
+    check("lcm", &[0, 5], 0);
+    check("binom", &[5, 2], 10);
+    check("nCr", &[100, 50], 100_891_344_545_564_193_334_812_497_256);
+    check("binom", &[3, 4], 0);
+    check("nPr", &[5, 2], 20);
+    check("totient", &[1], 1);
+    check("totient", &[36], 12);
+    assert!(number_theory("binom", &[200, 100]).is_err());
+    assert!(number_theory("binom", &[-1, 1]).is_err());
+    assert!(number_theory("totient", &[0]).is_err());
+  }
+
+  #[test]
+  fn primes() {
+    check("isprime", &[2], 1);
+    check("isprime", &[1], 0);
+    check("isprime", &[-7], 0);
+    check("isprime", &[561], 0);
+    check("isprime", &[2_147_483_647], 1);
+    check("isprime", &[(1 << 61) - 1], 1);
+    // A composite with a factor above 41 that passes the test for every base
+    assert!(number_theory("isprime", &[3_317_044_064_679_887_385_961_981]).is_err());
+    check("nextprime", &[-5], 2);
+    check("nextprime", &[13], 17);
+    check("nextprime", &[1_000_000_000_000], 1_000_000_000_039);
+    assert!(number_theory("isprime", &[(1 << 89) - 1]).is_err());
+    check("isprime", &[(1 << 89) + 1], 0);
+    assert_eq!(factor(360).unwrap().to_string(), "2^3 * 3^2 * 5");
+    assert_eq!(factor(-1).unwrap().to_string(), "-1");
+    assert_eq!(factor(1).unwrap().to_string(), "1");
+    assert_eq!(factor(600_851_475_143).unwrap().primes(), vec![71, 839, 1471, 6857]);
+    // The product of two 10-digit primes needs Pollard's rho.
+    let semiprime = 1_000_000_007 * 9_999_999_967_i128;
+    assert_eq!(factor(semiprime).unwrap().primes(), vec![1_000_000_007, 9_999_999_967]);
+    assert!(factor(0).is_err());
+  }
+
+  #[test]
+  fn modular_arithmetic() {
+    check("powmod", &[2, 10, 1000], 24);
+    check("powmod", &[3, -1, 7], 5);
+    check("powmod", &[-2, 3, 5], 2);
+    check("powmod", &[5, 0, 1], 0);
+    // Fermat's little theorem for a 64-bit prime, which needs 128-bit products
+    let p = 18_446_744_073_709_551_557;
+    check("powmod", &[123_456_789, p - 1, p], 1);
+    check("modinv", &[17, 3120], 2753);
+    check("modinv", &[-3, 7], 2);
+    assert!(number_theory("modinv", &[6, 9]).is_err());
+    assert!(number_theory("powmod", &[2, 3, 0]).is_err());
+  }
+}
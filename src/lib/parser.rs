@@ -1,67 +1,236 @@
 //! The parser for the calculator
 
-use crate::lib::ast::AstNode;
-use crate::lib::token::Token;
+use crate::lib::ast::{AstHead, AstNode, Bitwise, Comparison};
+use crate::lib::token::{recognize_identifier, Token};
 use crate::lib::{split_results, unlines};
 
+/// The levels of bitwise operators with their tokens, from the most loosely
+/// to the most tightly binding. All bind less tightly than `+` and `-`.
+const BITWISE_LEVELS: [&[(Token, Bitwise)]; 4] = [
+  &[(Token::Bar, Bitwise::Or)],
+  &[(Token::Xor, Bitwise::Xor)],
+  &[(Token::Ampersand, Bitwise::And)],
+  &[(Token::LessLess, Bitwise::ShiftLeft), (Token::GreaterGreater, Bitwise::ShiftRight)],
+];
+
+/// The comparison operators with their tokens, which bind less tightly than
+/// the bitwise operators.
+const COMPARISONS: [(Token, Comparison); 6] = [
+  (Token::Less, Comparison::Less),
+  (Token::LessEqual, Comparison::LessEqual),
+  (Token::Greater, Comparison::Greater),
+  (Token::GreaterEqual, Comparison::GreaterEqual),
+  (Token::EqualEqual, Comparison::Equal),
+  (Token::BangEqual, Comparison::NotEqual),
+];
+
+/// Whether a term directly after another multiplies it, as in `2x`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Multiplication {
+  /// Juxtaposition multiplies: `2x`, `3(a + b)`, `2pi`, `(a)(b)` and
+  /// `2 sin x`. A function written without parentheses takes the product
+  /// after it up to an operator or another function, so `sin 2x` is
+  /// `sin(2x)` and `sin x cos x` is `sin(x) cos(x)`. A number after a term
+  /// still needs a `*`.
+  Implicit,
+  /// Every product needs a `*`, so `2x` is an error.
+  Explicit,
+}
+
 /// The parser state
 pub struct Parser<'a> {
   /// Current index in the slice of tokens
   current_index: usize,
   /// Slice of tokens
   tokens: &'a [Token],
+  /// Whether juxtaposition multiplies
+  multiplication: Multiplication,
+  /// Whether the parser is inside an absolute value `|x|`, where a `|` after
+  /// an operand closes it rather than being bitwise or
+  in_bars: bool,
 }
 
 impl<'a> Parser<'a> {
   /// Advances the parser one token
   fn advance(&mut self) {
-    if self.current_index + 1 < self.tokens.len() {
+    if self.current_index < self.tokens.len() {
       self.current_index += 1;
     }
   }
 
   /// Parses the rule for assignmnent
-  /// assignment ::= identifier '=' expression
-  ///            | expression
+  /// assignment ::= identifier '=' conversion
+  ///            | conversion
   fn assignment(&mut self) -> Result<AstNode, String> {
     let curr_token = self.current_token();
     if let Token::Identifier(id) = curr_token {
       if self.peek(1) == Token::Equals {
         self.advance();
         self.advance();
-        let result = self.expression();
+        let result = self.conversion();
         return result.map(|expr| AstNode::assign(&id, expr));
       }
     }
-    self.expression()
+    self.conversion()
   }
 
   /// Parses atoms
-  /// atom ::= '(' expression ')'
-  ///      | Function atom
+  /// atom ::= '(' range ')'
+  ///      | '[' row (';' row)* ']'
+  ///      | '{' (range (',' range)*)? '}'
+  ///      | '|' expression '|'
+  ///      | '⌊' expression '⌋'
+  ///      | '⌈' expression '⌉'
+  ///      | 'if' expression 'then' expression 'else' expression
+  ///      | Function power? base? power? '(' expression (',' expression)* ')'
+  ///      | Function power? base? power? postfix (postfix)*
   ///      | Number
+  ///      | Boolean
+  ///      | Integer
+  ///      | Angle
   ///      | Identifier
   ///      | Constant
+  /// power ::= '^' '-'? Number | Superscript
+  /// base ::= Subscript
   fn atom(&mut self) -> Result<AstNode, String> {
+    // Within brackets, `|` is bitwise or again.
+    if self.in_bars && self.current_token() != Token::Bar {
+      self.in_bars = false;
+      let result = self.atom();
+      self.in_bars = true;
+      return result;
+    }
     match self.current_token() {
-      Token::LParen => {
+      Token::Bar => {
         self.advance();
+        let in_bars = std::mem::replace(&mut self.in_bars, true);
         let result = self.expression();
+        self.in_bars = in_bars;
+        self.close(Token::Bar, "an absolute value")?;
+        result.map(|x| AstNode::function("abs", x))
+      }
+      Token::LFloor | Token::LCeil => {
+        let (name, closing) = match self.current_token() {
+          Token::LFloor => ("floor", Token::RFloor),
+          _ => ("ceil", Token::RCeil),
+        };
+        self.advance();
+        let result = self.expression();
+        self.close(closing, name)?;
+        result.map(|x| AstNode::function(name, x))
+      }
+      Token::LParen => {
+        self.advance();
+        let result = self.range();
         match self.current_token() {
           Token::RParen => {
             self.advance();
-            return result;
+            result
           }
-          Token::Eoi => {
-            return Err("Unbalanced parentheses.".to_string());
+          Token::Eoi => Err("Unbalanced parentheses.".to_string()),
+          token => Err(format!("Expected to see a ')' instead of {}", token)),
+        }
+      }
+      Token::LBracket => {
+        self.advance();
+        let mut rows: Vec<Vec<AstNode>> = vec![Vec::new()];
+        let mut errors: Vec<String> = Vec::new();
+        loop {
+          match self.expression() {
+            Ok(entry) => rows.last_mut().expect("A matrix has a row.").push(entry),
+            Err(message) => errors.push(message),
           }
-          _ => unreachable!(),
+          match self.current_token() {
+            Token::Comma => self.advance(),
+            Token::Semicolon => {
+              self.advance();
+              rows.push(Vec::new());
+            }
+            Token::RBracket => {
+              self.advance();
+              break;
+            }
+            token => {
+              errors.push(format!(
+                "Expected to see a ',', ';' or ']' in a matrix instead of {}",
+                token
+              ));
+              return Err(unlines(errors).trim().to_string());
+            }
+          }
+        }
+        if !errors.is_empty() {
+          return Err(unlines(errors).trim().to_string());
         }
+        if let Some(row) = rows.iter().position(|row| row.len() != rows[0].len()) {
+          return Err(format!(
+            "Row {} of the matrix has {} entries, but row 1 has {}.",
+            row + 1,
+            rows[row].len(),
+            rows[0].len()
+          ));
+        }
+        Ok(AstNode::matrix(rows))
+      }
+      Token::LBrace => {
+        self.advance();
+        let mut results = Vec::new();
+        if self.current_token() == Token::RBrace {
+          self.advance();
+          return Ok(AstNode::list(Vec::new()));
+        }
+        loop {
+          results.push(self.range());
+          match self.current_token() {
+            Token::Comma => self.advance(),
+            Token::RBrace => {
+              self.advance();
+              break;
+            }
+            token => {
+              results.push(Err(format!(
+                "Expected to see a ',' or '}}' in a list instead of {}",
+                token
+              )));
+              break;
+            }
+          }
+        }
+        let (items, errors) = split_results(results);
+        if !errors.is_empty() {
+          return Err(unlines(errors).trim().to_string());
+        }
+        Ok(AstNode::list(items))
       }
       Token::Number(value) => {
         self.advance();
         Ok(AstNode::number(value))
       }
+      Token::Integer(value) => {
+        self.advance();
+        Ok(AstNode::integer(value))
+      }
+      Token::Decimal(text) => {
+        self.advance();
+        Ok(AstNode::decimal(text))
+      }
+      Token::Boolean(value) => {
+        self.advance();
+        Ok(AstNode::boolean(value))
+      }
+      Token::If => {
+        self.advance();
+        let condition = self.expression()?;
+        self.expect(Token::Then, "the condition of an if")?;
+        let then = self.expression()?;
+        self.expect(Token::Else, "the first branch of an if")?;
+        let otherwise = self.expression()?;
+        Ok(AstNode::conditional(condition, then, otherwise))
+      }
+      Token::Angle(value, unit) => {
+        self.advance();
+        Ok(AstNode::angle(value, unit))
+      }
       Token::Constant(constant) => {
         self.advance();
         Ok(AstNode::constant(&constant))
@@ -72,18 +241,254 @@ impl<'a> Parser<'a> {
       }
       Token::Function(function) => {
         self.advance();
-        let result = self.atom();
-        match result {
-          Ok(ast) => Ok(AstNode::function(&function, ast)),
-          Err(msg) => Err(msg),
+        // `sin^2 x` is `(sin x)^2`, `sin^-1 x` is `asin x` and `log_2 x` is
+        // the logarithm to base 2. The power and base may come in either
+        // order.
+        let mut exponent = self.function_exponent();
+        let base = match self.current_token() {
+          Token::Subscript(base) if function == "log" => {
+            self.advance();
+            Some(base)
+          }
+          Token::Subscript(_) => return Err(format!("Only log takes a base, not {}.", function)),
+          _ => None,
+        };
+        exponent = exponent.or_else(|| self.function_exponent());
+        let function =
+          if exponent == Some(-1.0) { inverse_function(&function)? } else { function };
+        let applied = if self.current_token() == Token::LParen {
+          self.advance();
+          AstNode::call(&function, self.arguments()?)
+        } else {
+          let mut factors = vec![self.postfix()?];
+          if self.multiplication == Multiplication::Implicit {
+            while self.juxtaposes(&self.current_token())
+              && !matches!(self.current_token(), Token::Function(_))
+            {
+              factors.push(self.postfix()?);
+            }
+          }
+          AstNode::function(&function, AstNode::times(factors))
+        };
+        let applied = match (base, applied.tail()) {
+          (Some(base), [x]) => logarithm(base, x.clone()),
+          (Some(_), _) => return Err("A logarithm to a base takes one argument.".to_string()),
+          (None, _) => applied,
+        };
+        match exponent {
+          Some(exponent) if exponent != -1.0 => {
+            Ok(AstNode::power(vec![applied, AstNode::number(exponent)]))
+          }
+          _ => Ok(applied),
+        }
+      }
+      _ => Err(format!(
+        "Expected to see a number here {}",
+        self.current_token()
+      )),
+    }
+  }
+
+  /// Parses the power of a function, as in `sin^2` or `sin²`, if there is
+  /// one. The power must be an integer literal, such as `2` or `-1`.
+  fn function_exponent(&mut self) -> Option<f64> {
+    let (exponent, length) = match (self.current_token(), self.peek(1), self.peek(2)) {
+      (Token::Superscript(exponent), _, _) => (exponent, 1),
+      (Token::Caret, Token::Number(exponent), _) => (exponent, 2),
+      (Token::Caret, Token::Minus, Token::Number(exponent)) => (-exponent, 3),
+      _ => return None,
+    };
+    if exponent.fract() != 0.0 {
+      return None;
+    }
+    for _ in 0..length {
+      self.advance();
+    }
+    Some(exponent)
+  }
+
+  /// Consumes the token `expected` that must follow `what`.
+  fn expect(&mut self, expected: Token, what: &str) -> Result<(), String> {
+    match self.current_token() {
+      token if token == expected => {
+        self.advance();
+        Ok(())
+      }
+      token => Err(format!(
+        "Expected to see '{}' after {} instead of {}",
+        expected.to_string().trim(),
+        what,
+        token
+      )),
+    }
+  }
+
+  /// Consumes the token `closing` that ends the bracketed `what`.
+  fn close(&mut self, closing: Token, what: &str) -> Result<(), String> {
+    match self.current_token() {
+      token if token == closing => {
+        self.advance();
+        Ok(())
+      }
+      Token::Eoi => Err(format!("Expected to see a '{}' closing {}.", closing, what)),
+      token => Err(format!(
+        "Expected to see a '{}' closing {} instead of {}",
+        closing, what, token
+      )),
+    }
+  }
+
+  /// Parses the arguments of a function call after the opening parenthesis.
+  /// arguments ::= argument (',' argument)* ')'
+  /// argument ::= lambda | range
+  fn arguments(&mut self) -> Result<Vec<AstNode>, String> {
+    let mut results = Vec::new();
+    loop {
+      match self.lambda() {
+        Some(lambda) => results.push(lambda),
+        None => results.push(self.range()),
+      }
+      match self.current_token() {
+        Token::Comma => self.advance(),
+        Token::RParen => {
+          self.advance();
+          break;
+        }
+        Token::Eoi => {
+          results.push(Err("Unbalanced parentheses.".to_string()));
+          break;
         }
+        token => {
+          results.push(Err(format!(
+            "Expected to see a ',' or ')' after an argument instead of {}",
+            token
+          )));
+          break;
+        }
+      }
+    }
+    let (arguments, errors) = split_results(results);
+    if !errors.is_empty() {
+      return Err(unlines(errors).trim().to_string());
+    }
+    Ok(arguments)
+  }
+
+  /// Parses a function literal if one starts here, or else returns `None`
+  /// without consuming any tokens.
+  /// lambda ::= Identifier '->' expression
+  ///        | '(' Identifier (',' Identifier)* ')' '->' expression
+  fn lambda(&mut self) -> Option<Result<AstNode, String>> {
+    let mut parameters = Vec::new();
+    let length = match self.current_token() {
+      Token::Identifier(name) => {
+        parameters.push(name);
+        1
       }
-      _ => {
-        return Err(format!(
-          "Expected to see a number here {}",
-          self.current_token()
-        ));
+      Token::LParen => {
+        let mut step = 1;
+        loop {
+          match (self.peek(step), self.peek(step + 1)) {
+            (Token::Identifier(name), Token::Comma) => parameters.push(name),
+            (Token::Identifier(name), Token::RParen) => {
+              parameters.push(name);
+              break step + 2;
+            }
+            _ => return None,
+          }
+          step += 2;
+        }
+      }
+      _ => return None,
+    };
+    if self.peek(length) != Token::Arrow {
+      return None;
+    }
+    for _ in 0..=length {
+      self.advance();
+    }
+    let body = self.expression();
+    // A repeated parameter could not be bound to both of its arguments.
+    let repeated = (1..parameters.len()).find(|&i| parameters[..i].contains(&parameters[i]));
+    if let Some(i) = repeated {
+      return Some(Err(format!("The parameter {} is repeated.", parameters[i])));
+    }
+    Some(body.map(|body| AstNode::lambda(parameters, body)))
+  }
+
+  /// Parses postfix indexing, factorials and percentages. A '%' followed by
+  /// an operand is a remainder instead, which `factor` parses.
+  /// postfix ::= atom ('[' expression ']' | '!' | '%')*
+  fn postfix(&mut self) -> Result<AstNode, String> {
+    let mut result = self.atom()?;
+    loop {
+      match self.current_token() {
+        Token::LBracket => {
+          self.advance();
+          let index = self.expression()?;
+          if self.current_token() != Token::RBracket {
+            return Err(format!(
+              "Expected to see a ']' after an index instead of {}",
+              self.current_token()
+            ));
+          }
+          self.advance();
+          result = AstNode::index(result, index);
+        }
+        Token::Bang => {
+          self.advance();
+          result = AstNode::function("factorial", result);
+        }
+        Token::Percent if !starts_operand(&self.peek(1)) => {
+          self.advance();
+          result = AstNode::percent(result);
+        }
+        _ => return Ok(result),
+      }
+    }
+  }
+
+  /// Parses the rule for ranges
+  /// range ::= expression ('..' expression ('step' expression)?)?
+  fn range(&mut self) -> Result<AstNode, String> {
+    let start = self.expression();
+    if self.current_token() != Token::DotDot {
+      return start;
+    }
+    self.advance();
+    let end = self.expression();
+    let step = if self.current_token() == Token::Step {
+      self.advance();
+      Some(self.expression())
+    } else {
+      None
+    };
+    let (bounds, errors) = split_results(vec![start, end].into_iter().chain(step).collect());
+    if !errors.is_empty() {
+      return Err(unlines(errors).trim().to_string());
+    }
+    let mut bounds = bounds.into_iter();
+    match (bounds.next(), bounds.next()) {
+      (Some(start), Some(end)) => Ok(AstNode::range(start, end, bounds.next())),
+      _ => unreachable!(),
+    }
+  }
+
+  /// Parses the rule for unit conversions
+  /// conversion ::= range (('to' | 'in') expression)?
+  fn conversion(&mut self) -> Result<AstNode, String> {
+    let result = self.range();
+    match self.current_token() {
+      Token::To | Token::In => {
+        self.advance();
+        let target = self.expression();
+        match (result, target) {
+          (Ok(expr), Ok(target)) => Ok(AstNode::convert(expr, target)),
+          (Err(msg1), Err(msg2)) => Err(unlines(vec![msg1, msg2]).trim().to_string()),
+          (Err(msg), _) | (_, Err(msg)) => Err(msg),
+        }
       }
+      _ => result,
     }
   }
 
@@ -96,9 +501,11 @@ impl<'a> Parser<'a> {
     }
   }
 
-  /// Parses the rule for exponentials
-  /// exponential ::= atom ('^' atom)*
-  ///             | '-' exponential
+  /// Parses the rule for exponentials, where a superscript such as `²` is an
+  /// exponent
+  /// exponential ::= postfix ('^' exponential | Superscript)*
+  ///             | ('+' | '-') exponential
+  ///             | '~' exponential
   fn exponential(&mut self) -> Result<AstNode, String> {
     let mut results: Vec<Result<AstNode, String>> = Vec::new();
     match self.current_token() {
@@ -107,13 +514,36 @@ impl<'a> Parser<'a> {
         let minus_1 = AstNode::number(-1.0);
         results.push(self.exponential().map(|node| AstNode::times(vec![minus_1, node])));
       }
-      _ => results.push(self.atom()),
+      Token::Plus => {
+        self.advance();
+        results.push(self.exponential());
+      }
+      Token::Tilde => {
+        self.advance();
+        results.push(self.exponential().map(AstNode::complement));
+      }
+      _ => results.push(self.postfix()),
     }
     loop {
       match self.current_token() {
-        Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Eoi | Token::RParen => {
+        Token::Plus
+        | Token::Minus
+        | Token::PlusMinus
+        | Token::Star
+        | Token::Slash
+        | Token::SlashSlash
+        | Token::Percent
+        | Token::Mod => {
           let (args, errors) = split_results(results);
-          if errors.len() > 0 {
+          if !errors.is_empty() {
+            return Err(unlines(errors).trim().to_string());
+          } else {
+            return Ok(AstNode::power(args));
+          }
+        }
+        token if closes_sum(&token) || self.juxtaposes(&token) => {
+          let (args, errors) = split_results(results);
+          if !errors.is_empty() {
             return Err(unlines(errors).trim().to_string());
           } else {
             return Ok(AstNode::power(args));
@@ -123,6 +553,10 @@ impl<'a> Parser<'a> {
           self.advance();
           results.push(self.exponential());
         }
+        Token::Superscript(exponent) => {
+          self.advance();
+          results.push(Ok(AstNode::number(exponent)));
+        }
         _ => {
           return Err(format!(
             "Expected to see a '^' after base {}",
@@ -133,29 +567,137 @@ impl<'a> Parser<'a> {
     }
   }
 
-  /// Parses the rule for expressions
-  /// expression ::= term (('+' | '-') term)*
+  /// Parses the rule for expressions, where `c ? a : b` is a conditional.
+  /// expression ::= disjunction ('?' expression ':' expression)?
   fn expression(&mut self) -> Result<AstNode, String> {
+    let condition = self.disjunction()?;
+    if self.current_token() != Token::Question {
+      return Ok(condition);
+    }
+    self.advance();
+    let then = self.expression()?;
+    self.expect(Token::Colon, "the first branch of a '?'")?;
+    let otherwise = self.expression()?;
+    Ok(AstNode::conditional(condition, then, otherwise))
+  }
+
+  /// Parses the rule for disjunctions.
+  /// disjunction ::= conjunction ('or' conjunction)*
+  fn disjunction(&mut self) -> Result<AstNode, String> {
+    let mut result = self.conjunction()?;
+    while self.current_token() == Token::Or {
+      self.advance();
+      result = AstNode::or(result, self.conjunction()?);
+    }
+    Ok(result)
+  }
+
+  /// Parses the rule for conjunctions.
+  /// conjunction ::= negation ('and' negation)*
+  fn conjunction(&mut self) -> Result<AstNode, String> {
+    let mut result = self.negation()?;
+    while self.current_token() == Token::And {
+      self.advance();
+      result = AstNode::and(result, self.negation()?);
+    }
+    Ok(result)
+  }
+
+  /// Parses the rule for negations.
+  /// negation ::= 'not' negation | comparison
+  fn negation(&mut self) -> Result<AstNode, String> {
+    if self.current_token() == Token::Not {
+      self.advance();
+      return self.negation().map(AstNode::logical_not);
+    }
+    self.comparison()
+  }
+
+  /// Parses the rule for comparisons. A chain of comparisons holds if each
+  /// holds, so `0 <= x < 10` is `0 <= x and x < 10`.
+  /// comparison ::= bitwise(0) (comparator bitwise(0))*
+  /// bitwise(k) ::= bitwise(k + 1) (operator(k) bitwise(k + 1))*
+  /// bitwise(4) ::= sum
+  fn comparison(&mut self) -> Result<AstNode, String> {
+    let mut left = self.bitwise(0)?;
+    let mut comparisons = Vec::new();
+    while let Some(&(_, operator)) = COMPARISONS.iter().find(|(t, _)| *t == self.current_token()) {
+      self.advance();
+      let right = self.bitwise(0)?;
+      comparisons.push(AstNode::comparison(operator, left, right.clone()));
+      left = right;
+    }
+    Ok(comparisons.into_iter().reduce(AstNode::and).unwrap_or(left))
+  }
+
+  /// Parses the bitwise operators of `level` and the tighter levels.
+  fn bitwise(&mut self, level: usize) -> Result<AstNode, String> {
+    let operand = |parser: &mut Parser<'a>| match BITWISE_LEVELS.get(level + 1) {
+      Some(_) => parser.bitwise(level + 1),
+      None => parser.sum(),
+    };
+    let mut result = operand(self)?;
+    loop {
+      let token = self.current_token();
+      match BITWISE_LEVELS[level].iter().find(|(t, _)| *t == token) {
+        Some(&(_, operator)) if !(self.in_bars && token == Token::Bar) => {
+          self.advance();
+          result = AstNode::bitwise(operator, result, operand(self)?);
+        }
+        _ => return Ok(result),
+      }
+    }
+  }
+
+  /// Parses the rule for sums. Adding a percentage increases the sum to its
+  /// left by that percentage, as on a desk calculator, so `200 + 10%` is
+  /// `220`.
+  /// sum ::= term (('+' | '-' | '±') term)*
+  fn sum(&mut self) -> Result<AstNode, String> {
     let mut results = vec![self.factor()];
     loop {
       match self.current_token() {
         Token::Plus => {
           self.advance();
-          results.push(self.factor())
+          match self.factor() {
+            Ok(term) if *term.head() == AstHead::Percent => {
+              results = add_percentage(results, term, false);
+            }
+            term => results.push(term),
+          }
         }
         Token::Minus => {
           self.advance();
           let minus1 = AstNode::number(-1.0);
           match self.factor() {
+            Ok(term) if *term.head() == AstHead::Percent => {
+              results = add_percentage(results, term, true);
+            }
             Ok(neg) => results.push(Ok(AstNode::times(vec![minus1, neg]))),
             Err(error) => {
               results.push(Err(error));
             }
           }
         }
-        Token::Eoi | Token::RParen => {
+        Token::PlusMinus => {
+          // `±` applies to the whole sum to its left.
+          self.advance();
+          let (args, mut errors) = split_results(results);
+          let error = self.factor();
+          match error {
+            Ok(error) if errors.is_empty() => {
+              results = vec![Ok(AstNode::plus_minus(AstNode::plus(args), error))];
+            }
+            Ok(_) => results = vec![Err(unlines(errors).trim().to_string())],
+            Err(message) => {
+              errors.push(message);
+              results = vec![Err(unlines(errors).trim().to_string())];
+            }
+          }
+        }
+        token if closes_sum(&token) => {
           let (args, errors) = split_results(results);
-          if errors.len() > 0 {
+          if !errors.is_empty() {
             return Err(unlines(errors).trim().to_string());
           } else {
             return Ok(AstNode::plus(args));
@@ -171,15 +713,27 @@ impl<'a> Parser<'a> {
     }
   }
 
-  /// Parses the rule for factors
-  /// factor ::= exponential (('*' | '/') exponential)*
+  /// Parses the rule for factors. An identifier directly after an exponential
+  /// multiplies it, as in `3 m` or `9.81 m/s^2`, and so does any term with
+  /// implicit multiplication. The remainder and floor division apply to the
+  /// whole product to their left.
+  /// factor ::= exponential (('*' | '/' | '%' | 'mod' | '//') exponential
+  ///                         | exponential)*
   fn factor(&mut self) -> Result<AstNode, String> {
     let mut results = vec![self.exponential()];
     loop {
       match self.current_token() {
-        Token::Plus | Token::Minus | Token::Eoi | Token::RParen => {
+        Token::Plus | Token::Minus | Token::PlusMinus => {
+          let (args, errors) = split_results(results);
+          if !errors.is_empty() {
+            return Err(unlines(errors).trim().to_string());
+          } else {
+            return Ok(AstNode::times(args));
+          }
+        }
+        token if closes_sum(&token) => {
           let (args, errors) = split_results(results);
-          if errors.len() > 0 {
+          if !errors.is_empty() {
             return Err(unlines(errors).trim().to_string());
           } else {
             return Ok(AstNode::times(args));
@@ -189,6 +743,27 @@ impl<'a> Parser<'a> {
           self.advance();
           results.push(self.exponential());
         }
+        token if self.juxtaposes(&token) => results.push(self.exponential()),
+        Token::Percent | Token::Mod | Token::SlashSlash => {
+          let floor = self.current_token() == Token::SlashSlash;
+          self.advance();
+          let (args, mut errors) = split_results(results);
+          match self.exponential() {
+            Ok(divisor) if errors.is_empty() => {
+              let dividend = AstNode::times(args);
+              results = vec![Ok(if floor {
+                AstNode::floor_divide(dividend, divisor)
+              } else {
+                AstNode::modulo(dividend, divisor)
+              })];
+            }
+            Ok(_) => results = vec![Err(unlines(errors).trim().to_string())],
+            Err(message) => {
+              errors.push(message);
+              results = vec![Err(unlines(errors).trim().to_string())];
+            }
+          }
+        }
         Token::Slash => {
           self.advance();
           let minus1 = AstNode::number(-1.0);
@@ -212,22 +787,51 @@ impl<'a> Parser<'a> {
   }
 
   /// Initializes parser state on a slice of tokens.
-  fn new(tokens: &'a [Token]) -> Parser {
+  fn new(tokens: &'a [Token], multiplication: Multiplication) -> Parser<'a> {
     Parser {
       current_index: 0,
       tokens,
+      multiplication,
+      in_bars: false,
     }
   }
 
-  /// Parses a slice of tokens into an abstract syntax tree.
+  /// Parses a slice of tokens into an abstract syntax tree, with implicit
+  /// multiplication.
   pub fn parse(tokens: &'a [Token]) -> Result<AstNode, String> {
-    let mut parser = Parser::new(tokens);
-    match parser.assignment() {
-      Ok(ast) => Ok(ast),
-      Err(msg) => Err(msg),
+    Parser::parse_with(tokens, Multiplication::Implicit)
+  }
+
+  /// Parses a slice of tokens into an abstract syntax tree, multiplying
+  /// juxtaposed terms as `multiplication` says.
+  pub fn parse_with(
+    tokens: &'a [Token],
+    multiplication: Multiplication,
+  ) -> Result<AstNode, String> {
+    let mut parser = Parser::new(tokens, multiplication);
+    let ast = parser.assignment()?;
+    // Everything must be used, so `1, 2` or `1 )` is not just `1`.
+    match parser.current_token() {
+      Token::Eoi => Ok(ast),
+      token => Err(format!("Unexpected token {}", token.to_string().trim())),
     }
   }
 
+  /// Tests whether a term starting with `token` multiplies the term before it,
+  /// which only happens with implicit multiplication.
+  fn juxtaposes(&self, token: &Token) -> bool {
+    self.multiplication == Multiplication::Implicit
+      && matches!(
+        token,
+        Token::Identifier(_)
+          | Token::Constant(_)
+          | Token::Function(_)
+          | Token::LParen
+          | Token::LFloor
+          | Token::LCeil
+      )
+  }
+
   /// Peeks at the `step`th token ahead. Used in the assignment rule.
   fn peek(&self, step: usize) -> Token {
     if self.current_index + step < self.tokens.len() {
@@ -238,10 +842,120 @@ impl<'a> Parser<'a> {
   }
 }
 
+/// Tests whether a token ends an expression, e.g. the ')' closing a
+/// parenthesized expression, the ',' separating the entries of a matrix, the
+/// '..' of a range or the 'to' before a unit conversion.
+fn closes_expression(token: &Token) -> bool {
+  matches!(
+    token,
+    Token::Eoi
+      | Token::RParen
+      | Token::RBracket
+      | Token::RBrace
+      | Token::Comma
+      | Token::Semicolon
+      | Token::DotDot
+      | Token::RFloor
+      | Token::RCeil
+      | Token::Step
+      | Token::To
+      | Token::In
+      | Token::Then
+      | Token::Else
+      | Token::Colon
+  )
+}
+
+/// Tests whether a token ends a sum: it ends an expression, or is a bitwise
+/// operator, a comparison or a logical operator, which bind less tightly
+/// than a sum.
+fn closes_sum(token: &Token) -> bool {
+  closes_expression(token)
+    || BITWISE_LEVELS.iter().any(|level| level.iter().any(|(t, _)| t == token))
+    || COMPARISONS.iter().any(|(t, _)| t == token)
+    || matches!(token, Token::And | Token::Or | Token::Question)
+}
+
+/// Tests whether a token can start an operand, which makes a '%' before it a
+/// remainder rather than a percentage. A sign does, so `7 % -2` is a
+/// remainder.
+fn starts_operand(token: &Token) -> bool {
+  matches!(
+    token,
+    Token::Plus
+      | Token::Minus
+      | Token::Number(_)
+      | Token::Integer(_)
+      | Token::Decimal(_)
+      | Token::Boolean(_)
+      | Token::Angle(_, _)
+      | Token::Tilde
+      | Token::If
+      | Token::Identifier(_)
+      | Token::Constant(_)
+      | Token::Function(_)
+      | Token::LParen
+      | Token::LBracket
+      | Token::LBrace
+      | Token::LFloor
+      | Token::LCeil
+  )
+}
+
+/// The inverse of a function, which `f^-1` names: `asin` for `sin` and
+/// `asinh` for `sinh`.
+fn inverse_function(function: &str) -> Result<String, String> {
+  let inverse = format!("a{}", function);
+  match recognize_identifier(&inverse) {
+    Token::Function(_) => Ok(inverse),
+    _ => Err(format!("The function {} has no inverse to write as {}^-1.", function, function)),
+  }
+}
+
+/// The logarithm of `x` to `base`, using `log2` and `log10` for their bases.
+fn logarithm(base: f64, x: AstNode) -> AstNode {
+  if base == 2.0 {
+    AstNode::function("log2", x)
+  } else if base == 10.0 {
+    AstNode::function("log10", x)
+  } else {
+    let log_base = AstNode::function("log", AstNode::number(base));
+    AstNode::times(vec![
+      AstNode::function("log", x),
+      AstNode::power(vec![log_base, AstNode::number(-1.0)]),
+    ])
+  }
+}
+
+/// Increases the sum of `terms` by the percentage `p%`, or decreases it if
+/// `negative`, as `sum * (100 ± p) / 100`. Unlike `sum * (1 + p/100)`, this
+/// is exact for sums like `200 + 10%`.
+fn add_percentage(
+  terms: Vec<Result<AstNode, String>>,
+  percentage: AstNode,
+  negative: bool,
+) -> Vec<Result<AstNode, String>> {
+  let (terms, errors) = split_results(terms);
+  if !errors.is_empty() {
+    return vec![Err(unlines(errors).trim().to_string())];
+  }
+  let mut p = percentage.tail()[0].clone();
+  if negative {
+    p = AstNode::times(vec![AstNode::number(-1.0), p]);
+  }
+  let hundred = AstNode::number(100.0);
+  vec![Ok(AstNode::times(vec![
+    AstNode::plus(terms),
+    AstNode::plus(vec![hundred.clone(), p]),
+    AstNode::power(vec![hundred, AstNode::number(-1.0)]),
+  ]))]
+}
+
 #[cfg(test)]
 mod test {
-  use crate::lib::ast::AstNode;
-  use crate::lib::parser::Parser;
+  use crate::lib::ast::{AstNode, Bitwise};
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::{Multiplication, Parser};
   use crate::lib::token::Token;
   #[test]
   fn parse_number() {
@@ -264,4 +978,245 @@ mod test {
       [AstNode::number(a), AstNode::number(b)].to_vec()
     )));
   }
+
+  #[test]
+  fn parse_uses_every_token() {
+    let parse = |input: &str| Parser::parse(&Lexer::lex(input).unwrap());
+    assert!(parse("1, 2").is_err());
+    assert!(parse("1 else 2").is_err());
+    assert!(parse("1 )").is_err());
+    assert!(parse("(1))").is_err());
+  }
+
+  #[test]
+  fn parse_matrix() {
+    let tokens = [
+      Token::LBracket,
+      Token::Number(1.0),
+      Token::Comma,
+      Token::Number(2.0),
+      Token::Semicolon,
+      Token::Number(3.0),
+      Token::Comma,
+      Token::Number(4.0),
+      Token::RBracket,
+    ];
+    let ast_result = Parser::parse(&tokens[..]);
+    assert!(ast_result.is_ok());
+    assert!(ast_result.unwrap().ast_equality(&AstNode::matrix(vec![
+      vec![AstNode::number(1.0), AstNode::number(2.0)],
+      vec![AstNode::number(3.0), AstNode::number(4.0)],
+    ])));
+    let ragged = [
+      Token::LBracket,
+      Token::Number(1.0),
+      Token::Comma,
+      Token::Number(2.0),
+      Token::Semicolon,
+      Token::Number(3.0),
+      Token::RBracket,
+    ];
+    assert!(Parser::parse(&ragged[..]).is_err());
+  }
+
+  #[test]
+  fn parse_range_and_lambda() {
+    let tokens = [
+      Token::Function("map".to_string()),
+      Token::LParen,
+      Token::Identifier("x".to_string()),
+      Token::Arrow,
+      Token::Identifier("x".to_string()),
+      Token::Comma,
+      Token::Number(0.0),
+      Token::DotDot,
+      Token::Number(1.0),
+      Token::Step,
+      Token::Number(0.5),
+      Token::RParen,
+      Token::LBracket,
+      Token::Number(2.0),
+      Token::RBracket,
+    ];
+    let ast_result = Parser::parse(&tokens[..]);
+    assert!(ast_result.is_ok());
+    let map = AstNode::call(
+      "map",
+      vec![
+        AstNode::lambda(vec!["x".to_string()], AstNode::identifier("x")),
+        AstNode::range(
+          AstNode::number(0.0),
+          AstNode::number(1.0),
+          Some(AstNode::number(0.5)),
+        ),
+      ],
+    );
+    assert!(ast_result
+      .unwrap()
+      .ast_equality(&AstNode::index(map, AstNode::number(2.0))));
+    let lambda =
+      |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).map(|ast| ast.to_string());
+    assert_eq!(lambda("reduce((a, b) -> a b, xs)").unwrap(), "(reduce (-> (a b) (* a b)) xs)");
+    assert_eq!(lambda("reduce((a, a) -> a, xs)"), Err("The parameter a is repeated.".to_string()));
+    assert!(lambda("map(x -> x, xs), 1").is_err());
+    assert!(lambda("map(x -> x, xs))").is_err());
+  }
+
+  #[test]
+  fn parse_unit_juxtaposition() {
+    let tokens = [
+      Token::Number(3.0),
+      Token::Identifier("m".to_string()),
+      Token::Slash,
+      Token::Identifier("s".to_string()),
+    ];
+    let ast_result = Parser::parse(&tokens[..]);
+    assert!(ast_result.is_ok());
+    assert!(ast_result.unwrap().ast_equality(&AstNode::times(vec![
+      AstNode::number(3.0),
+      AstNode::identifier("m"),
+      AstNode::power(vec![AstNode::identifier("s"), AstNode::number(-1.0)]),
+    ])));
+  }
+
+  #[test]
+  fn parse_remainders_and_percentages() {
+    let number = |value: f64| Token::Number(value);
+    // 2 * 3! mod 4 is (2 * (3!)) mod 4.
+    let tokens = [number(2.0), Token::Star, number(3.0), Token::Bang, Token::Mod, number(4.0)];
+    let factorial = AstNode::function("factorial", AstNode::number(3.0));
+    assert!(Parser::parse(&tokens[..]).unwrap().ast_equality(&AstNode::modulo(
+      AstNode::times(vec![AstNode::number(2.0), factorial]),
+      AstNode::number(4.0)
+    )));
+    // 7 % 2 is a remainder, but 200 + 10% increases 200 by 10%.
+    let tokens = [number(7.0), Token::Percent, number(2.0)];
+    assert!(Parser::parse(&tokens[..])
+      .unwrap()
+      .ast_equality(&AstNode::modulo(AstNode::number(7.0), AstNode::number(2.0))));
+    let tokens = [number(200.0), Token::Plus, number(10.0), Token::Percent];
+    let increase = AstNode::plus(vec![AstNode::number(100.0), AstNode::number(10.0)]);
+    let hundredth = AstNode::power(vec![AstNode::number(100.0), AstNode::number(-1.0)]);
+    assert!(Parser::parse(&tokens[..])
+      .unwrap()
+      .ast_equality(&AstNode::times(vec![AstNode::number(200.0), increase, hundredth])));
+    let tokens = [number(7.0), Token::SlashSlash, Token::Minus, number(2.0)];
+    assert!(Parser::parse(&tokens[..]).is_ok());
+    // A '%' before a sign is a remainder too.
+    let tokens = [number(7.0), Token::Percent, Token::Minus, number(2.0)];
+    assert_eq!(Parser::parse(&tokens[..]).unwrap().to_string(), "(mod 7 (* -1 2))");
+    let tokens = [number(7.0), Token::Percent, Token::Plus, number(2.0)];
+    assert_eq!(Parser::parse(&tokens[..]).unwrap().to_string(), "(mod 7 2)");
+  }
+
+  #[test]
+  fn parse_bitwise_operators() {
+    let number = |value: f64| AstNode::number(value);
+    let lex = |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).unwrap();
+    // 1 | 2 xor 3 & 4 << 5 + 6 is 1 | (2 xor (3 & (4 << (5 + 6)))).
+    let shift = AstNode::bitwise(
+      Bitwise::ShiftLeft,
+      number(4.0),
+      AstNode::plus(vec![number(5.0), number(6.0)]),
+    );
+    let and = AstNode::bitwise(Bitwise::And, number(3.0), shift);
+    let xor = AstNode::bitwise(Bitwise::Xor, number(2.0), and);
+    let or = AstNode::bitwise(Bitwise::Or, number(1.0), xor);
+    assert!(lex("1 | 2 xor 3 & 4 << 5 + 6").ast_equality(&or));
+    // The shifts are left-associative, and ~ binds like a unary minus.
+    let shifts = AstNode::bitwise(
+      Bitwise::ShiftRight,
+      AstNode::bitwise(Bitwise::ShiftLeft, AstNode::integer(0xff), number(1.0)),
+      number(2.0),
+    );
+    assert!(lex("0xff << 1 >> 2").ast_equality(&shifts));
+    let complement = AstNode::times(vec![AstNode::complement(number(1.0)), number(2.0)]);
+    assert!(lex("~1 * 2").ast_equality(&complement));
+    assert!(Parser::parse(&Lexer::lex("1 &").unwrap()).is_err());
+  }
+
+  #[test]
+  fn parse_implicit_multiplication() {
+    let implicit = |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).unwrap().to_string();
+    assert_eq!(implicit("2x"), "(* 2 x)");
+    assert_eq!(implicit("3(a + b)"), "(* 3 (+ a b))");
+    assert_eq!(implicit("2pi"), "(* 2 pi)");
+    assert_eq!(implicit("(a)(b)"), "(* a b)");
+    assert_eq!(implicit("2x^2 y"), "(* 2 (^ x 2) y)");
+    assert_eq!(implicit("1/2x"), "(* 1 (^ 2 -1) x)");
+    // A function without parentheses takes the product after it.
+    assert_eq!(implicit("sin 2x"), "(sin (* 2 x))");
+    assert_eq!(implicit("2 sin x cos x"), "(* 2 (sin x) (cos x))");
+    assert_eq!(implicit("sin(x) y"), "(* (sin x) y)");
+    assert_eq!(implicit("sin x^2"), "(^ (sin x) 2)");
+    assert!(Parser::parse(&Lexer::lex("2 3").unwrap()).is_err());
+    // Scientific notation is a number, not a product with `e`.
+    assert_eq!(implicit("1e-3"), "0.001");
+    assert_eq!(implicit("2E+3x"), "(* 2000 x)");
+    assert_eq!(implicit("2e - 3"), "(+ (* 2 e) (* -1 3))");
+    assert_eq!(implicit("2e x"), "(* 2 e x)");
+    let explicit = |input: &str| {
+      Parser::parse_with(&Lexer::lex(input).unwrap(), Multiplication::Explicit)
+    };
+    assert!(explicit("2x").is_err());
+    assert!(explicit("2pi").is_err());
+    assert!(explicit("3(a + b)").is_err());
+    assert!(explicit("sin x y").is_err());
+    assert_eq!(explicit("2*x").unwrap().to_string(), "(* 2 x)");
+    assert_eq!(explicit("sin x * y").unwrap().to_string(), "(* (sin x) y)");
+  }
+
+  #[test]
+  fn parse_unicode_notation() {
+    let parse =
+      |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).map(|ast| ast.to_string());
+    assert_eq!(parse("|x| + 1").unwrap(), "(+ (abs x) 1)");
+    assert_eq!(parse("||x| − |y||").unwrap(), "(abs (+ (abs x) (* -1 (abs y))))");
+    assert_eq!(parse("|(1 | 2)| | 3").unwrap(), "(| (abs (| 1 2)) 3)");
+    assert_eq!(parse("|-x|").unwrap(), "(abs (* -1 x))");
+    assert_eq!(parse("2⌊x/2⌋ + ⌈y⌉").unwrap(), "(+ (* 2 (floor (* x (^ 2 -1)))) (ceil y))");
+    assert_eq!(parse("√2 × x² ÷ 3").unwrap(), "(* (sqrt 2) (^ x 2) (^ 3 -1))");
+    assert_eq!(parse("x⁻¹").unwrap(), "(^ x -1)");
+    assert_eq!(parse("-x²").unwrap(), "(* -1 (^ x 2))");
+    assert!(parse("|x").is_err());
+    assert!(parse("⌊x⌉").is_err());
+  }
+
+  #[test]
+  fn parse_function_powers() {
+    let parse =
+      |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).map(|ast| ast.to_string());
+    assert_eq!(parse("sin^2 x + cos²x").unwrap(), "(+ (^ (sin x) 2) (^ (cos x) 2))");
+    assert_eq!(parse("sin^-1(0.5)").unwrap(), "(asin 0.5)");
+    assert_eq!(parse("tanh⁻¹ x").unwrap(), "(atanh x)");
+    assert_eq!(parse("log_2 8").unwrap(), "(log2 8)");
+    assert_eq!(parse("log₁₀(1000)").unwrap(), "(log10 1000)");
+    assert_eq!(parse("log_3 x").unwrap(), "(* (log x) (^ (log 3) -1))");
+    assert_eq!(parse("log_2^2 x").unwrap(), "(^ (log2 x) 2)");
+    assert_eq!(parse("log²₂ x").unwrap(), "(^ (log2 x) 2)");
+    assert!(parse("exp^-1 x").is_err());
+    assert!(parse("sin_2 x").is_err());
+    assert!(parse("log_2(x, y)").is_err());
+  }
+
+  #[test]
+  fn parse_conditions() {
+    let parse =
+      |input: &str| Parser::parse(&Lexer::lex(input).unwrap()).map(|ast| ast.to_string());
+    assert_eq!(parse("a + 1 < b * 2").unwrap(), "(< (+ a 1) (* b 2))");
+    assert_eq!(parse("0 <= x < 10").unwrap(), "(and (<= 0 x) (< x 10))");
+    assert_eq!(parse("not a or b and c").unwrap(), "(or (not a) (and b c))");
+    assert_eq!(parse("a & 1 == 0").unwrap(), "(== (& a 1) 0)");
+    assert_eq!(parse("if x > 1 then 1 else x + 1").unwrap(), "(if (> x 1) 1 (+ x 1))");
+    assert_eq!(parse("1 + if c then a else b").unwrap(), "(+ 1 (if c a b))");
+    assert_eq!(parse("x < 0 ? 0 : x > 1 ? 1 : x").unwrap(), "(if (< x 0) 0 (if (> x 1) 1 x))");
+    assert_eq!(parse("y = x == true").unwrap(), "(= y (== x true))");
+    assert!(parse("if x then 1").is_err());
+    assert!(parse("x ? 1").is_err());
+    assert!(parse("x <").is_err());
+    assert!(parse("if a then b else c else d").is_err());
+    assert!(parse("a ? b : c : d").is_err());
+    assert!(parse("a then b").is_err());
+  }
+
 }
@@ -0,0 +1,488 @@
+//! Fixed-width integer arithmetic for programmers, with the bitwise operators
+//! and results shown in decimal, hexadecimal, octal and binary at once.
+//!
+//! Every value has a type from `u8` to `u64` or `i8` to `i64`. Literals take
+//! the type of the mode, and `u8(x)` and the like convert a value to another
+//! type by truncating it to that width, as a cast does in C or Rust. A result
+//! outside the range of its type wraps around, saturates at the bound, or is
+//! an error, as the mode chooses.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::Bitwise;
+
+/// Every whole number up to this magnitude is exactly a `f64`.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+/// A fixed-width integer type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntegerType {
+  signed: bool,
+  bits: u32,
+}
+
+impl fmt::Display for IntegerType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+  }
+}
+
+impl FromStr for IntegerType {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<IntegerType, String> {
+    match (name.chars().next(), name.get(1..).map(str::parse)) {
+      (Some(sign @ ('i' | 'u')), Some(Ok(bits @ (8 | 16 | 32 | 64)))) => Ok(IntegerType {
+        signed: sign == 'i',
+        bits,
+      }),
+      _ => Err(format!("Expected an integer type from u8 to u64 or i8 to i64, not '{}'.", name)),
+    }
+  }
+}
+
+impl IntegerType {
+  /// The least value of the type.
+  fn min(&self) -> i128 {
+    if self.signed {
+      -(1 << (self.bits - 1))
+    } else {
+      0
+    }
+  }
+
+  /// The greatest value of the type.
+  fn max(&self) -> i128 {
+    if self.signed {
+      (1 << (self.bits - 1)) - 1
+    } else {
+      (1 << self.bits) - 1
+    }
+  }
+
+  /// Truncates `value` to the width of the type, in two's complement.
+  fn wrap(&self, value: i128) -> i128 {
+    let truncated = value & ((1 << self.bits) - 1);
+    if truncated > self.max() {
+      truncated - (1 << self.bits)
+    } else {
+      truncated
+    }
+  }
+}
+
+/// What happens to a result outside the range of its type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+  /// Wrap around modulo 2<sup>bits</sup>, as in two's complement hardware
+  Wrap,
+  /// Clamp to the least or greatest value of the type
+  Saturate,
+  /// Report an error
+  Checked,
+}
+
+impl fmt::Display for Overflow {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Overflow::Wrap => write!(f, "wrap"),
+      Overflow::Saturate => write!(f, "saturate"),
+      Overflow::Checked => write!(f, "checked"),
+    }
+  }
+}
+
+impl FromStr for Overflow {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Overflow, String> {
+    match name {
+      "wrap" => Ok(Overflow::Wrap),
+      "saturate" => Ok(Overflow::Saturate),
+      "checked" => Ok(Overflow::Checked),
+      _ => Err(format!(
+        "Expected the overflow behavior wrap, saturate or checked, not '{}'.",
+        name
+      )),
+    }
+  }
+}
+
+/// An integer of a fixed-width type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Word {
+  value: i128,
+  kind: IntegerType,
+}
+
+impl fmt::Display for Word {
+  /// Writes the value and its bits, as in `200 (u8) = 0xc8 = 0o310 =
+  /// 0b1100_1000`. The hexadecimal and binary digits are padded to the width
+  /// of the type and grouped by fours.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let bits = self.bits();
+    let width = self.kind.bits as usize;
+    write!(
+      f,
+      "{} ({}) = 0x{} = 0o{:o} = 0b{}",
+      self.value,
+      self.kind,
+      group(&format!("{:0width$x}", bits, width = width / 4)),
+      bits,
+      group(&format!("{:0width$b}", bits, width = width))
+    )
+  }
+}
+
+impl Word {
+  /// The value.
+  pub fn value(&self) -> i128 {
+    self.value
+  }
+
+  /// The bits of the value in two's complement.
+  fn bits(&self) -> u128 {
+    (self.value as u128) & ((1 << self.kind.bits) - 1)
+  }
+}
+
+/// Separates groups of four digits by underscores, counting from the right.
+fn group(digits: &str) -> String {
+  let mut grouped = String::new();
+  for (k, digit) in digits.chars().enumerate() {
+    if k > 0 && (digits.len() - k).is_multiple_of(4) {
+      grouped.push('_');
+    }
+    grouped.push(digit);
+  }
+  grouped
+}
+
+/// `base^n` modulo 2<sup>128</sup>, by repeated squaring.
+fn wrapping_power(base: i128, mut n: u128) -> i128 {
+  let (mut power, mut square) = (1_i128, base);
+  while n > 0 {
+    if n & 1 == 1 {
+      power = power.wrapping_mul(square);
+    }
+    square = square.wrapping_mul(square);
+    n >>= 1;
+  }
+  power
+}
+
+/// The exact result of an operation, or if it is too large even for `i128`,
+/// its value modulo 2<sup>128</sup> and whether it is negative.
+type Exact = Result<i128, (i128, bool)>;
+
+/// Evaluation in fixed-width integers. `/` truncates toward zero, as in C,
+/// while `//` and `mod` round down. Shifts discard the bits shifted out
+/// whatever the overflow behavior, but may not shift by the width or more.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgrammerArithmetic {
+  /// The type of literals
+  pub kind: IntegerType,
+  /// What happens to results out of range
+  pub overflow: Overflow,
+}
+
+impl Default for ProgrammerArithmetic {
+  /// 32-bit signed integers that wrap around.
+  fn default() -> ProgrammerArithmetic {
+    ProgrammerArithmetic {
+      kind: IntegerType {
+        signed: true,
+        bits: 32,
+      },
+      overflow: Overflow::Wrap,
+    }
+  }
+}
+
+impl fmt::Display for ProgrammerArithmetic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}, {}", self.kind, self.overflow)
+  }
+}
+
+impl ProgrammerArithmetic {
+  /// The common type of two operands, which must have the same type.
+  fn common(&self, a: &Word, b: &Word) -> Result<IntegerType, String> {
+    if a.kind == b.kind {
+      Ok(a.kind)
+    } else {
+      Err(format!(
+        "Cannot combine the {} {} and the {} {}; convert one, as in {}(x).",
+        a.kind, a.value, b.kind, b.value, a.kind
+      ))
+    }
+  }
+
+  /// Fits the exact result of `operation` into `kind` by the overflow
+  /// behavior.
+  fn settle(
+    &self,
+    kind: IntegerType,
+    result: Exact,
+    operation: impl Fn() -> String,
+  ) -> Result<Word, String> {
+    let value = match (result, self.overflow) {
+      (Ok(value), _) if kind.min() <= value && value <= kind.max() => value,
+      (_, Overflow::Checked) => return Err(format!("{} overflows {}.", operation(), kind)),
+      (Ok(value), Overflow::Wrap) | (Err((value, _)), Overflow::Wrap) => kind.wrap(value),
+      (Ok(value), Overflow::Saturate) => value.clamp(kind.min(), kind.max()),
+      (Err((_, true)), Overflow::Saturate) => kind.min(),
+      (Err((_, false)), Overflow::Saturate) => kind.max(),
+    };
+    Ok(Word { value, kind })
+  }
+
+  /// The quotient `a / b` rounded down.
+  fn floor_quotient(&self, a: &Word, b: &Word) -> Result<i128, String> {
+    if b.value == 0 {
+      return Err("Division by zero.".to_string());
+    }
+    let quotient = a.value / b.value;
+    if a.value % b.value != 0 && (a.value < 0) != (b.value < 0) {
+      Ok(quotient - 1)
+    } else {
+      Ok(quotient)
+    }
+  }
+}
+
+impl Arithmetic for ProgrammerArithmetic {
+  type Value = Word;
+
+  fn number(&self, value: f64) -> Result<Word, String> {
+    if value.fract() != 0.0 || value.abs() > MAX_EXACT {
+      return Err(format!(
+        "Programmer mode takes whole numbers, not {}; write large ones in hexadecimal.",
+        value
+      ));
+    }
+    self.settle(self.kind, Ok(value as i128), || value.to_string())
+  }
+
+  fn integer(&self, value: u128) -> Result<Word, String> {
+    let exact = i128::try_from(value).map_err(|_| (value as i128, false));
+    self.settle(self.kind, exact, || value.to_string())
+  }
+
+  fn constant(&self, name: &str) -> Result<Word, String> {
+    Err(format!("The constant {} is not an integer.", name))
+  }
+
+  fn add(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    self.settle(kind, Ok(a.value + b.value), || format!("{} + {}", a.value, b.value))
+  }
+
+  fn subtract(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    self.settle(kind, Ok(a.value - b.value), || format!("{} - {}", a.value, b.value))
+  }
+
+  fn negate(&self, x: &Word) -> Result<Word, String> {
+    self.settle(x.kind, Ok(-x.value), || format!("-{}", x.value))
+  }
+
+  fn multiply(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    let exact = a.value.checked_mul(b.value).ok_or((
+      a.value.wrapping_mul(b.value),
+      (a.value < 0) != (b.value < 0),
+    ));
+    self.settle(kind, exact, || format!("{} * {}", a.value, b.value))
+  }
+
+  /// The quotient truncated toward zero.
+  fn divide(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    if b.value == 0 {
+      return Err("Division by zero.".to_string());
+    }
+    self.settle(kind, Ok(a.value / b.value), || format!("{} / {}", a.value, b.value))
+  }
+
+  fn floor_divide(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    let quotient = self.floor_quotient(a, b)?;
+    self.settle(kind, Ok(quotient), || format!("{} // {}", a.value, b.value))
+  }
+
+  /// The remainder, which has the sign of `b` and always fits the type.
+  fn modulo(&self, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    let remainder = a.value - b.value * self.floor_quotient(a, b)?;
+    self.settle(kind, Ok(remainder), || format!("{} mod {}", a.value, b.value))
+  }
+
+  /// The exponent must not be negative.
+  fn power(&self, base: &Word, exponent: &Word) -> Result<Word, String> {
+    let kind = self.common(base, exponent)?;
+    let n = u128::try_from(exponent.value)
+      .map_err(|_| format!("The power {}^{} is not an integer.", base.value, exponent.value))?;
+    let exact = match base.value {
+      0 | 1 if n > 0 => Some(base.value),
+      -1 => Some(if n % 2 == 0 { 1 } else { -1 }),
+      _ => u32::try_from(n).ok().and_then(|n| base.value.checked_pow(n)),
+    };
+    let negative = base.value < 0 && n % 2 == 1;
+    let exact = exact.ok_or((wrapping_power(base.value, n), negative));
+    self.settle(kind, exact, || format!("{}^{}", base.value, exponent.value))
+  }
+
+  fn bitwise(&self, operator: Bitwise, a: &Word, b: &Word) -> Result<Word, String> {
+    let kind = self.common(a, b)?;
+    let value = match operator {
+      Bitwise::And => a.value & b.value,
+      Bitwise::Or => a.value | b.value,
+      Bitwise::Xor => a.value ^ b.value,
+      Bitwise::ShiftLeft | Bitwise::ShiftRight => {
+        let shift = u32::try_from(b.value)
+          .ok()
+          .filter(|&shift| shift < kind.bits)
+          .ok_or_else(|| {
+            format!(
+              "Cannot shift the {} {} by {} bits; shifts are from 0 to {}.",
+              kind,
+              a.value,
+              b.value,
+              kind.bits - 1
+            )
+          })?;
+        if operator == Bitwise::ShiftLeft {
+          kind.wrap(a.value << shift)
+        } else {
+          // Signed values shift in copies of the sign bit.
+          a.value >> shift
+        }
+      }
+    };
+    Ok(Word { value, kind })
+  }
+
+  fn complement(&self, x: &Word) -> Result<Word, String> {
+    Ok(Word {
+      value: x.kind.wrap(!x.value),
+      kind: x.kind,
+    })
+  }
+
+  /// Integers of different types compare by their values.
+  fn order(&self, a: &Word, b: &Word) -> Result<Option<Ordering>, String> {
+    Ok(Some(a.value.cmp(&b.value)))
+  }
+
+  /// The integer types convert a value by truncating it, and `abs` is the
+  /// magnitude.
+  fn function(&self, name: &str, argument: &Word) -> Result<Word, String> {
+    match name.parse::<IntegerType>() {
+      Ok(kind) => Ok(Word {
+        value: kind.wrap(argument.value),
+        kind,
+      }),
+      Err(_) if name == "abs" => {
+        self.settle(argument.kind, Ok(argument.value.abs()), || format!("abs({})", argument.value))
+      }
+      Err(_) => Err(format!("The function {} is not available in programmer mode.", name)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use crate::lib::programmer::{Overflow, ProgrammerArithmetic, Word};
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str, mode: &str) -> Result<Word, String> {
+    let mut words = mode.split_whitespace();
+    let arithmetic = ProgrammerArithmetic {
+      kind: words.next().unwrap().parse()?,
+      overflow: words.next().unwrap().parse()?,
+    };
+    let tokens = Lexer::lex(input)?;
+    arithmetic.evaluate(&Parser::parse(&tokens)?, &mut HashMap::new())
+  }
+
+  fn value(input: &str, mode: &str) -> i128 {
+    evaluate(input, mode).unwrap().value()
+  }
+
+  #[test]
+  fn results_show_their_bits() {
+    let result = evaluate("200", "u8 wrap").unwrap().to_string();
+    assert_eq!(result, "200 (u8) = 0xc8 = 0o310 = 0b1100_1000");
+    let result = evaluate("-1", "i16 wrap").unwrap().to_string();
+    assert_eq!(result, "-1 (i16) = 0xffff = 0o177777 = 0b1111_1111_1111_1111");
+    let result = evaluate("0xdead_beef", "u32 checked").unwrap().to_string();
+    assert!(result.starts_with("3735928559 (u32) = 0xdead_beef = 0o33653337357"));
+  }
+
+  #[test]
+  fn overflow_behaviors() {
+    assert_eq!(value("200 + 100", "u8 wrap"), 44);
+    assert_eq!(value("200 + 100", "u8 saturate"), 255);
+    assert!(evaluate("200 + 100", "u8 checked").is_err());
+    assert_eq!(value("5 - 3", "u8 checked"), 2);
+    assert!(evaluate("3 - 5", "u8 checked").is_err());
+    assert_eq!(value("3 - 5", "u8 wrap"), 254);
+    assert_eq!(value("3 - 5", "u8 saturate"), 0);
+    assert_eq!(value("-128", "i8 checked"), -128);
+    assert!(evaluate("-(-128)", "i8 checked").is_err());
+    assert_eq!(value("-128 / -1", "i8 wrap"), -128);
+    assert_eq!(value("0xffff_ffff_ffff_ffff", "u64 checked"), u64::MAX as i128);
+    assert_eq!(value("0xffff_ffff_ffff_ffff", "i64 wrap"), -1);
+    // The product of two 64-bit numbers can exceed even i128.
+    assert_eq!(value("0xffff_ffff_ffff_ffff * 0xffff_ffff_ffff_ffff", "u64 wrap"), 1);
+    let huge = "0xffff_ffff_ffff_ffff * 0xffff_ffff_ffff_ffff";
+    assert_eq!(value(huge, "u64 saturate"), u64::MAX as i128);
+    assert_eq!(value("3^5", "u8 wrap"), 243);
+    assert_eq!(value("3^6", "u8 wrap"), 729 % 256);
+    assert_eq!(value("(-2)^127", "i64 saturate"), i64::MIN as i128);
+    assert_eq!(value("3^1000", "u64 wrap"), 3_u64.wrapping_pow(1000) as i128);
+    assert!(evaluate("2^-1", "i32 wrap").is_err());
+    assert!(evaluate("1.5", "i32 wrap").is_err());
+  }
+
+  #[test]
+  fn bitwise_operators() {
+    assert_eq!(value("0b1100 & 0b1010", "u8 checked"), 0b1000);
+    assert_eq!(value("0b1100 | 0b1010", "u8 checked"), 0b1110);
+    assert_eq!(value("0b1100 xor 0b1010", "u8 checked"), 0b0110);
+    assert_eq!(value("~0", "u16 checked"), 0xffff);
+    assert_eq!(value("~5", "i32 checked"), -6);
+    assert_eq!(value("1 << 7", "u8 checked"), 128);
+    assert_eq!(value("0x81 << 1", "u8 checked"), 2);
+    assert_eq!(value("1 << 7", "i8 checked"), -128);
+    assert_eq!(value("-16 >> 2", "i8 checked"), -4);
+    assert_eq!(value("0xf0 >> 4", "u8 checked"), 0x0f);
+    assert_eq!(value("1 | 2 << 2 + 1", "u8 checked"), 17);
+    assert!(evaluate("1 << 8", "u8 wrap").is_err());
+    assert!(evaluate("1 << -1", "i8 wrap").is_err());
+  }
+
+  #[test]
+  fn conversions_and_division() {
+    assert_eq!(value("u8(0x1234)", "u32 checked"), 0x34);
+    assert_eq!(value("i8(u8(200))", "u32 checked"), -56);
+    assert!(evaluate("u8(1) + 1", "u32 checked").is_err());
+    assert_eq!(value("u8(1) + u8(2)", "u32 checked"), 3);
+    assert_eq!(value("-7 / 2", "i32 checked"), -3);
+    assert_eq!(value("-7 // 2", "i32 checked"), -4);
+    assert_eq!(value("-7 mod 2", "i32 checked"), 1);
+    assert_eq!(value("abs(-5)", "i8 checked"), 5);
+    assert!(evaluate("1 / 0", "i32 checked").is_err());
+    assert!(evaluate("sqrt(4)", "i32 checked").is_err());
+    assert!("u7".parse::<crate::lib::programmer::IntegerType>().is_err());
+    assert!("wraps".parse::<Overflow>().is_err());
+  }
+}
@@ -0,0 +1,178 @@
+//! Recognition of results as closed forms: fractions `p/q`, found from the
+//! continued fraction of a number, and small rational multiples of constants
+//! such as `pi/4` or `3*sqrt(2)/2`.
+
+use std::f64::consts;
+use std::fmt;
+
+/// The relative error within which the REPL recognizes results by default.
+pub const DEFAULT_TOLERANCE: f64 = 1e-12;
+
+/// The largest denominator of a recognized fraction.
+const MAX_DENOMINATOR: f64 = 1000.0;
+
+/// The largest numerator of a recognized fraction.
+const MAX_NUMERATOR: f64 = 1e6;
+
+/// The constants that results are recognized as rational multiples of, with
+/// the expression that names each, simplest first.
+const BASES: [(&str, f64); 8] = [
+  ("", 1.0),
+  ("pi", consts::PI),
+  ("e", consts::E),
+  ("sqrt(2)", consts::SQRT_2),
+  ("sqrt(3)", 1.732_050_807_568_877_2),
+  ("sqrt(5)", 2.236_067_977_499_79),
+  ("log(2)", consts::LN_2),
+  ("pi^2", consts::PI * consts::PI),
+];
+
+/// A closed form `numerator/denominator * constant` that a number was
+/// recognized as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClosedForm {
+  pub numerator: i64,
+  /// The denominator, which is positive and coprime to the numerator
+  pub denominator: i64,
+  /// The expression of the constant, or `None` for a fraction
+  pub constant: Option<&'static str>,
+  /// The value of the closed form in floating point
+  pub value: f64,
+  /// The error of the match relative to the number, at most the tolerance
+  pub error: f64,
+}
+
+impl fmt::Display for ClosedForm {
+  /// Writes the form as an expression, e.g. `1/3`, `-pi/4` or `3*sqrt(2)/2`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match (self.numerator, self.constant) {
+      (numerator, None) => write!(f, "{}", numerator)?,
+      (1, Some(constant)) => write!(f, "{}", constant)?,
+      (-1, Some(constant)) => write!(f, "-{}", constant)?,
+      (numerator, Some(constant)) => write!(f, "{}*{}", numerator, constant)?,
+    }
+    if self.denominator != 1 {
+      write!(f, "/{}", self.denominator)?;
+    }
+    Ok(())
+  }
+}
+
+impl ClosedForm {
+  /// Whether the closed form evaluates to exactly the number in floating
+  /// point.
+  pub fn is_exact(&self) -> bool {
+    self.error == 0.0
+  }
+
+  /// Whether the closed form is a whole number, which says nothing more
+  /// than the number itself.
+  pub fn is_integer(&self) -> bool {
+    self.constant.is_none() && self.denominator == 1
+  }
+}
+
+/// Recognizes `x` as the simplest closed form within the relative error
+/// `tolerance`, preferring small denominators and then the simpler constant.
+/// Zero and numbers that are not finite are not recognized.
+pub fn recognize(x: f64, tolerance: f64) -> Option<ClosedForm> {
+  if x == 0.0 || !x.is_finite() {
+    return None;
+  }
+  BASES
+    .iter()
+    .filter_map(|&(name, constant)| {
+      let name = if name.is_empty() { None } else { Some(name) };
+      rational_multiple(x, name, constant, tolerance)
+    })
+    .min_by_key(|form| form.denominator)
+}
+
+/// Finds the first convergent `p/q` of the continued fraction of
+/// `x / constant` such that `p/q * constant` is within `tolerance` of `x`.
+fn rational_multiple(
+  x: f64,
+  name: Option<&'static str>,
+  constant: f64,
+  tolerance: f64,
+) -> Option<ClosedForm> {
+  let sign = x.signum();
+  let mut remainder = x.abs() / constant;
+  // The last two convergents, h/k, starting from 1/0 and 0/1.
+  let (mut h, mut h_before) = (1.0, 0.0);
+  let (mut k, mut k_before) = (0.0, 1.0);
+  for _ in 0..64 {
+    let a = remainder.floor();
+    let next = (a * h + h_before, a * k + k_before);
+    h_before = h;
+    k_before = k;
+    h = next.0;
+    k = next.1;
+    if k > MAX_DENOMINATOR || h > MAX_NUMERATOR {
+      return None;
+    }
+    if h > 0.0 {
+      let value = sign * h * constant / k;
+      let error = ((value - x) / x).abs();
+      if error <= tolerance {
+        return Some(ClosedForm {
+          numerator: (sign * h) as i64,
+          denominator: k as i64,
+          constant: name,
+          value,
+          error,
+        });
+      }
+    }
+    let fraction = remainder - a;
+    if fraction == 0.0 {
+      return None;
+    }
+    remainder = 1.0 / fraction;
+  }
+  None
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::recognize::{recognize, DEFAULT_TOLERANCE};
+  use std::f64::consts;
+
+  fn form(x: f64) -> Option<String> {
+    recognize(x, DEFAULT_TOLERANCE).map(|form| form.to_string())
+  }
+
+  #[test]
+  fn fractions() {
+    assert_eq!(form(1.0 / 3.0).as_deref(), Some("1/3"));
+    assert_eq!(form(-22.0 / 7.0).as_deref(), Some("-22/7"));
+    assert_eq!(form(0.1 + 0.2).as_deref(), Some("3/10"));
+    assert_eq!(form(5.0).as_deref(), Some("5"));
+    assert!(recognize(1.0 / 3.0, DEFAULT_TOLERANCE).unwrap().is_exact());
+    assert!(!recognize(0.1 + 0.2, DEFAULT_TOLERANCE).unwrap().is_exact());
+    assert!(recognize(5.0, DEFAULT_TOLERANCE).unwrap().is_integer());
+    assert_eq!(form(0.0), None);
+  }
+
+  #[test]
+  fn multiples_of_constants() {
+    assert_eq!(form(consts::FRAC_PI_4).as_deref(), Some("pi/4"));
+    assert_eq!(form(1.0f64.atan() * 3.0).as_deref(), Some("3*pi/4"));
+    assert_eq!(form(-2.0 * consts::E).as_deref(), Some("-2*e"));
+    assert_eq!(form(1.5f64.sqrt()).as_deref(), None);
+    assert_eq!(form(3.0 * 2.0f64.sqrt() / 2.0).as_deref(), Some("3*sqrt(2)/2"));
+    assert_eq!(form(8.0f64.ln()).as_deref(), Some("3*log(2)"));
+    assert_eq!(form(consts::PI.powi(2) / 6.0).as_deref(), Some("pi^2/6"));
+  }
+
+  #[test]
+  fn tolerance() {
+    // 355/113 is within 1e-7 of pi but not within the default tolerance.
+    assert_eq!(form(355.0 / 113.0).as_deref(), Some("355/113"));
+    assert_eq!(recognize(consts::PI + 1e-9, DEFAULT_TOLERANCE), None);
+    let loose = recognize(consts::PI + 1e-9, 1e-6).unwrap();
+    assert_eq!(loose.to_string(), "pi");
+    assert!(loose.error > 0.0 && loose.error <= 1e-6);
+    assert_eq!(form(consts::E.sqrt()), None);
+  }
+}
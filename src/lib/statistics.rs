@@ -0,0 +1,261 @@
+//! Descriptive statistics of lists of numbers.
+//!
+//! Moments are accumulated in one pass with Welford's algorithm, extended to
+//! the third and fourth moments by Terriberry, and sums are compensated with
+//! Neumaier's variant of Kahan summation, so that large offsets do not swamp
+//! small variations.
+
+/// The names of the statistics.
+pub const STATISTICS: [&str; 15] = [
+  "corr", "cov", "kurtosis", "max", "mean", "median", "min", "mode", "quantile", "range",
+  "skewness", "stdev", "stdevp", "var", "varp",
+];
+
+/// The central moments of a sample, accumulated one value at a time.
+#[derive(Default)]
+struct Moments {
+  /// The number of values
+  count: f64,
+  /// The running mean
+  mean: f64,
+  /// The sums of the second, third and fourth powers of the deviations from
+  /// the mean
+  m2: f64,
+  m3: f64,
+  m4: f64,
+}
+
+impl Moments {
+  /// Accumulates the moments of `xs`.
+  fn of(xs: &[f64]) -> Moments {
+    let mut moments = Moments::default();
+    for &x in xs.iter() {
+      moments.push(x);
+    }
+    moments
+  }
+
+  /// Adds a value.
+  fn push(&mut self, x: f64) {
+    let previous = self.count;
+    self.count += 1.0;
+    let n = self.count;
+    let delta = x - self.mean;
+    let delta_n = delta / n;
+    let delta_n2 = delta_n * delta_n;
+    let term = delta * delta_n * previous;
+    self.mean += delta_n;
+    self.m4 += term * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * self.m2
+      - 4.0 * delta_n * self.m3;
+    self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+    self.m2 += term;
+  }
+}
+
+/// Requires at least `least` values for the statistic `name`.
+fn require(name: &str, xs: &[f64], least: usize) -> Result<(), String> {
+  if xs.len() >= least {
+    Ok(())
+  } else {
+    Err(format!(
+      "{} needs at least {} values, not {}.",
+      name,
+      least,
+      xs.len()
+    ))
+  }
+}
+
+/// The sum by Neumaier's compensated summation.
+pub fn sum(xs: &[f64]) -> f64 {
+  let (mut total, mut compensation) = (0.0_f64, 0.0);
+  for &x in xs.iter() {
+    let next = total + x;
+    if total.abs() >= x.abs() {
+      compensation += (total - next) + x;
+    } else {
+      compensation += (x - next) + total;
+    }
+    total = next;
+  }
+  total + compensation
+}
+
+/// The arithmetic mean.
+pub fn mean(xs: &[f64]) -> Result<f64, String> {
+  require("mean", xs, 1)?;
+  Ok(Moments::of(xs).mean)
+}
+
+/// The sample variance, dividing by `n - 1`, or the population variance,
+/// dividing by `n`.
+pub fn variance(xs: &[f64], sample: bool) -> Result<f64, String> {
+  let moments = Moments::of(xs);
+  if sample {
+    require("var", xs, 2)?;
+    Ok(moments.m2 / (moments.count - 1.0))
+  } else {
+    require("varp", xs, 1)?;
+    Ok(moments.m2 / moments.count)
+  }
+}
+
+/// The population skewness `g₁`.
+pub fn skewness(xs: &[f64]) -> Result<f64, String> {
+  require("skewness", xs, 2)?;
+  let moments = Moments::of(xs);
+  Ok(moments.count.sqrt() * moments.m3 / moments.m2.powf(1.5))
+}
+
+/// The population excess kurtosis `g₂`, which is 0 for a normal distribution.
+pub fn kurtosis(xs: &[f64]) -> Result<f64, String> {
+  require("kurtosis", xs, 2)?;
+  let moments = Moments::of(xs);
+  Ok(moments.count * moments.m4 / (moments.m2 * moments.m2) - 3.0)
+}
+
+/// Sorts a copy of the values.
+fn sorted(xs: &[f64]) -> Vec<f64> {
+  let mut sorted = xs.to_vec();
+  sorted.sort_by(f64::total_cmp);
+  sorted
+}
+
+/// The `p`-quantile for `0 ≤ p ≤ 1`, interpolating linearly between the
+/// order statistics as R and NumPy do by default.
+pub fn quantile(xs: &[f64], p: f64) -> Result<f64, String> {
+  require("quantile", xs, 1)?;
+  if !(0.0..=1.0).contains(&p) {
+    return Err(format!("A quantile must be between 0 and 1, not {}.", p));
+  }
+  let sorted = sorted(xs);
+  let h = (sorted.len() - 1) as f64 * p;
+  let below = h.floor() as usize;
+  let above = h.ceil() as usize;
+  Ok(sorted[below] + (h - below as f64) * (sorted[above] - sorted[below]))
+}
+
+/// The most frequent value, or the least of the most frequent values.
+pub fn mode(xs: &[f64]) -> Result<f64, String> {
+  require("mode", xs, 1)?;
+  let sorted = sorted(xs);
+  let (mut best, mut best_count) = (sorted[0], 0);
+  let mut start = 0;
+  for end in 1..=sorted.len() {
+    if end == sorted.len() || sorted[end] != sorted[start] {
+      if end - start > best_count {
+        best = sorted[start];
+        best_count = end - start;
+      }
+      start = end;
+    }
+  }
+  Ok(best)
+}
+
+/// The sample covariance of paired values, or their correlation coefficient.
+fn comoment(name: &str, xs: &[f64], ys: &[f64], correlation: bool) -> Result<f64, String> {
+  if xs.len() != ys.len() {
+    return Err(format!(
+      "{} needs two lists of the same length, not {} and {}.",
+      name,
+      xs.len(),
+      ys.len()
+    ));
+  }
+  require(name, xs, 2)?;
+  let (mut x_moments, mut y_moments) = (Moments::default(), Moments::default());
+  let mut comoment = 0.0;
+  for (&x, &y) in xs.iter().zip(ys.iter()) {
+    let dx = x - x_moments.mean;
+    x_moments.push(x);
+    y_moments.push(y);
+    comoment += dx * (y - y_moments.mean);
+  }
+  if correlation {
+    Ok(comoment / (x_moments.m2 * y_moments.m2).sqrt())
+  } else {
+    Ok(comoment / (x_moments.count - 1.0))
+  }
+}
+
+/// Computes the statistic `name` of `xs`, with `ys` or `p` as the second
+/// argument of `cov`, `corr` and `quantile`.
+pub fn statistic(name: &str, xs: &[f64], second: Option<&[f64]>) -> Result<f64, String> {
+  let extremum = |xs: &[f64], max: bool| -> Result<f64, String> {
+    require(name, xs, 1)?;
+    let sorted = sorted(xs);
+    Ok(if max { sorted[sorted.len() - 1] } else { sorted[0] })
+  };
+  match (name, second) {
+    ("quantile", Some([p])) => quantile(xs, *p),
+    ("cov", Some(ys)) => comoment(name, xs, ys, false),
+    ("corr", Some(ys)) => comoment(name, xs, ys, true),
+    ("quantile", _) => {
+      Err("quantile takes a list and a probability, as in quantile(xs, 0.9).".to_string())
+    }
+    ("cov" | "corr", _) => Err(format!("{} takes two lists.", name)),
+    (_, Some(_)) => Err(format!("{} takes one list.", name)),
+    ("kurtosis", None) => kurtosis(xs),
+    ("max", None) => extremum(xs, true),
+    ("mean", None) => mean(xs),
+    ("median", None) => {
+      require(name, xs, 1)?;
+      quantile(xs, 0.5)
+    }
+    ("min", None) => extremum(xs, false),
+    ("mode", None) => mode(xs),
+    ("range", None) => Ok(extremum(xs, true)? - extremum(xs, false)?),
+    ("skewness", None) => skewness(xs),
+    ("stdev", None) => variance(xs, true).map(f64::sqrt),
+    ("stdevp", None) => variance(xs, false).map(f64::sqrt),
+    ("var", None) => variance(xs, true),
+    ("varp", None) => variance(xs, false),
+    _ => Err(format!("Unknown statistic {}", name)),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::statistics::{kurtosis, mean, mode, quantile, skewness, statistic, sum, variance};
+
+  #[test]
+  fn moments() {
+    let xs = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+    assert_eq!(mean(&xs).unwrap(), 5.0);
+    assert!((variance(&xs, false).unwrap() - 4.0).abs() < 1e-12);
+    assert!((variance(&xs, true).unwrap() - 32.0 / 7.0).abs() < 1e-12);
+    assert!(skewness(&[1.0, 2.0, 3.0]).unwrap().abs() < 1e-12);
+    // Two equally likely values have excess kurtosis -2.
+    assert!((kurtosis(&[0.0, 1.0, 0.0, 1.0]).unwrap() + 2.0).abs() < 1e-12);
+    assert!((skewness(&[1.0, 1.0, 1.0, 5.0]).unwrap() - 1.1547005383792515).abs() < 1e-12);
+    assert!(variance(&[1.0], true).is_err());
+  }
+
+  #[test]
+  fn stable_with_large_offsets() {
+    let xs: Vec<f64> = [4.0, 7.0, 13.0, 16.0].iter().map(|x| x + 1e9).collect();
+    assert_eq!(variance(&xs, true).unwrap(), 30.0);
+    assert_eq!(sum(&[1e100, 1.0, -1e100]), 1.0);
+  }
+
+  #[test]
+  fn order_statistics() {
+    let xs = [3.0, 1.0, 4.0, 1.0, 5.0];
+    assert_eq!(quantile(&xs, 0.5).unwrap(), 3.0);
+    assert_eq!(quantile(&xs, 0.25).unwrap(), 1.0);
+    assert_eq!(quantile(&[1.0, 2.0], 0.5).unwrap(), 1.5);
+    assert_eq!(mode(&xs).unwrap(), 1.0);
+    assert_eq!(statistic("range", &xs, None).unwrap(), 4.0);
+    assert!(quantile(&xs, 1.5).is_err());
+  }
+
+  #[test]
+  fn covariance_and_correlation() {
+    let xs = [1.0, 2.0, 3.0];
+    let ys = [2.0, 4.0, 6.0];
+    assert!((statistic("cov", &xs, Some(&ys)).unwrap() - 2.0).abs() < 1e-12);
+    assert!((statistic("corr", &xs, Some(&ys)).unwrap() - 1.0).abs() < 1e-12);
+    assert!(statistic("corr", &xs, Some(&[1.0])).is_err());
+  }
+}
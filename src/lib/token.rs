@@ -2,18 +2,92 @@
 
 use std::fmt;
 
+use crate::lib::angle::AngleUnit;
+use crate::lib::constants::find as find_constant;
+
 /// An enumeration for the tokens accepted by the calculator.
 #[derive(Clone, PartialEq, Debug)]
 pub enum Token {
   LParen,
   RParen,
+  LBracket,
+  RBracket,
+  LBrace,
+  RBrace,
+  Comma,
+  Semicolon,
   Plus,
   Minus,
   Star,
   Slash,
+  /// `//`, floor division
+  SlashSlash,
+  /// `%`, which is a percentage after a number and otherwise the remainder
+  Percent,
+  /// `mod`, the remainder
+  Mod,
+  /// `!`, the factorial
+  Bang,
+  /// `&`, bitwise and
+  Ampersand,
+  /// `|`, bitwise or
+  Bar,
+  /// `xor`, bitwise exclusive or
+  Xor,
+  /// `~`, the bitwise complement
+  Tilde,
+  /// `<<`, the left shift
+  LessLess,
+  /// `>>`, the right shift
+  GreaterGreater,
   Caret,
+  /// An exponent written in superscript, as in `x²` or `x⁻¹`
+  Superscript(f64),
+  /// The base of a logarithm, as in `log_2` or `log₁₀`
+  Subscript(f64),
+  /// `⌊`, opening a floor
+  LFloor,
+  /// `⌋`, closing a floor
+  RFloor,
+  /// `⌈`, opening a ceiling
+  LCeil,
+  /// `⌉`, closing a ceiling
+  RCeil,
   Equals,
+  /// `<` or `≤` and the other comparisons
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+  /// `==`, equality
+  EqualEqual,
+  /// `!=` or `≠`, inequality
+  BangEqual,
+  And,
+  Or,
+  Not,
+  If,
+  Then,
+  Else,
+  /// `?`, as in `c ? a : b`
+  Question,
+  Colon,
+  PlusMinus,
+  DotDot,
+  Arrow,
+  Step,
+  To,
+  In,
   Number(f64),
+  /// An integer written in hexadecimal, octal or binary, or one too large to
+  /// be exact as a `Number`
+  Integer(u128),
+  /// A number with more significant digits than a `Number` keeps, as written
+  Decimal(String),
+  /// `true` or `false`
+  Boolean(bool),
+  /// An angle in a unit, such as `30°` or `1.2rad`
+  Angle(f64, AngleUnit),
   Identifier(String),
   Constant(String),
   Function(String),
@@ -25,13 +99,59 @@ impl fmt::Display for Token {
     match self.clone() {
       Token::LParen => write!(f, "("),
       Token::RParen => write!(f, ")"),
+      Token::LBracket => write!(f, "["),
+      Token::RBracket => write!(f, "]"),
+      Token::LBrace => write!(f, "{{"),
+      Token::RBrace => write!(f, "}}"),
+      Token::Comma => write!(f, ","),
+      Token::Semicolon => write!(f, ";"),
       Token::Plus => write!(f, "+"),
       Token::Minus => write!(f, "-"),
       Token::Star => write!(f, "*"),
       Token::Slash => write!(f, "/"),
+      Token::SlashSlash => write!(f, "//"),
+      Token::Percent => write!(f, "%"),
+      Token::Mod => write!(f, " mod "),
+      Token::Bang => write!(f, "!"),
+      Token::Ampersand => write!(f, "&"),
+      Token::Bar => write!(f, "|"),
+      Token::Xor => write!(f, " xor "),
+      Token::Tilde => write!(f, "~"),
+      Token::LessLess => write!(f, "<<"),
+      Token::GreaterGreater => write!(f, ">>"),
       Token::Caret => write!(f, "^"),
+      Token::Superscript(exponent) => write!(f, "^{}", exponent),
+      Token::Subscript(base) => write!(f, "_{}", base),
+      Token::LFloor => write!(f, "⌊"),
+      Token::RFloor => write!(f, "⌋"),
+      Token::LCeil => write!(f, "⌈"),
+      Token::RCeil => write!(f, "⌉"),
       Token::Equals => write!(f, "="),
+      Token::Less => write!(f, "<"),
+      Token::LessEqual => write!(f, "<="),
+      Token::Greater => write!(f, ">"),
+      Token::GreaterEqual => write!(f, ">="),
+      Token::EqualEqual => write!(f, "=="),
+      Token::BangEqual => write!(f, "!="),
+      Token::And => write!(f, " and "),
+      Token::Or => write!(f, " or "),
+      Token::Not => write!(f, "not "),
+      Token::If => write!(f, "if "),
+      Token::Then => write!(f, " then "),
+      Token::Else => write!(f, " else "),
+      Token::Question => write!(f, "?"),
+      Token::Colon => write!(f, ":"),
+      Token::PlusMinus => write!(f, "±"),
+      Token::DotDot => write!(f, ".."),
+      Token::Arrow => write!(f, " -> "),
+      Token::Step => write!(f, " step "),
+      Token::To => write!(f, " to "),
+      Token::In => write!(f, " in "),
       Token::Number(num) => write!(f, "{}", num),
+      Token::Integer(num) => write!(f, "{}", num),
+      Token::Decimal(text) => write!(f, "{}", text),
+      Token::Boolean(value) => write!(f, "{}", value),
+      Token::Angle(num, unit) => write!(f, "{}{}", num, unit),
       Token::Eoi => write!(f, "♣"),
       Token::Constant(name) => write!(f, "{}", name),
       Token::Function(name) => write!(f, "{}", name),
@@ -40,8 +160,8 @@ impl fmt::Display for Token {
   }
 }
 
-/// A helper function to distinguish various kinds of identifiers: variables,
-/// constancts, and functions
+/// A helper function to distinguish various kinds of identifiers: keywords,
+/// variables, constancts, and functions
 ///
 /// # Examples
 ///
@@ -53,14 +173,54 @@ impl fmt::Display for Token {
 pub fn recognize_identifier(identifier: &str) -> Token {
   let constants = ["e", "pi", "π"];
   let functions = [
-    "abs", "acos", "acosh", "asin", "asinh", "atan", "atanh", "cos", "cosh", "exp", "log", "sin",
-    "sinh", "sqrt", "tan", "tanh",
+    "abs", "acos", "acosh", "acot", "acoth", "acsc", "acsch", "asec", "asech", "asin", "asinh",
+    "atan", "atanh", "besselj", "bessely", "beta", "betacdf", "betainv", "betapdf", "binom",
+    "binomcdf", "binominv", "binompmf", "cbrt", "ceil", "chi2cdf", "chi2inv", "chi2pdf", "cond",
+    "corr", "cos", "cosh", "cot", "coth", "cov", "cross", "csc", "csch", "det", "digamma", "dot",
+    "eig", "erf", "erfc", "erfinv", "exp", "expcdf", "expinv", "expm1", "exppdf", "factor",
+    "factorial", "fcdf", "filter", "finv", "fit", "floor", "fpdf", "frac", "gamma", "gammacdf",
+    "gammainv", "gammapdf", "gcd", "i16", "i32", "i64", "i8", "inv", "isprime", "kurtosis",
+    "lambertw", "lcm", "len", "lgamma", "linfit", "log", "log10", "log1p", "log2", "lstsq", "map",
+    "max", "mean", "median", "min", "mode", "modinv", "nCr", "nPr", "nextprime", "norm", "normcdf",
+    "norminv", "normpdf", "poisscdf", "poissinv", "poisspmf", "polyfit", "powmod", "prod", "qr",
+    "quantile", "range", "rank", "reduce", "round", "sec", "sech", "sign", "sin", "sinh",
+    "skewness", "solve", "sqrt", "stdev", "stdevp", "sum", "svd", "tan", "tanh", "tcdf", "tinv",
+    "totient", "tpdf", "trace", "transpose", "trunc", "u16", "u32", "u64", "u8", "var", "varp",
+    "zeta",
   ];
-  if let Ok(index) = constants.binary_search(&identifier) {
-    Token::Constant(constants[index].to_owned())
-  } else if let Ok(index) = functions.binary_search(&identifier) {
-    Token::Function(functions[index].to_owned())
+  if identifier == "to" {
+    Token::To
+  } else if identifier == "in" {
+    Token::In
+  } else if identifier == "step" {
+    Token::Step
+  } else if identifier == "mod" {
+    Token::Mod
+  } else if identifier == "xor" {
+    Token::Xor
+  } else if let Some(keyword) = recognize_keyword(identifier) {
+    keyword
+  } else if constants.contains(&identifier) || find_constant(identifier).is_some() {
+    Token::Constant(identifier.to_owned())
+  } else if functions.contains(&identifier) {
+    Token::Function(identifier.to_owned())
   } else {
     Token::Identifier(identifier.to_owned())
   }
 }
+
+/// Recognizes the keywords of conditions: `and`, `or`, `not`, `if`, `then`,
+/// `else`, `true` and `false`.
+fn recognize_keyword(identifier: &str) -> Option<Token> {
+  match identifier {
+    "and" => Some(Token::And),
+    "or" => Some(Token::Or),
+    "not" => Some(Token::Not),
+    "if" => Some(Token::If),
+    "then" => Some(Token::Then),
+    "else" => Some(Token::Else),
+    "true" => Some(Token::Boolean(true)),
+    "false" => Some(Token::Boolean(false)),
+    _ => None,
+  }
+}
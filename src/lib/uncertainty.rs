@@ -0,0 +1,286 @@
+//! Propagation of measurement uncertainties, written `9.81 ± 0.02`, through
+//! expressions by first-order (linearized) error propagation.
+//!
+//! Every `±` creates an independent source of error. A measurement records how
+//! much each source contributes to it, so a variable that appears twice in an
+//! expression is correlated with itself: `x - x` is exactly `0 ± 0`.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::constants::CONSTANTS;
+use crate::lib::special;
+
+/// A value together with its linearized dependence on independent sources of
+/// error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+  /// The nominal value
+  value: f64,
+  /// The contribution of each source of error, i.e. the partial derivative of
+  /// the value with respect to the source times the source's standard deviation
+  contributions: BTreeMap<usize, f64>,
+}
+
+impl fmt::Display for Measurement {
+  /// Shows the standard uncertainty to two significant figures and the value
+  /// to the same decimal place, e.g. `96.24 ± 0.39`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let sigma = self.sigma();
+    if sigma > 0.0 && sigma.is_finite() {
+      let decimals = (1.0 - sigma.log10().floor()).clamp(0.0, 17.0) as usize;
+      write!(f, "{:.*} ± {:.*}", decimals, self.value, decimals, sigma)
+    } else {
+      write!(f, "{} ± {}", self.value, sigma)
+    }
+  }
+}
+
+impl Measurement {
+  /// A value known exactly.
+  pub fn exact(value: f64) -> Measurement {
+    Measurement {
+      value,
+      contributions: BTreeMap::new(),
+    }
+  }
+
+  /// The nominal value.
+  pub fn value(&self) -> f64 {
+    self.value
+  }
+
+  /// The standard uncertainty.
+  pub fn sigma(&self) -> f64 {
+    self
+      .contributions
+      .values()
+      .fold(0.0, |acc, c| acc + c * c)
+      .sqrt()
+  }
+
+  /// Propagates the errors of `arguments` into `value`. Each argument is
+  /// paired with the partial derivative of `value` with respect to it.
+  fn chain(value: f64, arguments: &[(&Measurement, f64)]) -> Measurement {
+    let mut contributions = BTreeMap::new();
+    for (argument, derivative) in arguments.iter() {
+      for (source, contribution) in argument.contributions.iter() {
+        *contributions.entry(*source).or_insert(0.0) += derivative * contribution;
+      }
+    }
+    Measurement {
+      value,
+      contributions,
+    }
+  }
+}
+
+/// Evaluation with uncertainties. Each evaluation of a `±` starts a new
+/// source of error, so this keeps a counter across evaluations.
+#[derive(Default)]
+pub struct UncertaintyArithmetic {
+  /// The identifier of the next source of error
+  next_source: Cell<usize>,
+}
+
+impl Arithmetic for UncertaintyArithmetic {
+  type Value = Measurement;
+
+  fn number(&self, value: f64) -> Result<Measurement, String> {
+    Ok(Measurement::exact(value))
+  }
+
+  /// The uncertainty of a measured constant such as `phys.G` is a source of
+  /// error of its own, shared by every use of the constant.
+  fn constant(&self, name: &str) -> Result<Measurement, String> {
+    match name {
+      "pi" => Ok(Measurement::exact(std::f64::consts::PI)),
+      "e" => Ok(Measurement::exact(std::f64::consts::E)),
+      _ => match CONSTANTS.iter().position(|constant| constant.name == name) {
+        Some(index) => {
+          let mut result = Measurement::exact(CONSTANTS[index].value);
+          if CONSTANTS[index].uncertainty > 0.0 {
+            // Counts down from the top so as not to meet the sources of `±`.
+            result
+              .contributions
+              .insert(usize::MAX - index, CONSTANTS[index].uncertainty);
+          }
+          Ok(result)
+        }
+        None => Err(format!("Unknown constant {}", name)),
+      },
+    }
+  }
+
+  fn add(&self, a: &Measurement, b: &Measurement) -> Result<Measurement, String> {
+    Ok(Measurement::chain(a.value + b.value, &[(a, 1.0), (b, 1.0)]))
+  }
+
+  fn multiply(&self, a: &Measurement, b: &Measurement) -> Result<Measurement, String> {
+    Ok(Measurement::chain(
+      a.value * b.value,
+      &[(a, b.value), (b, a.value)],
+    ))
+  }
+
+  fn divide(&self, a: &Measurement, b: &Measurement) -> Result<Measurement, String> {
+    let quotient = a.value / b.value;
+    Ok(Measurement::chain(
+      quotient,
+      &[(a, 1.0 / b.value), (b, -quotient / b.value)],
+    ))
+  }
+
+  fn power(&self, base: &Measurement, exponent: &Measurement) -> Result<Measurement, String> {
+    let value = base.value.powf(exponent.value);
+    let base_derivative = exponent.value * base.value.powf(exponent.value - 1.0);
+    if exponent.contributions.is_empty() {
+      // Skips the logarithm, which is undefined for negative bases.
+      Ok(Measurement::chain(value, &[(base, base_derivative)]))
+    } else {
+      Ok(Measurement::chain(
+        value,
+        &[(base, base_derivative), (exponent, value * base.value.ln())],
+      ))
+    }
+  }
+
+  fn function(&self, name: &str, argument: &Measurement) -> Result<Measurement, String> {
+    let x = argument.value;
+    let (value, derivative) = match name {
+      "abs" => (x.abs(), x.signum()),
+      "acos" => (x.acos(), -1.0 / (1.0 - x * x).sqrt()),
+      "acosh" => (x.acosh(), 1.0 / (x * x - 1.0).sqrt()),
+      "acot" => (x.recip().atan(), -1.0 / (1.0 + x * x)),
+      "acoth" => (x.recip().atanh(), 1.0 / (1.0 - x * x)),
+      "acsc" => (x.recip().asin(), -1.0 / (x.abs() * (x * x - 1.0).sqrt())),
+      "acsch" => (x.recip().asinh(), -1.0 / (x.abs() * (x * x + 1.0).sqrt())),
+      "asec" => (x.recip().acos(), 1.0 / (x.abs() * (x * x - 1.0).sqrt())),
+      "asech" => (x.recip().acosh(), -1.0 / (x * (1.0 - x * x).sqrt())),
+      "asin" => (x.asin(), 1.0 / (1.0 - x * x).sqrt()),
+      "asinh" => (x.asinh(), 1.0 / (x * x + 1.0).sqrt()),
+      "atan" => (x.atan(), 1.0 / (1.0 + x * x)),
+      "atanh" => (x.atanh(), 1.0 / (1.0 - x * x)),
+      "cbrt" => (x.cbrt(), 1.0 / (3.0 * x.cbrt() * x.cbrt())),
+      // Steps are flat almost everywhere, so they propagate no uncertainty.
+      "ceil" => (x.ceil(), 0.0),
+      "cos" => (x.cos(), -x.sin()),
+      "cosh" => (x.cosh(), x.sinh()),
+      "cot" => (x.tan().recip(), -1.0 / (x.sin() * x.sin())),
+      "coth" => (x.tanh().recip(), -1.0 / (x.sinh() * x.sinh())),
+      "csc" => (x.sin().recip(), -x.cos() / (x.sin() * x.sin())),
+      "csch" => (x.sinh().recip(), -x.cosh() / (x.sinh() * x.sinh())),
+      "exp" => (x.exp(), x.exp()),
+      "expm1" => (x.exp_m1(), x.exp()),
+      "factorial" => {
+        let value = special::gamma(x + 1.0);
+        (value, value * special::digamma(x + 1.0))
+      }
+      "floor" => (x.floor(), 0.0),
+      "frac" => (x.fract(), 1.0),
+      "log" => (x.ln(), 1.0 / x),
+      "log10" => (x.log10(), 1.0 / (x * std::f64::consts::LN_10)),
+      "log1p" => (x.ln_1p(), 1.0 / (1.0 + x)),
+      "log2" => (x.log2(), 1.0 / (x * std::f64::consts::LN_2)),
+      "round" => (x.round(), 0.0),
+      "sec" => (x.cos().recip(), x.sin() / (x.cos() * x.cos())),
+      "sech" => (x.cosh().recip(), -x.sinh() / (x.cosh() * x.cosh())),
+      "sign" => (if x == 0.0 { 0.0 } else { x.signum() }, 0.0),
+      "sin" => (x.sin(), x.cos()),
+      "sinh" => (x.sinh(), x.cosh()),
+      "sqrt" => (x.sqrt(), 0.5 / x.sqrt()),
+      "tan" => (x.tan(), 1.0 + x.tan() * x.tan()),
+      "tanh" => (x.tanh(), 1.0 - x.tanh() * x.tanh()),
+      "trunc" => (x.trunc(), 0.0),
+      _ => return Err(format!("Unknown function {}", name)),
+    };
+    Ok(Measurement::chain(value, &[(argument, derivative)]))
+  }
+
+  /// Adds a new, independent source of error with standard deviation
+  /// `|error|` to `value`.
+  fn plus_minus(&self, value: &Measurement, error: &Measurement) -> Result<Measurement, String> {
+    let source = self.next_source.get();
+    self.next_source.set(source + 1);
+    let mut result = value.clone();
+    result.contributions.insert(source, error.value.abs());
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use crate::lib::uncertainty::{Measurement, UncertaintyArithmetic};
+  use std::collections::HashMap;
+
+  fn evaluate(
+    arithmetic: &UncertaintyArithmetic,
+    memory: &mut HashMap<String, Measurement>,
+    input: &str,
+  ) -> Measurement {
+    let tokens = Lexer::lex(input).unwrap();
+    let ast = Parser::parse(&tokens).unwrap();
+    arithmetic.evaluate(&ast, memory).unwrap()
+  }
+
+  #[test]
+  fn independent_errors_add_in_quadrature() {
+    let arithmetic = UncertaintyArithmetic::default();
+    let mut memory = HashMap::new();
+    let sum = evaluate(&arithmetic, &mut memory, "(1 ± 0.3) + (2 ± 0.4)");
+    assert_eq!(sum.value(), 3.0);
+    assert!((sum.sigma() - 0.5).abs() < 1e-12);
+    let product = evaluate(&arithmetic, &mut memory, "2 * (9.81 ± 0.02)");
+    assert!((product.sigma() - 0.04).abs() < 1e-12);
+  }
+
+  #[test]
+  fn repeated_variables_are_correlated() {
+    let arithmetic = UncertaintyArithmetic::default();
+    let mut memory = HashMap::new();
+    evaluate(&arithmetic, &mut memory, "x = 9.81 ± 0.02");
+    assert_eq!(evaluate(&arithmetic, &mut memory, "x - x").sigma(), 0.0);
+    assert_eq!(evaluate(&arithmetic, &mut memory, "x / x").sigma(), 0.0);
+    let doubled = evaluate(&arithmetic, &mut memory, "x + x");
+    assert!((doubled.sigma() - 0.04).abs() < 1e-12);
+  }
+
+  #[test]
+  fn functions_use_derivatives() {
+    let arithmetic = UncertaintyArithmetic::default();
+    let mut memory = HashMap::new();
+    let sine = evaluate(&arithmetic, &mut memory, "sin(0 ± 0.1)");
+    assert!((sine.sigma() - 0.1).abs() < 1e-12);
+    let square = evaluate(&arithmetic, &mut memory, "(3 ± 0.1)^2");
+    assert!((square.sigma() - 0.6).abs() < 1e-12);
+    let decibels = evaluate(&arithmetic, &mut memory, "log10(100 ± 1)");
+    assert!((decibels.sigma() - 0.01 / std::f64::consts::LN_10).abs() < 1e-12);
+    let secant = evaluate(&arithmetic, &mut memory, "sec(1 ± 0.1)");
+    assert!((secant.sigma() - 0.1 * 1.0_f64.tan() / 1.0_f64.cos()).abs() < 1e-12);
+    assert_eq!(evaluate(&arithmetic, &mut memory, "floor(2.5 ± 0.1)").sigma(), 0.0);
+  }
+
+  #[test]
+  fn constants_are_correlated_with_themselves() {
+    let arithmetic = UncertaintyArithmetic::default();
+    let mut memory = HashMap::new();
+    let g = evaluate(&arithmetic, &mut memory, "phys.G");
+    assert!((g.sigma() - 0.00015e-11).abs() < 1e-20);
+    assert_eq!(evaluate(&arithmetic, &mut memory, "phys.G / phys.G").sigma(), 0.0);
+    assert_eq!(evaluate(&arithmetic, &mut memory, "phys.c").sigma(), 0.0);
+  }
+
+  #[test]
+  fn display_rounds_to_the_uncertainty() {
+    let arithmetic = UncertaintyArithmetic::default();
+    let mut memory = HashMap::new();
+    let square = evaluate(&arithmetic, &mut memory, "(9.81 ± 0.02)^2");
+    assert_eq!(square.to_string(), "96.24 ± 0.39");
+    assert_eq!(Measurement::exact(2.5).to_string(), "2.5 ± 0");
+  }
+}
@@ -0,0 +1,580 @@
+//! Quantities with units of measure and dimensional analysis.
+//!
+//! A quantity is stored in SI base units together with its dimension, the
+//! powers of the seven SI base units. Units are looked up by name whenever an
+//! identifier is not a variable, so `3 m` or `9.81 m/s^2` evaluate to
+//! quantities, and names may carry SI prefixes, as in `km` or `µs`.
+//!
+//! Temperatures in the affine units `°C` and `°F` are absolute temperatures:
+//! `20 °C` is 293.15 K, so `20 °C + 1 K` is 294.15 K, but `20 °C * 2` doubles
+//! 293.15 K. Of two such temperatures added together, the second is a
+//! difference, so `20 °C + 5 °C` is 25 °C, or 298.15 K.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::apply_function;
+use crate::lib::constants;
+use crate::lib::format::{Format, NumberFormat};
+
+/// The symbols of the SI base units, in the order of a dimension's exponents.
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// The powers of the SI base units m, kg, s, A, K, mol and cd.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dimension([i8; 7]);
+
+/// A unit that a quantity may be written in.
+struct Unit {
+  /// The symbol of the unit
+  name: &'static str,
+  /// The size of the unit in SI base units
+  factor: f64,
+  /// The dimension of the unit
+  dimension: Dimension,
+  /// Where zero of an affine unit such as `°C` lies, in SI base units
+  offset: f64,
+  /// Whether the unit accepts SI prefixes
+  prefixable: bool,
+}
+
+/// Abbreviates the entries of `UNITS`.
+const fn unit(name: &'static str, factor: f64, dimension: [i8; 7], prefixable: bool) -> Unit {
+  Unit {
+    name,
+    factor,
+    dimension: Dimension(dimension),
+    offset: 0.0,
+    prefixable,
+  }
+}
+
+/// Abbreviates the entries of `UNITS` for affine units, whose zero differs
+/// from the zero of the SI unit.
+const fn affine(name: &'static str, factor: f64, offset: f64, dimension: [i8; 7]) -> Unit {
+  Unit {
+    name,
+    factor,
+    dimension: Dimension(dimension),
+    offset,
+    prefixable: false,
+  }
+}
+
+/// The units known to the calculator. Derived units come before the base
+/// units so that they are preferred when showing a dimension.
+const UNITS: &[Unit] = &[
+  // Named derived SI units
+  unit("N", 1.0, [1, 1, -2, 0, 0, 0, 0], true),
+  unit("J", 1.0, [2, 1, -2, 0, 0, 0, 0], true),
+  unit("W", 1.0, [2, 1, -3, 0, 0, 0, 0], true),
+  unit("Pa", 1.0, [-1, 1, -2, 0, 0, 0, 0], true),
+  unit("Hz", 1.0, [0, 0, -1, 0, 0, 0, 0], true),
+  unit("C", 1.0, [0, 0, 1, 1, 0, 0, 0], true),
+  unit("V", 1.0, [2, 1, -3, -1, 0, 0, 0], true),
+  unit("Ω", 1.0, [2, 1, -3, -2, 0, 0, 0], true),
+  unit("F", 1.0, [-2, -1, 4, 2, 0, 0, 0], true),
+  unit("T", 1.0, [0, 1, -2, -1, 0, 0, 0], true),
+  unit("Wb", 1.0, [2, 1, -2, -1, 0, 0, 0], true),
+  unit("H", 1.0, [2, 1, -2, -2, 0, 0, 0], true),
+  unit("S", 1.0, [-2, -1, 3, 2, 0, 0, 0], true),
+  // SI base units
+  unit("m", 1.0, [1, 0, 0, 0, 0, 0, 0], true),
+  unit("g", 1e-3, [0, 1, 0, 0, 0, 0, 0], true),
+  unit("s", 1.0, [0, 0, 1, 0, 0, 0, 0], true),
+  unit("A", 1.0, [0, 0, 0, 1, 0, 0, 0], true),
+  unit("K", 1.0, [0, 0, 0, 0, 1, 0, 0], true),
+  unit("mol", 1.0, [0, 0, 0, 0, 0, 1, 0], true),
+  unit("cd", 1.0, [0, 0, 0, 0, 0, 0, 1], true),
+  // Other metric units
+  unit("ohm", 1.0, [2, 1, -3, -2, 0, 0, 0], true),
+  unit("L", 1e-3, [3, 0, 0, 0, 0, 0, 0], true),
+  unit("t", 1e3, [0, 1, 0, 0, 0, 0, 0], false),
+  unit("eV", 1.602176634e-19, [2, 1, -2, 0, 0, 0, 0], true),
+  unit("cal", 4.184, [2, 1, -2, 0, 0, 0, 0], true),
+  unit("bar", 1e5, [-1, 1, -2, 0, 0, 0, 0], true),
+  unit("atm", 101325.0, [-1, 1, -2, 0, 0, 0, 0], false),
+  unit("min", 60.0, [0, 0, 1, 0, 0, 0, 0], false),
+  unit("h", 3600.0, [0, 0, 1, 0, 0, 0, 0], false),
+  unit("day", 86400.0, [0, 0, 1, 0, 0, 0, 0], false),
+  unit("week", 604800.0, [0, 0, 1, 0, 0, 0, 0], false),
+  unit("yr", 31557600.0, [0, 0, 1, 0, 0, 0, 0], false),
+  unit("rad", 1.0, [0, 0, 0, 0, 0, 0, 0], false),
+  unit("deg", std::f64::consts::PI / 180.0, [0, 0, 0, 0, 0, 0, 0], false),
+  unit("°", std::f64::consts::PI / 180.0, [0, 0, 0, 0, 0, 0, 0], false),
+  // Temperature scales
+  affine("°C", 1.0, 273.15, [0, 0, 0, 0, 1, 0, 0]),
+  affine("degC", 1.0, 273.15, [0, 0, 0, 0, 1, 0, 0]),
+  affine("°F", 5.0 / 9.0, 459.67 * 5.0 / 9.0, [0, 0, 0, 0, 1, 0, 0]),
+  affine("degF", 5.0 / 9.0, 459.67 * 5.0 / 9.0, [0, 0, 0, 0, 1, 0, 0]),
+  unit("°R", 5.0 / 9.0, [0, 0, 0, 0, 1, 0, 0], false),
+  // Imperial and US customary units
+  unit("inch", 0.0254, [1, 0, 0, 0, 0, 0, 0], false),
+  unit("ft", 0.3048, [1, 0, 0, 0, 0, 0, 0], false),
+  unit("yd", 0.9144, [1, 0, 0, 0, 0, 0, 0], false),
+  unit("mi", 1609.344, [1, 0, 0, 0, 0, 0, 0], false),
+  unit("nmi", 1852.0, [1, 0, 0, 0, 0, 0, 0], false),
+  unit("acre", 4046.8564224, [2, 0, 0, 0, 0, 0, 0], false),
+  unit("gal", 3.785411784e-3, [3, 0, 0, 0, 0, 0, 0], false),
+  unit("qt", 9.46352946e-4, [3, 0, 0, 0, 0, 0, 0], false),
+  unit("pt", 4.73176473e-4, [3, 0, 0, 0, 0, 0, 0], false),
+  unit("floz", 2.95735295625e-5, [3, 0, 0, 0, 0, 0, 0], false),
+  unit("oz", 0.028349523125, [0, 1, 0, 0, 0, 0, 0], false),
+  unit("lb", 0.45359237, [0, 1, 0, 0, 0, 0, 0], false),
+  unit("st", 6.35029318, [0, 1, 0, 0, 0, 0, 0], false),
+  unit("mph", 0.44704, [1, 0, -1, 0, 0, 0, 0], false),
+  unit("knot", 1852.0 / 3600.0, [1, 0, -1, 0, 0, 0, 0], false),
+  unit("lbf", 4.4482216152605, [1, 1, -2, 0, 0, 0, 0], false),
+  unit("psi", 6894.757293168, [-1, 1, -2, 0, 0, 0, 0], false),
+  unit("BTU", 1055.05585262, [2, 1, -2, 0, 0, 0, 0], false),
+  unit("hp", 745.6998715822702, [2, 1, -3, 0, 0, 0, 0], false),
+];
+
+/// The SI prefixes and their factors. `da` comes before `d` so that the longer
+/// prefix is tried first.
+const PREFIXES: &[(&str, f64)] = &[
+  ("Y", 1e24),
+  ("Z", 1e21),
+  ("E", 1e18),
+  ("P", 1e15),
+  ("T", 1e12),
+  ("G", 1e9),
+  ("M", 1e6),
+  ("k", 1e3),
+  ("h", 1e2),
+  ("da", 1e1),
+  ("d", 1e-1),
+  ("c", 1e-2),
+  ("m", 1e-3),
+  ("µ", 1e-6),
+  ("u", 1e-6),
+  ("n", 1e-9),
+  ("p", 1e-12),
+  ("f", 1e-15),
+  ("a", 1e-18),
+  ("z", 1e-21),
+  ("y", 1e-24),
+];
+
+impl fmt::Display for Dimension {
+  /// Shows the dimension in base units, e.g. `kg*m/s^2`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let power = |name: &str, exponent: i16| {
+      if exponent == 1 {
+        name.to_string()
+      } else {
+        format!("{}^{}", name, exponent)
+      }
+    };
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+    // List kg first, as in kg*m^2.
+    for &index in [1, 0, 2, 3, 4, 5, 6].iter() {
+      let exponent = self.0[index];
+      if exponent > 0 {
+        numerator.push(power(BASE_UNITS[index], i16::from(exponent)));
+      } else if exponent < 0 {
+        denominator.push(power(BASE_UNITS[index], -i16::from(exponent)));
+      }
+    }
+    let numerator = if numerator.is_empty() {
+      "1".to_string()
+    } else {
+      numerator.join("*")
+    };
+    match denominator.len() {
+      0 => write!(f, "{}", numerator),
+      1 => write!(f, "{}/{}", numerator, denominator[0]),
+      _ => write!(f, "{}/({})", numerator, denominator.join("*")),
+    }
+  }
+}
+
+impl Dimension {
+  /// The dimension of pure numbers.
+  pub fn none() -> Dimension {
+    Dimension([0; 7])
+  }
+
+  /// Tests whether this is the dimension of pure numbers.
+  pub fn is_none(&self) -> bool {
+    self.0.iter().all(|&exponent| exponent == 0)
+  }
+
+  /// The dimension of a product, if its exponents fit in an `i8`.
+  fn multiply(&self, other: &Dimension) -> Option<Dimension> {
+    let mut result = *self;
+    for (a, b) in result.0.iter_mut().zip(other.0.iter()) {
+      *a = a.checked_add(*b)?;
+    }
+    Some(result)
+  }
+
+  /// The dimension of a power, if the exponents stay integers.
+  fn power(&self, exponent: f64) -> Option<Dimension> {
+    let mut result = *self;
+    for a in result.0.iter_mut() {
+      let scaled = f64::from(*a) * exponent;
+      if (scaled - scaled.round()).abs() > 1e-9 || scaled.abs() > f64::from(i8::MAX) {
+        return None;
+      }
+      *a = scaled.round() as i8;
+    }
+    Some(result)
+  }
+}
+
+/// A number together with the dimension of its unit, stored in SI base units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+  /// The size of the quantity in SI base units
+  value: f64,
+  /// The dimension of the quantity
+  dimension: Dimension,
+  /// The zero point of an affine unit such as `°C` on its own, which becomes
+  /// part of the value once a number is given in that unit
+  offset: f64,
+  /// Where zero lies on the scale of a temperature given in an affine unit,
+  /// as in `20 °C`, or 0 for other quantities
+  zero: f64,
+  /// The unit to show the quantity in, as chosen by a conversion
+  shown_in: Option<ShownUnit>,
+}
+
+/// A unit that a quantity has been converted to.
+#[derive(Clone, Debug, PartialEq)]
+struct ShownUnit {
+  /// How the unit was written
+  name: String,
+  /// The size of the unit in SI base units
+  factor: f64,
+  /// Where zero of the unit lies, in SI base units
+  offset: f64,
+}
+
+impl fmt::Display for Quantity {
+  /// Shows the quantity in the unit it was converted to, or else in SI units,
+  /// using a named unit such as `N` when one has exactly this dimension.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.format(f, &NumberFormat::default())
+  }
+}
+
+impl Format for Quantity {
+  fn format(&self, f: &mut fmt::Formatter, numbers: &NumberFormat) -> fmt::Result {
+    if let Some(unit) = &self.shown_in {
+      let value = (self.value - unit.offset) / unit.factor;
+      // Hides the rounding error of the conversion by keeping 15 significant
+      // digits, so that `100 °C to °F` shows 212 rather than 211.99999999999997.
+      let value: f64 = format!("{:.14e}", value).parse().unwrap_or(value);
+      return write!(f, "{} {}", numbers.number(value), unit.name);
+    }
+    let value = numbers.number(self.value);
+    if self.dimension.is_none() {
+      return write!(f, "{}", value);
+    }
+    match UNITS
+      .iter()
+      .find(|unit| unit.factor == 1.0 && unit.offset == 0.0 && unit.dimension == self.dimension)
+    {
+      Some(unit) => write!(f, "{} {}", value, unit.name),
+      None => write!(f, "{} {}", value, self.dimension),
+    }
+  }
+}
+
+impl Quantity {
+  /// Creates a quantity from its size in SI base units.
+  pub fn new(value: f64, dimension: Dimension) -> Quantity {
+    Quantity {
+      value,
+      dimension,
+      offset: 0.0,
+      zero: 0.0,
+      shown_in: None,
+    }
+  }
+
+  /// A pure number.
+  pub fn number(value: f64) -> Quantity {
+    Quantity::new(value, Dimension::none())
+  }
+
+  /// The size of the quantity in SI base units.
+  pub fn value(&self) -> f64 {
+    self.value
+  }
+
+  /// The dimension of the quantity.
+  pub fn dimension(&self) -> Dimension {
+    self.dimension
+  }
+
+  /// Looks up a unit, possibly with an SI prefix, as the quantity of one unit.
+  pub fn unit(name: &str) -> Option<Quantity> {
+    let found = |name: &str| UNITS.iter().find(|unit| unit.name == name);
+    if let Some(unit) = found(name) {
+      let mut quantity = Quantity::new(unit.factor, unit.dimension);
+      quantity.offset = unit.offset;
+      return Some(quantity);
+    }
+    PREFIXES.iter().find_map(|(prefix, scale)| {
+      let unit = found(name.strip_prefix(prefix)?)?;
+      if unit.prefixable {
+        Some(Quantity::new(scale * unit.factor, unit.dimension))
+      } else {
+        None
+      }
+    })
+  }
+
+  /// Places an affine unit on its own on the absolute scale, so that `°C`
+  /// means one degree Celsius.
+  fn absolute(&self) -> Quantity {
+    if self.offset == 0.0 {
+      self.clone()
+    } else {
+      Quantity::new(self.value + self.offset, self.dimension)
+    }
+  }
+
+  /// Requires a pure number, e.g. for the argument of a function.
+  fn dimensionless(&self, context: &str) -> Result<f64, String> {
+    if self.dimension.is_none() {
+      Ok(self.value)
+    } else {
+      Err(format!(
+        "{} needs a dimensionless argument, not {}.",
+        context, self.dimension
+      ))
+    }
+  }
+}
+
+/// Evaluation with units of measure. Identifiers that are not variables are
+/// looked up as units.
+pub struct UnitArithmetic;
+
+impl Arithmetic for UnitArithmetic {
+  type Value = Quantity;
+
+  fn number(&self, value: f64) -> Result<Quantity, String> {
+    Ok(Quantity::number(value))
+  }
+
+  fn constant(&self, name: &str) -> Result<Quantity, String> {
+    match name {
+      "pi" => Ok(Quantity::number(std::f64::consts::PI)),
+      "e" => Ok(Quantity::number(std::f64::consts::E)),
+      _ => match constants::find(name) {
+        Some(constant) => Ok(Quantity::new(constant.value, Dimension(constant.dimension))),
+        None => Err(format!("Unknown constant {}", name)),
+      },
+    }
+  }
+
+  /// Adding to a temperature in an affine unit keeps it on that scale, and
+  /// of two such temperatures the second is a difference, measured from the
+  /// zero of its scale.
+  fn add(&self, a: &Quantity, b: &Quantity) -> Result<Quantity, String> {
+    let (a, b) = (a.absolute(), b.absolute());
+    if a.dimension != b.dimension {
+      return Err(format!(
+        "Dimension error: cannot add {} and {}.",
+        a.dimension, b.dimension
+      ));
+    }
+    let (b_value, zero) = match (a.zero, b.zero) {
+      (a_zero, b_zero) if a_zero != 0.0 && b_zero != 0.0 => (b.value - b_zero, a_zero),
+      (a_zero, b_zero) => (b.value, a_zero + b_zero),
+    };
+    let mut sum = Quantity::new(a.value + b_value, a.dimension);
+    sum.zero = zero;
+    Ok(sum)
+  }
+
+  /// A number times an affine unit, as in `20 °C`, is a point on that unit's
+  /// scale.
+  fn multiply(&self, a: &Quantity, b: &Quantity) -> Result<Quantity, String> {
+    if b.offset != 0.0 && a.offset == 0.0 && a.dimension.is_none() {
+      let mut point = Quantity::new(a.value * b.value + b.offset, b.dimension);
+      point.zero = b.offset;
+      return Ok(point);
+    }
+    let (a, b) = (a.absolute(), b.absolute());
+    match a.dimension.multiply(&b.dimension) {
+      Some(dimension) => Ok(Quantity::new(a.value * b.value, dimension)),
+      None => Err(format!(
+        "Dimension error: the product of {} and {} has too large a power.",
+        a.dimension, b.dimension
+      )),
+    }
+  }
+
+  fn divide(&self, a: &Quantity, b: &Quantity) -> Result<Quantity, String> {
+    let (a, b) = (a.absolute(), b.absolute());
+    match b.dimension.power(-1.0).and_then(|inverse| a.dimension.multiply(&inverse)) {
+      Some(dimension) => Ok(Quantity::new(a.value / b.value, dimension)),
+      None => Err(format!(
+        "Dimension error: the quotient of {} and {} has too large a power.",
+        a.dimension, b.dimension
+      )),
+    }
+  }
+
+  fn power(&self, base: &Quantity, exponent: &Quantity) -> Result<Quantity, String> {
+    let base = base.absolute();
+    let exponent = exponent.absolute().dimensionless("An exponent")?;
+    match base.dimension.power(exponent) {
+      Some(dimension) => Ok(Quantity::new(base.value.powf(exponent), dimension)),
+      None => Err(format!(
+        "Dimension error: cannot raise {} to the power {}.",
+        base.dimension, exponent
+      )),
+    }
+  }
+
+  fn function(&self, name: &str, argument: &Quantity) -> Result<Quantity, String> {
+    let argument = &argument.absolute();
+    match name {
+      "abs" => return Ok(Quantity::new(argument.value.abs(), argument.dimension)),
+      "cbrt" => {
+        return match argument.dimension.power(1.0 / 3.0) {
+          Some(dimension) => Ok(Quantity::new(argument.value.cbrt(), dimension)),
+          None => Err(format!(
+            "Dimension error: cannot take the cube root of {}.",
+            argument.dimension
+          )),
+        }
+      }
+      // The sign of a quantity does not depend on its unit.
+      "sign" if argument.value == 0.0 => return Ok(Quantity::number(0.0)),
+      "sign" => return Ok(Quantity::number(argument.value.signum())),
+      "sqrt" => return self.power(argument, &Quantity::number(0.5)),
+      _ => (),
+    }
+    let x = argument.dimensionless(name)?;
+    let value = apply_function(name, x).ok_or_else(|| format!("Unknown function {}", name))?;
+    Ok(Quantity::number(value))
+  }
+
+  /// Quantities compare only with quantities of the same dimension, except
+  /// that a zero compares with any quantity, as its sign does not depend on
+  /// the unit.
+  fn order(&self, a: &Quantity, b: &Quantity) -> Result<Option<Ordering>, String> {
+    let (a, b) = (a.absolute(), b.absolute());
+    let zero = |x: &Quantity| x.value == 0.0 && x.dimension.is_none();
+    if a.dimension == b.dimension || zero(&a) || zero(&b) {
+      Ok(a.value.partial_cmp(&b.value))
+    } else {
+      Err(format!(
+        "Dimension error: cannot compare {} and {}.",
+        a.dimension, b.dimension
+      ))
+    }
+  }
+
+  fn identifier(&self, name: &str) -> Result<Quantity, String> {
+    Quantity::unit(name).ok_or_else(|| format!("Unknown variable or unit {}", name))
+  }
+
+  fn convert(&self, value: &Quantity, unit: &Quantity, name: &str) -> Result<Quantity, String> {
+    let mut value = value.absolute();
+    if value.dimension != unit.dimension {
+      return Err(format!(
+        "Cannot convert {} to {}: {} and {} are different dimensions.",
+        value, name, value.dimension, unit.dimension
+      ));
+    }
+    value.shown_in = Some(ShownUnit {
+      name: name.to_string(),
+      factor: unit.value,
+      offset: unit.offset,
+    });
+    Ok(value)
+  }
+
+  fn finish(&self, value: Quantity) -> Quantity {
+    value.absolute()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use crate::lib::units::{Dimension, Quantity, UnitArithmetic};
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str) -> Result<Quantity, String> {
+    let tokens = Lexer::lex(input)?;
+    let ast = Parser::parse(&tokens)?;
+    UnitArithmetic.evaluate(&ast, &mut HashMap::new())
+  }
+
+  #[test]
+  fn units_multiply_and_simplify() {
+    assert_eq!(evaluate("5 kg * 3 m/s^2").unwrap().to_string(), "15 N");
+    assert_eq!(evaluate("3 m * 2 m").unwrap().to_string(), "6 m^2");
+    assert_eq!(evaluate("10 m / (4 s)").unwrap().to_string(), "2.5 m/s");
+    assert_eq!(evaluate("2 kg*m^2/s").unwrap().to_string(), "2 kg*m^2/s");
+  }
+
+  #[test]
+  fn prefixes_and_imperial_units() {
+    assert_eq!(evaluate("1.5 km").unwrap().to_string(), "1500 m");
+    assert_eq!(evaluate("250 ms").unwrap().to_string(), "0.25 s");
+    assert_eq!(evaluate("2 ft").unwrap().to_string(), "0.6096 m");
+    assert!((evaluate("1 lb").unwrap().value() - 0.45359237).abs() < 1e-12);
+  }
+
+  #[test]
+  fn constants_have_units() {
+    assert_eq!(evaluate("phys.c * 2 s").unwrap().to_string(), "599584916 m");
+    assert_eq!(evaluate("phys.h / phys.hbar").unwrap().dimension(), Dimension::none());
+    assert!(evaluate("phys.c + 1 m").is_err());
+  }
+
+  #[test]
+  fn conversions() {
+    assert_eq!(evaluate("3 m to cm").unwrap().to_string(), "300 cm");
+    assert_eq!(evaluate("1 atm to Pa").unwrap().to_string(), "101325 Pa");
+    let speed = evaluate("60 mph to km/h").unwrap().to_string();
+    assert!(speed.starts_with("96.56") && speed.ends_with(" km/h"));
+    assert_eq!(evaluate("5 kg*m/s^2 in N").unwrap().to_string(), "5 N");
+    assert!(evaluate("3 m to s").is_err());
+  }
+
+  #[test]
+  fn temperatures() {
+    assert_eq!(evaluate("100 °C to °F").unwrap().to_string(), "212 °F");
+    assert_eq!(evaluate("-40 °C to °F").unwrap().to_string(), "-40 °F");
+    assert_eq!(evaluate("0 °C to K").unwrap().to_string(), "273.15 K");
+    let boiling = evaluate("212 degF to degC").unwrap().value();
+    assert!((boiling - 373.15).abs() < 1e-9);
+    // The second of two temperatures is a difference.
+    assert_eq!(evaluate("20 °C + 5 °C").unwrap().to_string(), "298.15 K");
+    assert_eq!(evaluate("20 °C + 5 °C to °C").unwrap().to_string(), "25 °C");
+    assert_eq!(evaluate("20 °C + 1 K").unwrap().to_string(), "294.15 K");
+    let difference = evaluate("25 °C - 20 °C").unwrap().value();
+    assert!((difference - 5.0).abs() < 1e-9);
+    let warmer = evaluate("20 °C + 9 °F").unwrap().value();
+    assert!((warmer - 298.15).abs() < 1e-9);
+  }
+
+  #[test]
+  fn dimension_errors() {
+    assert!(evaluate("1 m + 1 s").is_err());
+    assert!(evaluate("sin(1 m)").is_err());
+    assert!(evaluate("2^(1 s)").is_err());
+    assert!(evaluate("sqrt(1 m)").is_err());
+    assert_eq!(evaluate("sqrt(4 m^2)").unwrap().to_string(), "2 m");
+    // Exponents beyond an `i8` are errors rather than overflowing or being
+    // dropped.
+    assert!(evaluate("m^100 * m^100").is_err());
+    assert!(evaluate("1 / (m^-100 * m^-28)").is_err());
+    assert_eq!(evaluate("m^100 / m^99").unwrap().to_string(), "1 m");
+  }
+}
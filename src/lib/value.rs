@@ -0,0 +1,1149 @@
+//! Evaluation in floating point, where a value is a number, a boolean, a
+//! matrix or a list. Functions and arithmetic broadcast over the items of lists.
+//!
+//! Integers beyond 2^53, which `f64` cannot all represent, are kept exact in
+//! `i128` when they come from integer literals, from sums, products, powers
+//! and exact quotients of integers, or from number theory, and become
+//! numbers as soon as they meet a number that is not whole.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::lib::angle::{self, convert_trigonometric, AngleUnit};
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::{self, apply_function, AstNode, Comparison};
+use crate::lib::constants;
+use crate::lib::distributions::{self, DISTRIBUTIONS};
+use crate::lib::fitting::{self, Fit};
+use crate::lib::format::{Format, Formatted, NumberFormat};
+use crate::lib::linalg::{self, ILL_CONDITIONED};
+use crate::lib::matrix::Matrix;
+use crate::lib::number_theory::{self, Factorization, NUMBER_THEORY};
+use crate::lib::special::{self, SPECIAL};
+use crate::lib::statistics::{self, STATISTICS};
+
+/// The most items that a range may have.
+const MAX_RANGE: f64 = 1e6;
+
+/// Every whole number up to this magnitude is exactly a `f64`.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+/// A value in floating point mode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Number(f64),
+  /// An exact integer beyond 2^53, where `f64` skips integers
+  Integer(i128),
+  /// A truth value, as a comparison gives
+  Boolean(bool),
+  Matrix(Matrix),
+  /// The named factors of a decomposition, such as `Q` and `R`
+  Factors(Vec<(&'static str, Matrix)>),
+  List(Vec<Value>),
+  /// The parameters of a fitted model, whose items are the parameters
+  Fit(Fit),
+  /// A prime factorization, whose items are the primes with multiplicity
+  Factorization(Factorization),
+}
+
+impl fmt::Display for Value {
+  /// With `{:#}`, matrices are written one row per line.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.format(f, &NumberFormat::default())
+  }
+}
+
+impl Format for Value {
+  /// Fits and factorizations keep their own format.
+  fn format(&self, f: &mut fmt::Formatter, numbers: &NumberFormat) -> fmt::Result {
+    match self {
+      Value::Number(number) => write!(f, "{}", numbers.number(*number)),
+      Value::Integer(n) => write!(f, "{}", numbers.integer(*n)),
+      Value::Boolean(value) => write!(f, "{}", value),
+      Value::Matrix(matrix) if f.alternate() => write!(f, "\n{:#}", Formatted(matrix, *numbers)),
+      Value::Matrix(matrix) => matrix.format(f, numbers),
+      Value::List(items) => {
+        let items: Vec<String> =
+          items.iter().map(|item| Formatted(item, *numbers).to_string()).collect();
+        write!(f, "{{{}}}", items.join(", "))
+      }
+      Value::Fit(fit) if f.alternate() => write!(f, "{:#}", fit),
+      Value::Fit(fit) => write!(f, "{}", fit),
+      Value::Factorization(factorization) => write!(f, "{}", factorization),
+      Value::Factors(factors) => {
+        for (k, (name, matrix)) in factors.iter().enumerate() {
+          match (k, f.alternate()) {
+            (_, true) => write!(f, "\n{} =\n{:#}", name, Formatted(matrix, *numbers))?,
+            (0, false) => write!(f, "{} = {}", name, Formatted(matrix, *numbers))?,
+            (_, false) => write!(f, ", {} = {}", name, Formatted(matrix, *numbers))?,
+          }
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl Value {
+  /// Names the value in an error message.
+  fn describe(&self) -> String {
+    match self {
+      Value::Number(number) => format!("the number {}", number),
+      Value::Integer(n) => format!("the integer {}", n),
+      Value::Boolean(value) => format!("the boolean {}", value),
+      Value::Matrix(matrix) => matrix.describe(),
+      Value::Factors(factors) => {
+        let names: Vec<&str> = factors.iter().map(|(name, _)| *name).collect();
+        format!("the factors {}", names.join(", "))
+      }
+      Value::List(_) => format!("the list {}", self),
+      Value::Fit(fit) => format!("the fit of {}", fit.names().join(", ")),
+      Value::Factorization(factorization) => format!("the factorization {}", factorization),
+    }
+  }
+
+  /// Reads an integer, such as an index, or returns `None`.
+  fn to_integer(&self) -> Option<i64> {
+    match self {
+      Value::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+      Value::Integer(n) => i64::try_from(*n).ok(),
+      _ => None,
+    }
+  }
+
+  /// Reads an exact integer: an `Integer`, or a whole number that `f64`
+  /// represents exactly.
+  fn to_exact(&self) -> Option<i128> {
+    match self {
+      Value::Number(number) if number.fract() == 0.0 && number.abs() <= MAX_EXACT => {
+        Some(*number as i128)
+      }
+      Value::Integer(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  /// Approximates an exact integer by a number, leaving other values alone.
+  fn approximate(&self) -> Cow<'_, Value> {
+    match self {
+      Value::Integer(n) => Cow::Owned(Value::Number(*n as f64)),
+      _ => Cow::Borrowed(self),
+    }
+  }
+
+  /// Treats a number as a 1×1 matrix, for the functions of matrices.
+  fn to_matrix(&self) -> Result<Matrix, String> {
+    match self.approximate().as_ref() {
+      Value::Number(number) => Ok(Matrix::new(1, 1, vec![*number])),
+      Value::Matrix(matrix) => Ok(matrix.clone()),
+      _ => Err(format!("Expected a matrix, not {}.", self.describe())),
+    }
+  }
+}
+
+/// An exact integer as a value, which is a `Number` if `f64` represents it
+/// exactly.
+fn exact(n: i128) -> Value {
+  if n.unsigned_abs() <= MAX_EXACT as u128 {
+    Value::Number(n as f64)
+  } else {
+    Value::Integer(n)
+  }
+}
+
+/// Applies `operation` to two exact integers if either is an `Integer` or
+/// the result is beyond 2^53, where `f64` would round it. Returns `None` if
+/// `f64` computes the result exactly, if an operand is not an exact integer,
+/// or if `operation` has no integer result.
+fn exactly(
+  a: &Value,
+  b: &Value,
+  operation: impl Fn(i128, i128) -> Option<i128>,
+) -> Option<Value> {
+  let result = operation(a.to_exact()?, b.to_exact()?)?;
+  let integers = matches!(a, Value::Integer(_)) || matches!(b, Value::Integer(_));
+  if integers || result.unsigned_abs() > MAX_EXACT as u128 {
+    Some(exact(result))
+  } else {
+    None
+  }
+}
+
+/// The quotient `x // y` of integers rounded down.
+fn floor_quotient(x: i128, y: i128) -> Option<i128> {
+  let quotient = x.checked_div(y)?;
+  if x % y != 0 && (x < 0) != (y < 0) {
+    Some(quotient - 1)
+  } else {
+    Some(quotient)
+  }
+}
+
+/// Reads an argument of a number theory function, which must be an exact
+/// whole number.
+fn whole(name: &str, x: &Value) -> Result<i128, String> {
+  x.to_exact().ok_or_else(|| {
+    format!(
+      "{} takes whole numbers, which beyond 2^53 must be exact integers such as 10^20, \
+       not {}.",
+      name,
+      x.describe()
+    )
+  })
+}
+
+/// Evaluates a number theory function exactly.
+fn integer_function(name: &str, arguments: &[Value]) -> Result<Value, String> {
+  let integers = arguments
+    .iter()
+    .map(|x| whole(name, x))
+    .collect::<Result<Vec<i128>, String>>()?;
+  number_theory::number_theory(name, &integers).map(exact)
+}
+
+/// Evaluation in binary floating point, with matrices. Solvers report
+/// ill-conditioned systems as warnings, which are kept until taken.
+#[derive(Default)]
+pub struct FloatArithmetic {
+  /// The warnings raised since they were last taken
+  warnings: RefCell<Vec<String>>,
+}
+
+impl FloatArithmetic {
+  /// Takes the warnings raised since the last call.
+  pub fn take_warnings(&self) -> Vec<String> {
+    self.warnings.take()
+  }
+
+  /// The numbers in a list or vector, e.g. for statistics.
+  fn numbers(&self, value: &Value) -> Result<Vec<f64>, String> {
+    self
+      .items(value)?
+      .iter()
+      .map(|item| match item.approximate().as_ref() {
+        Value::Number(number) => Ok(*number),
+        _ => Err(format!(
+          "Expected a list of numbers, not {}.",
+          value.describe()
+        )),
+      })
+      .collect()
+  }
+
+  /// Applies `operation` item by item if either operand is a list. A list
+  /// combines with a list of the same length or with any other value.
+  fn broadcast(
+    &self,
+    a: &Value,
+    b: &Value,
+    operation: impl Fn(&Value, &Value) -> Result<Value, String>,
+  ) -> Option<Result<Value, String>> {
+    let items: Result<Vec<Value>, String> = match (a, b) {
+      (Value::List(xs), Value::List(ys)) if xs.len() != ys.len() => {
+        return Some(Err(format!(
+          "Cannot combine {} and {}: their lengths differ.",
+          a.describe(),
+          b.describe()
+        )))
+      }
+      (Value::List(xs), Value::List(ys)) => {
+        xs.iter().zip(ys.iter()).map(|(x, y)| operation(x, y)).collect()
+      }
+      (Value::List(xs), y) => xs.iter().map(|x| operation(x, y)).collect(),
+      (x, Value::List(ys)) => ys.iter().map(|y| operation(x, y)).collect(),
+      _ => return None,
+    };
+    Some(items.map(Value::List))
+  }
+
+  /// Evaluates a function of several numbers, such as a distribution
+  /// function, mapping it over any lists among its arguments.
+  fn map_numbers(
+    &self,
+    name: &str,
+    arguments: &[Value],
+    function: fn(&str, &[f64]) -> Result<f64, String>,
+  ) -> Result<Value, String> {
+    self.map_items(name, arguments, &|row| {
+      let numbers = row
+        .iter()
+        .map(|argument| match argument.approximate().as_ref() {
+          Value::Number(x) => Ok(*x),
+          other => Err(format!("{} takes numbers, not {}.", name, other.describe())),
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+      function(name, &numbers).map(Value::Number)
+    })
+  }
+
+  /// Evaluates `function` of the arguments `name`, mapping it over any lists
+  /// among them, which must then have the same length.
+  fn map_items(
+    &self,
+    name: &str,
+    arguments: &[Value],
+    function: &dyn Fn(&[Value]) -> Result<Value, String>,
+  ) -> Result<Value, String> {
+    let lengths: Vec<usize> = arguments
+      .iter()
+      .filter_map(|argument| match argument {
+        Value::List(items) => Some(items.len()),
+        _ => None,
+      })
+      .collect();
+    match lengths.first() {
+      None => function(arguments),
+      Some(&length) if lengths.iter().any(|&other| other != length) => Err(format!(
+        "Cannot apply {} to lists of different lengths.",
+        name
+      )),
+      Some(&length) => (0..length)
+        .map(|i| {
+          let row: Vec<Value> = arguments
+            .iter()
+            .map(|argument| match argument {
+              Value::List(items) => items[i].clone(),
+              other => other.clone(),
+            })
+            .collect();
+          self.map_items(name, &row, function)
+        })
+        .collect::<Result<Vec<Value>, String>>()
+        .map(Value::List),
+    }
+  }
+
+  /// Warns if `a` is so ill-conditioned that a solution of `a x = b` may be
+  /// inaccurate.
+  fn check_conditioning(&self, a: &Matrix) {
+    let condition = linalg::condition_number(a);
+    if condition > ILL_CONDITIONED {
+      self.warnings.borrow_mut().push(format!(
+        "Warning: {} is ill-conditioned (condition number {:e}); the result may be inaccurate.",
+        a.describe(),
+        condition
+      ));
+    }
+  }
+}
+
+impl Arithmetic for FloatArithmetic {
+  type Value = Value;
+
+  fn number(&self, value: f64) -> Result<Value, String> {
+    Ok(Value::Number(value))
+  }
+
+  fn integer(&self, value: u128) -> Result<Value, String> {
+    Ok(i128::try_from(value).map_or(Value::Number(value as f64), exact))
+  }
+
+  fn constant(&self, name: &str) -> Result<Value, String> {
+    match name {
+      "pi" => Ok(Value::Number(std::f64::consts::PI)),
+      "e" => Ok(Value::Number(std::f64::consts::E)),
+      _ => match constants::find(name) {
+        Some(constant) => Ok(Value::Number(constant.value)),
+        None => Err(format!("Unknown constant {}", name)),
+      },
+    }
+  }
+
+  fn add(&self, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.add(a, b)) {
+      return result;
+    }
+    if let Some(result) = exactly(a, b, |x, y| x.checked_add(y)) {
+      return Ok(result);
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+      (Value::Matrix(a), Value::Matrix(b)) => a.add(b).map(Value::Matrix),
+      _ => Err(format!(
+        "Cannot add {} and {}.",
+        a.describe(),
+        b.describe()
+      )),
+    }
+  }
+
+  fn multiply(&self, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.multiply(a, b)) {
+      return result;
+    }
+    if let Some(result) = exactly(a, b, |x, y| x.checked_mul(y)) {
+      return Ok(result);
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+      (Value::Number(k), Value::Matrix(m)) | (Value::Matrix(m), Value::Number(k)) => {
+        Ok(Value::Matrix(m.scale(*k)))
+      }
+      (Value::Matrix(a), Value::Matrix(b)) => a.multiply(b).map(Value::Matrix),
+      _ => Err(format!(
+        "Cannot multiply {} by {}.",
+        a.describe(),
+        b.describe()
+      )),
+    }
+  }
+
+  /// Dividing by a matrix multiplies by its inverse.
+  fn divide(&self, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.divide(a, b)) {
+      return result;
+    }
+    let whole_quotient = |x: i128, y: i128| match x.checked_rem(y) {
+      Some(0) => x.checked_div(y),
+      _ => None,
+    };
+    if let Some(result) = exactly(a, b, whole_quotient) {
+      return Ok(result);
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+      (_, Value::Number(b)) => self.multiply(&a, &Value::Number(1.0 / b)),
+      (_, Value::Matrix(b)) => self.multiply(&a, &Value::Matrix(b.inverse()?)),
+      _ => Err(format!(
+        "Cannot divide {} by {}.",
+        a.describe(),
+        b.describe()
+      )),
+    }
+  }
+
+  fn floor_divide(&self, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.floor_divide(a, b)) {
+      return result;
+    }
+    if let Some(result) = exactly(a, b, floor_quotient) {
+      return Ok(result);
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(Value::Number(ast::floor_divide(*a, *b))),
+      _ => Err(format!("Cannot floor-divide {} by {}.", a.describe(), b.describe())),
+    }
+  }
+
+  /// The remainder is exact, unlike `a - b (a // b)`.
+  fn modulo(&self, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.modulo(a, b)) {
+      return result;
+    }
+    let remainder = |x: i128, y: i128| x.checked_sub(y.checked_mul(floor_quotient(x, y)?)?);
+    if let Some(result) = exactly(a, b, remainder) {
+      return Ok(result);
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(Value::Number(ast::modulo(*a, *b))),
+      _ => Err(format!("Cannot take {} mod {}.", a.describe(), b.describe())),
+    }
+  }
+
+  fn power(&self, base: &Value, exponent: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(base, exponent, |a, b| self.power(a, b)) {
+      return result;
+    }
+    if let Some(result) = exactly(base, exponent, |x, y| x.checked_pow(u32::try_from(y).ok()?)) {
+      return Ok(result);
+    }
+    let (base, exponent) = (base.approximate(), exponent.approximate());
+    match (base.as_ref(), exponent.as_ref()) {
+      (Value::Number(base), Value::Number(exponent)) => Ok(Value::Number(base.powf(*exponent))),
+      (Value::Matrix(matrix), Value::Number(exponent)) if exponent.fract() == 0.0 => {
+        matrix.powi(*exponent as i64).map(Value::Matrix)
+      }
+      (Value::Matrix(_), Value::Number(_)) => Err(format!(
+        "Cannot raise {} to the power {}: matrices only have integer powers.",
+        base.describe(),
+        exponent
+      )),
+      _ => Err(format!(
+        "Cannot raise {} to the power of {}.",
+        base.describe(),
+        exponent.describe()
+      )),
+    }
+  }
+
+  /// Numbers are exact at the angles where the function takes a simple value,
+  /// as in `cos(90°)`.
+  fn trigonometric(&self, name: &str, x: &Value, unit: AngleUnit) -> Result<Value, String> {
+    match x {
+      Value::Number(x) => angle::trigonometric(name, *x, unit)
+        .map(Value::Number)
+        .ok_or_else(|| format!("{} is not a trigonometric function.", name)),
+      Value::Integer(_) => self.trigonometric(name, &x.approximate(), unit),
+      Value::List(items) => items
+        .iter()
+        .map(|item| self.trigonometric(name, item, unit))
+        .collect::<Result<Vec<Value>, String>>()
+        .map(Value::List),
+      _ => convert_trigonometric(self, name, x, unit),
+    }
+  }
+
+  /// Functions other than `sum`, `prod`, `len` and statistics apply to each
+  /// item of a list.
+  fn function(&self, name: &str, argument: &Value) -> Result<Value, String> {
+    match (name, argument) {
+      ("len", _) => return Ok(Value::Number(self.items(argument)?.len() as f64)),
+      (_, _) if STATISTICS.contains(&name) => {
+        let xs = self.numbers(argument)?;
+        return statistics::statistic(name, &xs, None).map(Value::Number);
+      }
+      ("sum", _) => {
+        if let Ok(xs) = self.numbers(argument) {
+          return Ok(Value::Number(statistics::sum(&xs)));
+        }
+        let mut items = self.items(argument)?.into_iter();
+        let first = items.next().unwrap_or(Value::Number(0.0));
+        return items.try_fold(first, |acc, item| self.add(&acc, &item));
+      }
+      ("prod", _) => {
+        let mut items = self.items(argument)?.into_iter();
+        let first = items.next().unwrap_or(Value::Number(1.0));
+        return items.try_fold(first, |acc, item| self.multiply(&acc, &item));
+      }
+      (_, Value::List(items)) => {
+        return items
+          .iter()
+          .map(|item| self.function(name, item))
+          .collect::<Result<Vec<Value>, String>>()
+          .map(Value::List)
+      }
+      // Rounding keeps an exact integer, and so does `abs`.
+      ("floor" | "ceil" | "round" | "trunc", Value::Integer(_)) => return Ok(argument.clone()),
+      ("abs", Value::Integer(n)) if n.checked_abs().is_some() => return Ok(exact(n.abs())),
+      (_, Value::Integer(_)) => return self.function(name, &argument.approximate()),
+      _ => {}
+    }
+    let matrix = || argument.to_matrix();
+    let result = match name {
+      "cond" => Value::Number(linalg::condition_number(&matrix()?)),
+      "det" => Value::Number(matrix()?.determinant()?),
+      "eig" => {
+        let (values, vectors) = linalg::symmetric_eigen(&matrix()?)?;
+        Value::Factors(vec![("values", values), ("vectors", vectors)])
+      }
+      "inv" => {
+        let matrix = matrix()?;
+        self.check_conditioning(&matrix);
+        Value::Matrix(matrix.inverse()?)
+      }
+      "norm" => Value::Number(matrix()?.norm()),
+      "qr" => {
+        let (q, r) = linalg::qr(&matrix()?);
+        Value::Factors(vec![("Q", q), ("R", r)])
+      }
+      "rank" => Value::Number(matrix()?.rank() as f64),
+      "svd" => {
+        let (u, s, v) = linalg::svd(&matrix()?);
+        Value::Factors(vec![("U", u), ("S", s), ("V", v)])
+      }
+      "trace" => Value::Number(matrix()?.trace()?),
+      "transpose" => Value::Matrix(matrix()?.transpose()),
+      "cross" | "dot" | "lstsq" | "solve" => {
+        return Err(format!("{} takes two arguments, not 1.", name))
+      }
+      _ => match argument {
+        Value::Number(x) => {
+          let value = apply_function(name, *x).ok_or_else(|| format!("Unknown function {}", name))?;
+          Value::Number(value)
+        }
+        _ => {
+          return Err(format!(
+            "{} expects a number, not {}.",
+            name,
+            argument.describe()
+          ))
+        }
+      },
+    };
+    // Matrix functions of a number give a number.
+    match (argument, result) {
+      (Value::Number(_), Value::Matrix(matrix)) => Ok(Value::Number(matrix.get(0, 0))),
+      (_, result) => Ok(result),
+    }
+  }
+
+  fn call(&self, name: &str, arguments: &[Value]) -> Result<Value, String> {
+    // Functions of several numbers, other than number theory, approximate
+    // exact integers; `function` decides for functions of one.
+    let exact = NUMBER_THEORY.contains(&name) || arguments.len() < 2;
+    if !exact && arguments.iter().any(|a| matches!(a, Value::Integer(_))) {
+      let approximated: Vec<Value> =
+        arguments.iter().map(|a| a.approximate().into_owned()).collect();
+      return self.call(name, &approximated);
+    }
+    match (name, arguments) {
+      ("dot", [a, b]) => a.to_matrix()?.dot(&b.to_matrix()?).map(Value::Number),
+      ("cross", [a, b]) => a.to_matrix()?.cross(&b.to_matrix()?).map(Value::Matrix),
+      ("solve", [a, b]) => {
+        let a = a.to_matrix()?;
+        let x = linalg::solve(&a, &b.to_matrix()?)?;
+        self.check_conditioning(&a);
+        Ok(Value::Matrix(x))
+      }
+      ("lstsq", [a, b]) => {
+        let a = a.to_matrix()?;
+        let (x, rank) = linalg::lstsq(&a, &b.to_matrix()?)?;
+        if rank < a.columns() {
+          self.warnings.borrow_mut().push(format!(
+            "Warning: {} is rank deficient (rank {} of {}, condition number {:e}); \
+             this is the least-squares solution of minimum norm.",
+            a.describe(),
+            rank,
+            a.columns(),
+            linalg::condition_number(&a)
+          ));
+        } else {
+          self.check_conditioning(&a);
+        }
+        Ok(Value::Matrix(x))
+      }
+      ("linfit", [xs, ys]) => {
+        fitting::linear(&self.numbers(xs)?, &self.numbers(ys)?).map(Value::Fit)
+      }
+      ("polyfit", [xs, ys, degree]) => match degree.to_integer() {
+        Some(degree) if degree >= 0 => {
+          let (xs, ys) = (self.numbers(xs)?, self.numbers(ys)?);
+          fitting::polynomial(&xs, &ys, degree as usize).map(Value::Fit)
+        }
+        _ => Err(format!("The degree of polyfit must be a whole number, not {}.", degree)),
+      },
+      ("quantile", [xs, Value::Number(p)]) => {
+        statistics::statistic(name, &self.numbers(xs)?, Some(&[*p])).map(Value::Number)
+      }
+      ("cov" | "corr", [xs, ys]) => {
+        let ys = self.numbers(ys)?;
+        statistics::statistic(name, &self.numbers(xs)?, Some(&ys)).map(Value::Number)
+      }
+      // `max(1, 5, 3)` is the maximum of the list of its arguments.
+      (_, [_, _, ..]) if STATISTICS.contains(&name) => {
+        self.function(name, &Value::List(arguments.to_vec()))
+      }
+      _ if DISTRIBUTIONS.contains(&name) => {
+        self.map_numbers(name, arguments, distributions::distribution)
+      }
+      _ if SPECIAL.contains(&name) => self.map_numbers(name, arguments, special::special),
+      ("factor", [x @ (Value::Number(_) | Value::Integer(_))]) => {
+        number_theory::factor(whole(name, x)?).map(Value::Factorization)
+      }
+      ("factor", _) => Err("factor takes a whole number, as in factor(360).".to_string()),
+      // `gcd({12, 18})` is the greatest common divisor of the list.
+      ("gcd" | "lcm", [xs @ Value::List(_)]) => {
+        integer_function(name, &self.items(xs)?)
+      }
+      _ if NUMBER_THEORY.contains(&name) => {
+        self.map_items(name, arguments, &|row| integer_function(name, row))
+      }
+      ("linfit", _) => Err("linfit takes two lists, as in linfit(xs, ys).".to_string()),
+      ("polyfit", _) => {
+        Err("polyfit takes two lists and a degree, as in polyfit(xs, ys, 2).".to_string())
+      }
+      ("cross" | "dot" | "lstsq" | "solve", _) => Err(format!(
+        "{} takes two arguments, not {}.",
+        name,
+        arguments.len()
+      )),
+      (_, [argument]) => self.function(name, argument),
+      _ => Err(format!(
+        "{} takes one argument, not {}.",
+        name,
+        arguments.len()
+      )),
+    }
+  }
+
+  /// Fits by evaluating the model as any other expression, starting every
+  /// parameter at 1. Numeric variables may appear in the model as known
+  /// constants.
+  fn fit(
+    &self,
+    model: &AstNode,
+    variable: &str,
+    xs: &Value,
+    ys: &Value,
+    memory: &HashMap<String, Value>,
+  ) -> Result<Value, String> {
+    let (xs, ys) = (self.numbers(xs)?, self.numbers(ys)?);
+    let known: HashMap<String, Value> = memory
+      .iter()
+      .filter(|(_, value)| matches!(value, Value::Number(_) | Value::Integer(_)))
+      .map(|(name, value)| (name.clone(), value.clone()))
+      .collect();
+    let names: Vec<String> = model
+      .identifiers()
+      .into_iter()
+      .filter(|name| name != variable && !memory.contains_key(name))
+      .collect();
+    if names.is_empty() {
+      return Err(format!("The model {} has no parameters to fit.", model));
+    }
+    let evaluate = |parameters: &[f64], x: f64| {
+      let mut scope = known.clone();
+      scope.insert(variable.to_string(), Value::Number(x));
+      for (name, parameter) in names.iter().zip(parameters) {
+        scope.insert(name.clone(), Value::Number(*parameter));
+      }
+      match self.evaluate(model, &mut scope)?.approximate().as_ref() {
+        Value::Number(y) => Ok(*y),
+        other => Err(format!("The model {} gives {}, not a number.", model, other.describe())),
+      }
+    };
+    let initial = vec![1.0; names.len()];
+    // An error such as an unknown function is reported rather than fitted.
+    evaluate(&initial, xs.first().copied().unwrap_or(0.0))?;
+    let model = |parameters: &[f64], x: f64| evaluate(parameters, x).unwrap_or(f64::NAN);
+    fitting::nonlinear(model, names.clone(), initial, &xs, &ys).map(Value::Fit)
+  }
+
+  fn list(&self, items: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::List(items))
+  }
+
+  /// The items of a list, or the entries of a vector.
+  fn items(&self, value: &Value) -> Result<Vec<Value>, String> {
+    match value {
+      Value::List(items) => Ok(items.clone()),
+      Value::Fit(fit) => Ok(fit.parameters().iter().map(|&p| Value::Number(p)).collect()),
+      Value::Factorization(factorization) => {
+        Ok(factorization.primes().iter().map(|&p| exact(p as i128)).collect())
+      }
+      Value::Matrix(matrix) if matrix.is_vector() => {
+        Ok(matrix.entries().iter().map(|x| Value::Number(*x)).collect())
+      }
+      _ => Err(format!("Expected a list, not {}.", value.describe())),
+    }
+  }
+
+  /// The step defaults to 1, or -1 if `end` is below `start`. Each item is
+  /// computed from `start` rather than by repeated addition, and `end` is
+  /// included if it is within rounding error of a step.
+  fn range(&self, start: &Value, end: &Value, step: Option<&Value>) -> Result<Value, String> {
+    let (start, end) = (start.approximate(), end.approximate());
+    let step = step.map(Value::approximate);
+    let (start, end, step) = match (start.as_ref(), end.as_ref(), step.as_deref()) {
+      (Value::Number(start), Value::Number(end), None) => {
+        (*start, *end, if end < start { -1.0 } else { 1.0 })
+      }
+      (Value::Number(start), Value::Number(end), Some(Value::Number(step))) => {
+        (*start, *end, *step)
+      }
+      _ => return Err("The bounds and step of a range must be numbers.".to_string()),
+    };
+    if step == 0.0 || !step.is_finite() {
+      return Err(format!("The step of a range cannot be {}.", step));
+    }
+    let steps = ((end - start) / step * (1.0 + 1e-12)).floor();
+    if steps > MAX_RANGE {
+      return Err(format!(
+        "The range {}..{} step {} has more than {} items.",
+        start, end, step, MAX_RANGE
+      ));
+    }
+    let steps = if steps.is_nan() { -1.0 } else { steps };
+    let items = (0..=steps as i64)
+      .map(|k| Value::Number(start + k as f64 * step))
+      .collect();
+    Ok(Value::List(items))
+  }
+
+  /// Indices count from 1. A list or vector gives an item, and a matrix
+  /// gives a row.
+  fn index(&self, value: &Value, index: &Value) -> Result<Value, String> {
+    if let Value::List(indices) = index {
+      return indices
+        .iter()
+        .map(|index| self.index(value, index))
+        .collect::<Result<Vec<Value>, String>>()
+        .map(Value::List);
+    }
+    let length = match value {
+      Value::Matrix(matrix) if !matrix.is_vector() => matrix.rows(),
+      _ => self.items(value)?.len(),
+    };
+    let i = match index.to_integer() {
+      Some(i) if i >= 1 && i as usize <= length => i as usize - 1,
+      _ => {
+        return Err(format!(
+          "Cannot index {} by {}: indices are whole numbers from 1 to {}.",
+          value.describe(),
+          index,
+          length
+        ))
+      }
+    };
+    match value {
+      Value::Matrix(matrix) if !matrix.is_vector() => {
+        let row = (0..matrix.columns()).map(|j| matrix.get(i, j)).collect();
+        Ok(Value::Matrix(Matrix::new(1, matrix.columns(), row)))
+      }
+      _ => Ok(self.items(value)?.swap_remove(i)),
+    }
+  }
+
+  fn boolean(&self, value: bool) -> Result<Value, String> {
+    Ok(Value::Boolean(value))
+  }
+
+  /// Numbers are ordered, and so are booleans, with `false` before `true`.
+  fn order(&self, a: &Value, b: &Value) -> Result<Option<Ordering>, String> {
+    if let (Some(x), Some(y)) = (a.to_exact(), b.to_exact()) {
+      return Ok(Some(x.cmp(&y)));
+    }
+    let (a, b) = (a.approximate(), b.approximate());
+    match (a.as_ref(), b.as_ref()) {
+      (Value::Number(a), Value::Number(b)) => Ok(a.partial_cmp(b)),
+      (Value::Boolean(a), Value::Boolean(b)) => Ok(Some(a.cmp(b))),
+      _ => Err(format!("Cannot compare {} and {}.", a.describe(), b.describe())),
+    }
+  }
+
+  /// Lists are compared item by item.
+  fn compare(&self, comparison: Comparison, a: &Value, b: &Value) -> Result<Value, String> {
+    if let Some(result) = self.broadcast(a, b, |a, b| self.compare(comparison, a, b)) {
+      return result;
+    }
+    self.boolean(comparison.holds(self.order(a, b)?))
+  }
+
+  /// Nonzero numbers are true.
+  fn truth(&self, value: &Value) -> Result<bool, String> {
+    match value {
+      Value::Number(number) => Ok(*number != 0.0),
+      Value::Integer(n) => Ok(*n != 0),
+      Value::Boolean(value) => Ok(*value),
+      _ => Err(format!("Expected a condition, not {}.", value.describe())),
+    }
+  }
+
+  fn matrix(&self, rows: usize, columns: usize, entries: &[Value]) -> Result<Value, String> {
+    let numbers = entries
+      .iter()
+      .map(|entry| match entry.approximate().as_ref() {
+        Value::Number(number) => Ok(*number),
+        _ => Err(format!(
+          "The entries of a matrix must be numbers, not {}.",
+          entry.describe()
+        )),
+      })
+      .collect::<Result<Vec<f64>, String>>()?;
+    Ok(Value::Matrix(Matrix::new(rows, columns, numbers)))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lib::arithmetic::Arithmetic;
+  use crate::lib::lexer::Lexer;
+  use crate::lib::parser::Parser;
+  use crate::lib::value::{FloatArithmetic, Value};
+  use std::collections::HashMap;
+
+  fn evaluate(input: &str) -> Result<Value, String> {
+    let tokens = Lexer::lex(input)?;
+    let ast = Parser::parse(&tokens)?;
+    FloatArithmetic::default().evaluate(&ast, &mut HashMap::new())
+  }
+
+  #[test]
+  fn matrix_arithmetic() {
+    assert_eq!(evaluate("[1, 2; 3, 4]^2").unwrap().to_string(), "[7, 10; 15, 22]");
+    assert_eq!(evaluate("2 * [1, 2] - [1, 1]").unwrap().to_string(), "[1, 3]");
+    assert_eq!(evaluate("[1, 2; 3, 4] * [1; 1]").unwrap().to_string(), "[3; 7]");
+    assert_eq!(evaluate("[2, 0; 0, 4]^-1").unwrap().to_string(), "[0.5, 0; 0, 0.25]");
+    assert_eq!(evaluate("1 + 2 * 3").unwrap(), Value::Number(7.0));
+  }
+
+  #[test]
+  fn matrix_functions() {
+    assert_eq!(evaluate("det([1, 2; 3, 4])").unwrap().to_string(), "-2");
+    assert_eq!(evaluate("transpose [1, 2, 3]").unwrap().to_string(), "[1; 2; 3]");
+    assert_eq!(evaluate("trace([1, 2; 3, 4])").unwrap(), Value::Number(5.0));
+    assert_eq!(evaluate("rank([1, 2; 2, 4])").unwrap(), Value::Number(1.0));
+    assert_eq!(evaluate("norm([3, 4])").unwrap(), Value::Number(5.0));
+    assert_eq!(evaluate("dot([1, 2, 3], [4, 5, 6])").unwrap(), Value::Number(32.0));
+    assert_eq!(evaluate("cross([1, 0, 0], [0, 1, 0])").unwrap().to_string(), "[0, 0, 1]");
+    assert_eq!(evaluate("inv(4)").unwrap(), Value::Number(0.25));
+  }
+
+  #[test]
+  fn solvers() {
+    assert_eq!(evaluate("solve([2, 0; 0, 4], [2, 2])").unwrap().to_string(), "[1; 0.5]");
+    match evaluate("lstsq([1, 0; 1, 1; 1, 2], [1; 3; 5])").unwrap() {
+      Value::Matrix(fit) => {
+        assert!((fit.get(0, 0) - 1.0).abs() < 1e-12 && (fit.get(1, 0) - 2.0).abs() < 1e-12)
+      }
+      fit => panic!("Expected a matrix, not {}", fit),
+    }
+    let eig = evaluate("eig([2, 0; 0, 1])").unwrap();
+    assert_eq!(eig.to_string(), "values = [1; 2], vectors = [0, 1; 1, 0]");
+    assert!(evaluate("qr([1, 2; 3, 4]) + 1").is_err());
+  }
+
+  #[test]
+  fn ill_conditioned_systems_warn() {
+    let arithmetic = FloatArithmetic::default();
+    let tokens = Lexer::lex("solve([1, 1; 1, 1.0000000001], [2, 2])").unwrap();
+    let ast = Parser::parse(&tokens).unwrap();
+    assert!(arithmetic.evaluate(&ast, &mut HashMap::new()).is_ok());
+    let warnings = arithmetic.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("condition number"));
+    assert!(arithmetic.take_warnings().is_empty());
+  }
+
+  #[test]
+  fn lists_and_ranges() {
+    assert_eq!(evaluate("1..5").unwrap().to_string(), "{1, 2, 3, 4, 5}");
+    assert_eq!(evaluate("0..1 step 0.25").unwrap().to_string(), "{0, 0.25, 0.5, 0.75, 1}");
+    assert_eq!(len("0..1 step 0.1"), 11);
+    assert_eq!(evaluate("3..1").unwrap().to_string(), "{3, 2, 1}");
+    assert_eq!(evaluate("{1, 2, 3}^2 + 1").unwrap().to_string(), "{2, 5, 10}");
+    assert_eq!(evaluate("sqrt({4, 9})").unwrap().to_string(), "{2, 3}");
+    assert_eq!(evaluate("{1, 2} * {3, 4}").unwrap().to_string(), "{3, 8}");
+    assert!(evaluate("{1, 2} + {1, 2, 3}").is_err());
+    assert!(evaluate("0..1 step 0").is_err());
+  }
+
+  /// The length of the list that `input` evaluates to.
+  fn len(input: &str) -> usize {
+    match evaluate(input).unwrap() {
+      Value::List(items) => items.len(),
+      value => panic!("Expected a list, not {}", value),
+    }
+  }
+
+  #[test]
+  fn list_functions() {
+    assert_eq!(evaluate("map(x -> x^2, 1..4)").unwrap().to_string(), "{1, 4, 9, 16}");
+    assert_eq!(evaluate("filter(x -> x - 2, 1..4)").unwrap().to_string(), "{1, 3, 4}");
+    assert_eq!(evaluate("reduce((a, b) -> a * b, 1..5)").unwrap(), Value::Number(120.0));
+    assert_eq!(evaluate("sum(1..100)").unwrap(), Value::Number(5050.0));
+    assert_eq!(evaluate("prod({2, 3, 4})").unwrap(), Value::Number(24.0));
+    assert_eq!(evaluate("len(1..10)").unwrap(), Value::Number(10.0));
+    assert_eq!(evaluate("sum([1, 2, 3])").unwrap(), Value::Number(6.0));
+    assert!(evaluate("reduce((a, b) -> a + b, {})").is_err());
+    assert!(evaluate("map(x -> x, 3)").is_err());
+  }
+
+  #[test]
+  fn statistics() {
+    assert_eq!(evaluate("mean(1..10)").unwrap(), Value::Number(5.5));
+    assert_eq!(evaluate("median({5, 1, 3})").unwrap(), Value::Number(3.0));
+    assert_eq!(evaluate("max(1, 5, 3)").unwrap(), Value::Number(5.0));
+    assert_eq!(evaluate("varp({1, 3})").unwrap(), Value::Number(1.0));
+    assert_eq!(evaluate("quantile(0..100, 0.9)").unwrap(), Value::Number(90.0));
+    assert_eq!(evaluate("corr(1..3, {3, 2, 1})").unwrap(), Value::Number(-1.0));
+    assert_eq!(evaluate("sum({0.1, 0.2, 0.3})").unwrap(), Value::Number(0.6));
+    assert!(evaluate("stdev({1})").is_err());
+    assert!(evaluate("mean({})").is_err());
+  }
+
+  #[test]
+  fn fits() {
+    let fit = |input: &str| match evaluate(input).unwrap() {
+      Value::Fit(fit) => fit,
+      other => panic!("{} is not a fit", other),
+    };
+    let line = fit("linfit(1..5, {3, 5, 7, 9, 11})");
+    assert!((line.parameters()[1] - 2.0).abs() < 1e-12);
+    assert!((line.r_squared() - 1.0).abs() < 1e-12);
+    let parabola = fit("polyfit(0..4, map(x -> x^2 - 1, 0..4), 2)");
+    assert!((parabola.parameters()[0] + 1.0).abs() < 1e-12);
+    let model = fit("fit(a*exp(b*t), t, 0..5, map(t -> 2*exp(t/2), 0..5))");
+    assert_eq!(model.names(), ["a", "b"]);
+    assert!((model.parameters()[1] - 0.5).abs() < 1e-9);
+    // Models use functions of several arguments as anywhere else.
+    let clipped = fit("fit(a*max(x, 2), x, 1..5, {4, 4, 6, 8, 10})");
+    assert!((clipped.parameters()[0] - 2.0).abs() < 1e-9);
+    let gated = fit("fit(if x < 3 then c else 2c, x, 1..5, {1, 1, 2, 2, 2})");
+    assert!((gated.parameters()[0] - 1.0).abs() < 1e-9);
+    assert!(evaluate("fit(x < a, x, 1..3, 1..3)").is_err());
+    assert_eq!(evaluate("linfit(1..3, {1, 3, 5})[1]").unwrap(), Value::Number(-1.0));
+    assert!(evaluate("fit(2*x, x, 1..3, 1..3)").is_err());
+    assert!(evaluate("fit(a*x, 2, 1..3, 1..3)").is_err());
+    assert!(evaluate("polyfit(1..3, 1..3, 0.5)").is_err());
+  }
+
+  #[test]
+  fn distributions() {
+    assert_eq!(evaluate("binompmf(2, 4, 0.5)").unwrap(), Value::Number(0.375));
+    assert_eq!(evaluate("normcdf(0)").unwrap(), Value::Number(0.5));
+    match evaluate("binomcdf(0..4, 4, 0.5)").unwrap() {
+      Value::List(items) => {
+        let expected = [0.0625, 0.3125, 0.6875, 0.9375, 1.0];
+        assert_eq!(items.len(), expected.len());
+        for (item, p) in items.iter().zip(expected.iter()) {
+          assert!(matches!(item, Value::Number(x) if (x - p).abs() < 1e-12));
+        }
+      }
+      other => panic!("{} is not a list", other),
+    }
+    assert!(evaluate("normcdf({1, 2}, {0, 1, 2}, 1)").is_err());
+    assert!(evaluate("tinv(0.9)").is_err());
+  }
+
+  #[test]
+  fn elementary_functions() {
+    let number = |input: &str| match evaluate(input).unwrap() {
+      Value::Number(x) => x,
+      other => panic!("{} is not a number", other),
+    };
+    assert_eq!(number("log2(8)"), 3.0);
+    assert_eq!(number("log10(1000)"), 3.0);
+    assert_eq!(number("log1p(10^-20)"), number("10^-20"));
+    assert_eq!(number("expm1(10^-20)"), number("10^-20"));
+    assert_eq!(number("cbrt(-27)"), -3.0);
+    assert_eq!(number("floor(-2.5) + ceil(-2.5)"), -5.0);
+    assert_eq!(number("round(2.5) + trunc(-2.5)"), 1.0);
+    assert_eq!(number("sign(-3) + sign(0)"), -1.0);
+    assert_eq!(number("frac(-2.25)"), -0.25);
+    assert_eq!(number("sec(0) + sech(0)"), 2.0);
+    assert!((number("acot(1)") - std::f64::consts::FRAC_PI_4).abs() < 1e-15);
+    for (f, inverse) in [("csc", "acsc"), ("cot", "acot"), ("coth", "acoth"), ("asech", "sech")] {
+      let x = number(&format!("{}({}(0.5))", inverse, f));
+      assert!((x - 0.5).abs() < 1e-15, "{}({}(0.5)) = {}", inverse, f, x);
+    }
+  }
+
+  #[test]
+  fn remainders_and_factorials() {
+    let number = |input: &str| match evaluate(input).unwrap() {
+      Value::Number(x) => x,
+      other => panic!("{} is not a number", other),
+    };
+    assert_eq!(number("5!"), 120.0);
+    assert_eq!(number("2^3!"), 64.0);
+    assert_eq!(number("-3!"), -6.0);
+    assert!((number("0.5!") - std::f64::consts::PI.sqrt() / 2.0).abs() < 1e-15);
+    // The remainder has the sign of the divisor.
+    assert_eq!(number("7 % 3"), 1.0);
+    assert_eq!(number("-7 mod 3"), 2.0);
+    assert_eq!(number("7 mod -3"), -2.0);
+    assert_eq!(number("7 // 2"), 3.0);
+    assert_eq!(number("-7 // 2"), -4.0);
+    assert_eq!(number("-7 // 2 * 2 + -7 mod 2"), -7.0);
+    assert_eq!(number("200 + 10%"), 220.0);
+    assert_eq!(number("200 - 10%"), 180.0);
+    assert_eq!(number("50% * 8"), 4.0);
+    assert_eq!(evaluate("{5, 7} % 3").unwrap(), evaluate("{2, 1}").unwrap());
+  }
+
+  #[test]
+  fn special_functions() {
+    assert_eq!(evaluate("gamma(5)").unwrap(), Value::Number(24.0));
+    assert_eq!(evaluate("erf(0)").unwrap(), Value::Number(0.0));
+    assert_eq!(evaluate("zeta(-2)").unwrap(), Value::Number(0.0));
+    assert_eq!(evaluate("besselj(0, 0)").unwrap(), Value::Number(1.0));
+    assert_eq!(evaluate("lambertw(-1, -1/e)").unwrap(), evaluate("lambertw(-1/e)").unwrap());
+    match evaluate("beta({1, 2}, 1)").unwrap() {
+      Value::List(items) => assert_eq!(items, vec![Value::Number(1.0), Value::Number(0.5)]),
+      other => panic!("{} is not a list", other),
+    }
+    assert!(evaluate("besselj(1.5, 2)").is_err());
+    assert!(evaluate("lambertw(1, 2)").is_err());
+  }
+
+  #[test]
+  fn number_theory() {
+    assert_eq!(evaluate("gcd(12, 18)").unwrap(), Value::Number(6.0));
+    assert_eq!(evaluate("lcm({4, 6, 10})").unwrap(), Value::Number(60.0));
+    assert_eq!(evaluate("nCr(5, 2) + binom(5, 3)").unwrap(), Value::Number(20.0));
+    assert_eq!(evaluate("nPr(5, 2)").unwrap(), Value::Number(20.0));
+    assert_eq!(evaluate("isprime(1..6)").unwrap().to_string(), "{0, 1, 1, 0, 1, 0}");
+    assert_eq!(evaluate("nextprime(2^31)").unwrap(), Value::Number(2147483659.0));
+    assert_eq!(evaluate("totient(36)").unwrap(), Value::Number(12.0));
+    assert_eq!(evaluate("powmod(2, 10, 1000)").unwrap(), Value::Number(24.0));
+    assert_eq!(evaluate("modinv(3, 7)").unwrap(), Value::Number(5.0));
+    assert_eq!(evaluate("factor(360)").unwrap().to_string(), "2^3 * 3^2 * 5");
+    assert_eq!(evaluate("sum(factor(360))").unwrap(), Value::Number(17.0));
+    assert!(evaluate("gcd(1.5, 3)").is_err());
+    assert!(evaluate("isprime(2^60 + 0.5)").is_err());
+    assert!(evaluate("factor(360) + 1").is_err());
+  }
+
+  #[test]
+  fn exact_integers() {
+    let exact = |input: &str| evaluate(input).unwrap().to_string();
+    assert_eq!(exact("isprime(2^61 - 1)"), "1");
+    assert_eq!(exact("binom(60, 30)"), "118264581564861424");
+    assert_eq!(exact("gcd(2^60, 2^61)"), "1152921504606846976");
+    assert_eq!(exact("powmod(3, 10^18, 10^18 + 7)"), "306506197988169072");
+    assert_eq!(exact("lcm(2^40, 3^20)"), "3833759992447475122176");
+    assert_eq!(exact("nCr(100, 50)"), "100891344545564193334812497256");
+    assert_eq!(exact("123456789012345678901 - 1"), "123456789012345678900");
+    assert_eq!(exact("10^20 // 7"), "14285714285714285714");
+    assert_eq!(exact("abs(-2^60)"), "1152921504606846976");
+    assert_eq!(evaluate("2^61 > 2^61 - 1").unwrap(), Value::Boolean(true));
+    // A number that is not whole makes the result a number.
+    assert_eq!(evaluate("2^64 + 0.5").unwrap(), Value::Number(18446744073709551616.0));
+    assert_eq!(evaluate("2^60 / 3").unwrap(), Value::Number(2f64.powi(60) / 3.0));
+  }
+
+  #[test]
+  fn indexing() {
+    let mut memory = HashMap::new();
+    let arithmetic = FloatArithmetic::default();
+    for input in ["xs = 10..50 step 10", "m = [1, 2; 3, 4]"].iter() {
+      let tokens = Lexer::lex(input).unwrap();
+      arithmetic.evaluate(&Parser::parse(&tokens).unwrap(), &mut memory).unwrap();
+    }
+    let mut index = |input: &str| {
+      let tokens = Lexer::lex(input).unwrap();
+      arithmetic.evaluate(&Parser::parse(&tokens).unwrap(), &mut memory)
+    };
+    assert_eq!(index("xs[3]").unwrap(), Value::Number(30.0));
+    assert_eq!(index("xs[{1, 5}]").unwrap().to_string(), "{10, 50}");
+    assert_eq!(index("m[2]").unwrap().to_string(), "[3, 4]");
+    assert_eq!(index("m[2][1]").unwrap(), Value::Number(3.0));
+    assert!(index("xs[0]").is_err());
+    assert!(index("xs[6]").is_err());
+    // A parameter hides a variable only inside its function.
+    assert!(index("map(xs -> xs + 1, 1..2)").is_ok());
+    assert_eq!(index("len(xs)").unwrap(), Value::Number(5.0));
+  }
+
+  #[test]
+  fn shape_errors_name_the_operands() {
+    let error = evaluate("[1, 2] * [3, 4]").unwrap_err();
+    assert!(error.contains("[1, 2]") && error.contains("[3, 4]"), "{}", error);
+    let error = evaluate("[1, 2] + [1, 2, 3]").unwrap_err();
+    assert!(error.contains("[1, 2, 3]"), "{}", error);
+    assert!(evaluate("1 + [1, 2]").is_err());
+    assert!(evaluate("det([1, 2, 3])").is_err());
+    assert!(evaluate("sin([1, 2])").is_err());
+    assert!(evaluate("dot([1, 2])").is_err());
+  }
+
+  #[test]
+  fn comparisons_and_conditionals() {
+    assert_eq!(evaluate("1 < 2").unwrap(), Value::Boolean(true));
+    assert_eq!(evaluate("0 <= 15 < 10").unwrap(), Value::Boolean(false));
+    assert_eq!(evaluate("not (1 == 1) or 2 != 3").unwrap(), Value::Boolean(true));
+    assert_eq!(evaluate("0/0 == 0/0").unwrap(), Value::Boolean(false));
+    assert_eq!(evaluate("{1, 2, 3} >= 2").unwrap().to_string(), "{false, true, true}");
+    assert_eq!(evaluate("filter(x -> x > 1, {1, 2, 3})").unwrap().to_string(), "{2, 3}");
+    assert_eq!(evaluate("if 120 <= 100 then 1 else 2").unwrap(), Value::Number(2.0));
+    assert_eq!(evaluate("2 * (1.5 > 1 ? 1 : 1.5)").unwrap(), Value::Number(2.0));
+    // Only the branch that is chosen, and only the operands that decide, are
+    // evaluated.
+    assert_eq!(evaluate("1 > 0 ? 1 : undefined").unwrap(), Value::Number(1.0));
+    assert_eq!(evaluate("false and undefined").unwrap(), Value::Boolean(false));
+    assert_eq!(evaluate("true or undefined").unwrap(), Value::Boolean(true));
+    assert!(evaluate("true and undefined").is_err());
+    assert!(evaluate("true + 1").is_err());
+    assert!(evaluate("[1, 2] < 3").is_err());
+  }
+}
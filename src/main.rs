@@ -1,10 +1,27 @@
+#![allow(special_module_name)]
+
 use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, Write};
 
 pub mod lib;
 
+use crate::lib::angle::{in_angle_mode, AngleUnit};
+use crate::lib::arithmetic::Arithmetic;
+use crate::lib::ast::AstNode;
+use crate::lib::constants;
+use crate::lib::decimal::{Decimal, DecimalContext};
+use crate::lib::format::{split_format, Formatted, NumberFormat};
+use crate::lib::interval::{Interval, IntervalArithmetic};
 use crate::lib::lexer::Lexer;
-use crate::lib::parser::Parser;
+use crate::lib::modular::{ModularArithmetic, Residue};
+use crate::lib::parser::{Multiplication, Parser};
+use crate::lib::programmer::{ProgrammerArithmetic, Word};
+use crate::lib::recognize::{recognize, DEFAULT_TOLERANCE};
+use crate::lib::uncertainty::{Measurement, UncertaintyArithmetic};
+use crate::lib::units::{Quantity, UnitArithmetic};
+use crate::lib::unlines;
+use crate::lib::value::{FloatArithmetic, Value};
 
 /// A simple enumeration to determine if the program should continue or halt.
 /// The program halts on empty input.
@@ -13,10 +30,67 @@ enum State {
     Exit,
 }
 
+/// The arithmetic used to evaluate expressions.
+enum Mode {
+    /// Binary floating point, with matrices
+    Float,
+    /// Fixed-point decimal arithmetic
+    Decimal(DecimalContext),
+    /// Interval arithmetic with guaranteed bounds
+    Interval,
+    /// The integers modulo n
+    Modular(ModularArithmetic),
+    /// Fixed-width integers with bitwise operators
+    Programmer(ProgrammerArithmetic),
+    /// Values with uncertainties, propagated to first order
+    Uncertainty,
+    /// Quantities with units of measure
+    Units,
+}
+
+/// The state of the REPL: the current mode and the variables assigned in each
+/// mode.
+struct Session {
+    mode: Mode,
+    /// The unit of angle of trigonometric functions, outside units mode
+    angle: AngleUnit,
+    /// Whether juxtaposition such as `2x` multiplies
+    multiplication: Multiplication,
+    memory: HashMap<String, Value>,
+    decimal_memory: HashMap<String, Decimal>,
+    interval_memory: HashMap<String, Interval>,
+    modular_memory: HashMap<String, Residue>,
+    programmer_memory: HashMap<String, Word>,
+    float: FloatArithmetic,
+    /// How numbers are written in floating point and units mode
+    format: NumberFormat,
+    /// The tolerance within which numbers are recognized as closed forms, if
+    /// they are
+    recognition: Option<f64>,
+    uncertainty: UncertaintyArithmetic,
+    uncertainty_memory: HashMap<String, Measurement>,
+    unit_memory: HashMap<String, Quantity>,
+}
+
 fn main() -> io::Result<()> {
-    let mut memory: HashMap<String, f64> = HashMap::new();
+    let mut session = Session {
+        mode: Mode::Float,
+        angle: AngleUnit::Radians,
+        multiplication: Multiplication::Implicit,
+        memory: HashMap::new(),
+        float: FloatArithmetic::default(),
+        format: NumberFormat::default(),
+        recognition: None,
+        decimal_memory: HashMap::new(),
+        interval_memory: HashMap::new(),
+        modular_memory: HashMap::new(),
+        programmer_memory: HashMap::new(),
+        uncertainty: UncertaintyArithmetic::default(),
+        uncertainty_memory: HashMap::new(),
+        unit_memory: HashMap::new(),
+    };
     loop {
-        match read_line(&mut memory) {
+        match read_line(&mut session) {
             Ok(State::Continue) => continue,
             Ok(State::Exit) => break,
             Err(err) => {
@@ -30,23 +104,88 @@ fn main() -> io::Result<()> {
 
 /// Reads the current line of input and evaluates it. The state that it returns
 /// indicates whether or not the main program should continue.
-fn read_line(memory: &mut HashMap<String, f64>) -> io::Result<State> {
+fn read_line(session: &mut Session) -> io::Result<State> {
     print!("> ");
     io::stdout().flush()?;
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
     input = input.trim().to_string();
-    if input.len() == 0 {
+    if input.is_empty() {
         Ok(State::Exit)
+    } else if let Some(command) = input.strip_prefix(':') {
+        match run_command(session, command) {
+            Ok(message) => println!("{}", message),
+            Err(message) => eprintln!("{}", message),
+        }
+        Ok(State::Continue)
     } else {
-        match Lexer::lex(&input) {
+        // A trailing format such as `to sci 3` applies to this line only.
+        let (input, format) = match split_format(&input) {
+            Some((expression, spec)) => {
+                let mut format = session.format;
+                if let Err(message) = format.apply(spec) {
+                    eprintln!("{}", message);
+                    return Ok(State::Continue);
+                }
+                (expression, format)
+            }
+            None => (input.as_str(), session.format),
+        };
+        match Lexer::lex(input) {
             Ok(tokens) => {
                 for token in tokens.clone() {
                     print!("{}", token);
                 }
-                println!("");
-                match Parser::parse(tokens.as_slice()) {
-                    Ok(ast) => println!("{} = {}", ast, ast.evaluate(memory)),
+                println!();
+                // Units mode measures angles in its units, such as `deg`.
+                let angle = match session.mode {
+                    Mode::Units => AngleUnit::Radians,
+                    _ => session.angle,
+                };
+                let parsed = Parser::parse_with(tokens.as_slice(), session.multiplication)
+                    .map(|ast| (in_angle_mode(&ast, angle), ast));
+                match parsed {
+                    Ok((angled, ast)) => match &session.mode {
+                        Mode::Float => {
+                            let result = session.float.evaluate(&angled, &mut session.memory);
+                            for warning in session.float.take_warnings() {
+                                eprintln!("{}", warning);
+                            }
+                            let recognition = session.recognition;
+                            print_result(
+                                &ast,
+                                result.map(|value| recognized(value, format, recognition)),
+                            )
+                        }
+                        Mode::Decimal(context) => print_result(
+                            &ast,
+                            context.evaluate(&angled, &mut session.decimal_memory),
+                        ),
+                        Mode::Interval => print_result(
+                            &ast,
+                            IntervalArithmetic.evaluate(&angled, &mut session.interval_memory),
+                        ),
+                        Mode::Modular(arithmetic) => print_result(
+                            &ast,
+                            arithmetic.evaluate(&angled, &mut session.modular_memory),
+                        ),
+                        Mode::Programmer(arithmetic) => print_result(
+                            &ast,
+                            arithmetic.evaluate(&angled, &mut session.programmer_memory),
+                        ),
+                        Mode::Uncertainty => print_result(
+                            &ast,
+                            session
+                                .uncertainty
+                                .evaluate(&angled, &mut session.uncertainty_memory),
+                        ),
+                        Mode::Units => print_result(
+                            &ast,
+                            UnitArithmetic
+                                .evaluate(&angled, &mut session.unit_memory)
+                                .map(|quantity| Formatted(quantity, format)),
+                        ),
+                    },
                     Err(message) => eprintln!("{}", message),
                 }
             }
@@ -55,3 +194,179 @@ fn read_line(memory: &mut HashMap<String, f64>) -> io::Result<State> {
         Ok(State::Continue)
     }
 }
+
+/// Prints the result of evaluating `ast`, or the error that stopped it.
+/// Results are written in their alternate form, which lays out matrices on
+/// the lines below.
+fn print_result<T: fmt::Display>(ast: &AstNode, result: Result<T, String>) {
+    match result {
+        Ok(value) => {
+            let value = format!("{:#}", value);
+            if value.starts_with('\n') {
+                println!("{} ={}", ast, value)
+            } else {
+                println!("{} = {}", ast, value)
+            }
+        }
+        Err(message) => eprintln!("{}", message),
+    }
+}
+
+/// Writes a floating point result in `format`, followed by the closed form
+/// that a number is recognized as within `tolerance`, as in `0.25 = 1/4` or
+/// `0.7853981633974483 ≈ pi/4`.
+fn recognized(value: Value, format: NumberFormat, tolerance: Option<f64>) -> String {
+    let form = match (&value, tolerance) {
+        (Value::Number(x), Some(tolerance)) => recognize(*x, tolerance),
+        _ => None,
+    };
+    let value = format!("{:#}", Formatted(value, format));
+    match form {
+        Some(form) if form.is_integer() => value,
+        Some(form) if form.is_exact() => format!("{} = {}", value, form),
+        Some(form) => format!("{} ≈ {} (relative error {:.1e})", value, form, form.error),
+        None => value,
+    }
+}
+
+/// Runs a REPL command, the text after a leading `:`, and returns a message
+/// describing the outcome.
+///
+/// * `:float` evaluates in binary floating point, with matrices.
+/// * `:decimal [scale] [rounding]` evaluates in decimal arithmetic, keeping
+///   `scale` decimal places and rounding with `half-even`, `half-up` or
+///   `truncate`.
+/// * `:interval` evaluates in interval arithmetic.
+/// * `:mod n` evaluates in the integers modulo `n`, forgetting the variables
+///   assigned modulo another number.
+/// * `:programmer [type] [overflow]` evaluates in integers of `type`, from
+///   `u8` to `u64` or `i8` to `i64`, whose results out of range `wrap`,
+///   `saturate` or are `checked` errors.
+/// * `:uncertainty` propagates the uncertainties of measurements `x ± dx`.
+/// * `:units` evaluates quantities with units of measure, like `9.81 m/s^2`.
+/// * `:format [format]` replaces how numbers are written in floating point
+///   and units mode, as in `:format fixed 2 group ,`, or shows the format.
+/// * `:angle [unit]` measures the angles of trigonometric functions in `rad`,
+///   `deg` or `grad`, or shows the unit.
+/// * `:implicit [on|off]` sets whether juxtaposition such as `2x` multiplies,
+///   or shows the setting.
+/// * `:recognize [tolerance]` shows the closed forms, such as `pi/4`, that
+///   floating point results are recognized as within a relative `tolerance`,
+///   and `:recognize off` stops.
+/// * `:constants [query]` lists the library constants whose name or
+///   description contains `query`.
+fn run_command(session: &mut Session, command: &str) -> Result<String, String> {
+    let mut words = command.split_whitespace();
+    match words.next() {
+        Some("float") => {
+            session.mode = Mode::Float;
+            Ok("Floating point mode".to_string())
+        }
+        Some("decimal") => {
+            let mut context = DecimalContext::default();
+            if let Some(scale) = words.next() {
+                context.scale = scale
+                    .parse()
+                    .map_err(|_| format!("Expected a number of decimal places, not '{}'.", scale))?;
+            }
+            if let Some(rounding) = words.next() {
+                context.rounding = rounding.parse()?;
+            }
+            session.mode = Mode::Decimal(context);
+            Ok(format!("Decimal mode ({})", context))
+        }
+        Some("interval") => {
+            session.mode = Mode::Interval;
+            Ok("Interval mode".to_string())
+        }
+        Some("mod") => {
+            let modulus = words.next().ok_or("Expected a modulus, as in :mod 101.")?;
+            let arithmetic = ModularArithmetic::new(
+                modulus
+                    .parse()
+                    .map_err(|_| format!("Expected a whole number modulus, not '{}'.", modulus))?,
+            )?;
+            session
+                .modular_memory
+                .retain(|_, residue| residue.modulus() == arithmetic.modulus());
+            session.mode = Mode::Modular(arithmetic);
+            Ok(format!("Modular mode (mod {})", arithmetic.modulus()))
+        }
+        Some("programmer") => {
+            let mut arithmetic = ProgrammerArithmetic::default();
+            if let Some(kind) = words.next() {
+                arithmetic.kind = kind.parse()?;
+            }
+            if let Some(overflow) = words.next() {
+                arithmetic.overflow = overflow.parse()?;
+            }
+            session.mode = Mode::Programmer(arithmetic);
+            Ok(format!("Programmer mode ({})", arithmetic))
+        }
+        Some("uncertainty") => {
+            session.mode = Mode::Uncertainty;
+            Ok("Uncertainty mode".to_string())
+        }
+        Some("units") => {
+            session.mode = Mode::Units;
+            Ok("Units mode".to_string())
+        }
+        Some("format") => {
+            let spec = words.collect::<Vec<&str>>().join(" ");
+            if !spec.is_empty() {
+                let mut format = NumberFormat::default();
+                format.apply(&spec)?;
+                session.format = format;
+            }
+            Ok(format!("Format: {}", session.format))
+        }
+        Some("angle") => {
+            if let Some(unit) = words.next() {
+                session.angle = unit.parse()?;
+            }
+            Ok(format!("Angles in {}", session.angle))
+        }
+        Some("implicit") => {
+            match words.next() {
+                Some("on") => session.multiplication = Multiplication::Implicit,
+                Some("off") => session.multiplication = Multiplication::Explicit,
+                Some(setting) => return Err(format!("Expected on or off, not '{}'.", setting)),
+                None => {}
+            }
+            match session.multiplication {
+                Multiplication::Implicit => Ok("Implicit multiplication is on".to_string()),
+                Multiplication::Explicit => Ok("Implicit multiplication is off".to_string()),
+            }
+        }
+        Some("recognize") => match words.next() {
+            Some("off") => {
+                session.recognition = None;
+                Ok("Not recognizing closed forms".to_string())
+            }
+            tolerance => {
+                let tolerance = match tolerance {
+                    Some(tolerance) => tolerance
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|&tolerance| tolerance >= 0.0)
+                        .ok_or_else(|| format!("Expected a tolerance, not '{}'.", tolerance))?,
+                    None => DEFAULT_TOLERANCE,
+                };
+                session.recognition = Some(tolerance);
+                Ok(format!("Recognizing closed forms within a relative error of {:e}", tolerance))
+            }
+        },
+        Some("constants") => {
+            let query = words.collect::<Vec<&str>>().join(" ");
+            let found = constants::search(&query);
+            if found.is_empty() {
+                Err(format!("No constants match '{}'.", query))
+            } else {
+                Ok(unlines(found.iter().map(|constant| constant.to_string()).collect())
+                    .trim_start()
+                    .to_string())
+            }
+        }
+        _ => Err(format!("Unknown command :{}", command)),
+    }
+}